@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE filters ADD COLUMN filter_type text not null default 'Peaking';
+ALTER TABLE filters ADD COLUMN slope real;
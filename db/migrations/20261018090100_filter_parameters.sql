@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE filters ADD COLUMN freq_target integer;
+ALTER TABLE filters ADD COLUMN q_target real;
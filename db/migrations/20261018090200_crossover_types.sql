@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE speakers_settings_for_ui ADD COLUMN crossover_type text not null default 'Butterworth';
+ALTER TABLE speakers_settings_for_ui ADD COLUMN crossover_speaker_order integer not null default 4;
+ALTER TABLE speakers_settings_for_ui ADD COLUMN crossover_subwoofer_order integer not null default 4;
+ALTER TABLE speakers_for_camilla ADD COLUMN crossover_type text not null default 'Butterworth';
+ALTER TABLE speakers_for_camilla ADD COLUMN crossover_speaker_order integer not null default 4;
+ALTER TABLE speakers_for_camilla ADD COLUMN crossover_subwoofer_order integer not null default 4;
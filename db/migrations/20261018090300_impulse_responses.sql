@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE if not exists impulse_responses (
+            filename text not null PRIMARY KEY,
+            path text not null,
+            file_type text not null,
+            sample_format text,
+            sample_rate integer not null,
+            channels integer not null,
+            length integer not null,
+            upload_date text not null);
+ALTER TABLE speakers_settings_for_ui ADD COLUMN impulse_response text;
+ALTER TABLE speakers_settings_for_ui ADD COLUMN impulse_response_channel integer not null default 0;
+ALTER TABLE speakers_for_camilla ADD COLUMN impulse_response text;
+ALTER TABLE speakers_for_camilla ADD COLUMN impulse_response_channel integer not null default 0;
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE speakers_settings_for_ui ADD COLUMN inverted integer not null default 0;
+ALTER TABLE speakers_for_camilla ADD COLUMN inverted integer not null default 0;
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE versions ADD COLUMN loudness_reference_level real;
+ALTER TABLE versions ADD COLUMN loudness_high_boost real;
+ALTER TABLE versions ADD COLUMN loudness_low_boost real;
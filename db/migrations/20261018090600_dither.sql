@@ -0,0 +1,3 @@
+-- Add migration script here
+-- versions saved before dither was added keep playing without it; new versions choose their own
+ALTER TABLE versions ADD COLUMN dither text NOT NULL DEFAULT 'None';
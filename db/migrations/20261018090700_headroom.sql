@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE versions ADD COLUMN headroom_compensation integer NOT NULL DEFAULT 0;
@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE if not exists global_filters (
+    version integer not null, 
+    filter_index integer not null, 
+    freq integer not null, 
+    gain real not null, 
+    q real not null, 
+    filter_type text not null default 'Peaking',
+    slope real,
+    freq_target integer,
+    q_target real,
+    PRIMARY KEY (version, filter_index)
+);
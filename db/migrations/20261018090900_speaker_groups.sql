@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE if not exists speaker_groups (
+    version integer not null,
+    group_index integer not null,
+    group_name text not null,
+    crossover integer,
+    gain real,
+    PRIMARY KEY (version, group_name)
+);
+CREATE TABLE if not exists speaker_group_members (
+    version integer not null,
+    group_name text not null,
+    speaker text not null,
+    PRIMARY KEY (version, speaker)
+);
@@ -0,0 +1,5 @@
+-- Add migration script here
+ALTER TABLE filters ADD COLUMN enabled integer not null default 1;
+ALTER TABLE global_filters ADD COLUMN enabled integer not null default 1;
+ALTER TABLE speakers_settings_for_ui ADD COLUMN crossover_enabled integer not null default 1;
+ALTER TABLE speaker_groups ADD COLUMN crossover_enabled integer not null default 1;
@@ -0,0 +1,20 @@
+-- Add migration script here
+CREATE TABLE if not exists target_curves (
+    name text not null,
+    point_index integer not null,
+    freq real not null,
+    gain real not null,
+    PRIMARY KEY (name, point_index)
+);
+INSERT OR IGNORE INTO target_curves (name, point_index, freq, gain) VALUES
+    ('flat', 0, 20.0, 0.0),
+    ('flat', 1, 20000.0, 0.0),
+    ('harman', 0, 20.0, 6.0),
+    ('harman', 1, 60.0, 5.5),
+    ('harman', 2, 100.0, 4.0),
+    ('harman', 3, 200.0, 1.0),
+    ('harman', 4, 300.0, 0.0),
+    ('harman', 5, 1000.0, -0.5),
+    ('harman', 6, 10000.0, -3.0),
+    ('harman', 7, 20000.0, -4.5);
+ALTER TABLE speakers_settings_for_ui ADD COLUMN target text;
@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE if not exists phase_alignment (
+    version integer not null,
+    filter_index integer not null,
+    speaker text not null,
+    freq integer not null,
+    allpass_order text not null default 'Second',
+    q real not null,
+    PRIMARY KEY (version, filter_index)
+);
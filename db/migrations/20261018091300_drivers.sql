@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE if not exists drivers (
+    version integer not null,
+    driver_index integer not null,
+    speaker text not null,
+    driver text not null,
+    highpass integer,
+    lowpass integer,
+    crossover_type text not null default 'Butterworth',
+    crossover_order integer not null default 4,
+    delay real not null default 0,
+    gain real not null default 0,
+    inverted boolean not null default false,
+    PRIMARY KEY (version, driver_index)
+);
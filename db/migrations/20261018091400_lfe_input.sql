@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE versions ADD COLUMN lfe_enabled integer NOT NULL DEFAULT 1;
+ALTER TABLE versions ADD COLUMN lfe_channel integer;
+ALTER TABLE versions ADD COLUMN lfe_gain real NOT NULL DEFAULT 10;
@@ -0,0 +1,10 @@
+-- Add migration script here
+ALTER TABLE versions ADD COLUMN input_layout text NOT NULL DEFAULT 'SpeakerOrder';
+ALTER TABLE speakers_settings_for_ui ADD COLUMN role text;
+ALTER TABLE speakers_for_camilla ADD COLUMN role text;
+CREATE TABLE if not exists custom_input_layout (
+    version integer not null,
+    channel_index integer not null,
+    role text not null,
+    PRIMARY KEY (version, channel_index)
+);
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE speakers_settings_for_ui ADD COLUMN output_port integer;
+ALTER TABLE speakers_for_camilla ADD COLUMN output_port integer;
+ALTER TABLE drivers ADD COLUMN output_port integer;
@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE versions ADD COLUMN upmix_center_gain real;
+ALTER TABLE versions ADD COLUMN upmix_surround_gain real;
+ALTER TABLE versions ADD COLUMN upmix_surround_delay real;
+ALTER TABLE versions ADD COLUMN upmix_lfe_gain real;
+ALTER TABLE versions ADD COLUMN upmix_lfe_lowpass integer;
@@ -0,0 +1,560 @@
+use crate::processor::{Filter, PeqType};
+use crate::response::Biquad;
+use rocket::serde::Deserialize;
+
+/// resolution of the grid the fit is evaluated on
+const POINTS_PER_OCTAVE: f64 = 24.0;
+/// q of the shelves; only their frequency and gain are fitted
+const SHELF_Q: f32 = 0.707;
+/// filters with less gain than this are dropped once the fit is done
+const MIN_GAIN: f64 = 0.1;
+/// a filter is only added when it lowers the rms error by at least this many dB
+const MIN_IMPROVEMENT: f64 = 0.01;
+const REFINE_ROUNDS: usize = 40;
+/// every filter is re-optimised after each addition, so the fit slows down with the square of this
+const MAX_FILTERS: usize = 20;
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase", default)]
+/// Bounds on the fitted filters
+pub struct AutoEqLimits {
+    pub max_boost: f32, //dB; boosting into nulls wastes headroom, so this is usually small
+    pub max_cut: f32,   //dB
+    pub min_q: f32,
+    pub max_q: f32,
+    pub min_freq: i32, //only this range of the measurement is corrected
+    pub max_freq: i32,
+    pub shelves: bool, //allows a low and a high shelf in addition to peaking filters
+}
+
+impl Default for AutoEqLimits {
+    fn default() -> Self {
+        Self {
+            max_boost: 3.0,
+            max_cut: 12.0,
+            min_q: 0.5,
+            max_q: 10.0,
+            min_freq: 20,
+            max_freq: 20000,
+            shelves: true,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub struct AutoEqRequest {
+    pub measurement: String, //REW text export or csv of frequency, SPL
+    #[serde(default)]
+    pub target: String, //same format as the measurement; when empty the speaker's target curve is used
+    pub max_filters: usize,
+    #[serde(default)]
+    pub limits: AutoEqLimits,
+}
+
+/// rejects limits the fit can't work within, before any of them are used as clamp bounds
+fn validate_request(request: &AutoEqRequest) -> Result<(), String> {
+    let limits = &request.limits;
+    if request.max_filters > MAX_FILTERS {
+        Err(format!(
+            "Max filters {} must be at most {}",
+            request.max_filters, MAX_FILTERS
+        ))
+    } else if limits.max_boost < 0.0 || limits.max_cut < 0.0 {
+        Err(format!(
+            "Max boost {} and max cut {} must not be negative",
+            limits.max_boost, limits.max_cut
+        ))
+    } else if limits.min_q <= 0.0 {
+        Err(format!("Min Q {} must be positive", limits.min_q))
+    } else if limits.min_q > limits.max_q {
+        Err(format!(
+            "Min Q {} must not be above max Q {}",
+            limits.min_q, limits.max_q
+        ))
+    } else if limits.min_freq <= 0 || limits.min_freq >= limits.max_freq {
+        Err(format!(
+            "Frequency range {} to {} Hz must be positive and increasing",
+            limits.min_freq, limits.max_freq
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Reads frequency and level pairs from REW text exports or csv files.
+/// Comments (`*` or `#`), headers and other lines that don't start with two numbers are skipped;
+/// extra columns such as phase are ignored
+pub fn parse_response_text(text: &str) -> Vec<(f64, f64)> {
+    let mut points: Vec<(f64, f64)> = text
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.starts_with('*') && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut values = line
+                .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse::<f64>());
+            match (values.next(), values.next()) {
+                (Some(Ok(freq)), Some(Ok(level))) if freq > 0.0 => Some((freq, level)),
+                _ => None,
+            }
+        })
+        .collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    points
+}
+
+/// level at the frequency, interpolated linearly on a log frequency axis and held past the ends
+pub fn interpolate(points: &[(f64, f64)], freq: f64) -> f64 {
+    match points.iter().position(|(f, _)| *f >= freq) {
+        None => points[points.len() - 1].1,
+        Some(0) => points[0].1,
+        Some(i) => {
+            let (f0, l0) = points[i - 1];
+            let (f1, l1) = points[i];
+            l0 + (l1 - l0) * (freq / f0).ln() / (f1 / f0).ln()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Band {
+    filter_type: PeqType,
+    freq: f64,
+    gain: f64,
+    q: f64,
+}
+
+impl Band {
+    fn biquad(&self, samplerate: i32) -> Biquad {
+        let (freq, gain, q) = (self.freq as f32, self.gain as f32, self.q as f32);
+        match self.filter_type {
+            PeqType::Lowshelf => Biquad::lowshelf(freq, gain, q, None, samplerate),
+            PeqType::Highshelf => Biquad::highshelf(freq, gain, q, None, samplerate),
+            _ => Biquad::peaking(freq, gain, q, samplerate),
+        }
+    }
+    /// magnitude in dB at each frequency
+    fn response(&self, frequencies: &[f64], samplerate: i32) -> Vec<f64> {
+        let biquad = self.biquad(samplerate);
+        frequencies
+            .iter()
+            .map(|freq| {
+                let ((re, im), _) = biquad.evaluate(*freq, samplerate);
+                10.0 * (re * re + im * im).log10()
+            })
+            .collect()
+    }
+}
+
+/// Fits the filters being optimised, keeping each band's response so a change to one band
+/// only needs that band evaluated again
+struct Fit<'a> {
+    frequencies: &'a [f64],
+    error: &'a [f64], //measurement minus target, before any filters
+    limits: &'a AutoEqLimits,
+    samplerate: i32,
+    bands: Vec<(Band, Vec<f64>)>,
+}
+
+impl<'a> Fit<'a> {
+    fn rms(&self, replace: Option<(usize, &[f64])>, add: Option<&[f64]>) -> f64 {
+        let sum: f64 = (0..self.frequencies.len())
+            .map(|i| {
+                let filtered: f64 = self
+                    .bands
+                    .iter()
+                    .enumerate()
+                    .map(|(b, (_, response))| match replace {
+                        Some((r, replacement)) if r == b => replacement[i],
+                        _ => response[i],
+                    })
+                    .sum::<f64>()
+                    + add.map(|a| a[i]).unwrap_or(0.0);
+                (self.error[i] + filtered).powi(2)
+            })
+            .sum();
+        (sum / self.frequencies.len() as f64).sqrt()
+    }
+    fn residual(&self) -> Vec<f64> {
+        (0..self.frequencies.len())
+            .map(|i| self.error[i] + self.bands.iter().map(|(_, r)| r[i]).sum::<f64>())
+            .collect()
+    }
+    fn clamp(&self, band: Band) -> Band {
+        let limits = self.limits;
+        Band {
+            freq: band.freq.clamp(
+                limits.min_freq as f64,
+                self.frequencies[self.frequencies.len() - 1],
+            ),
+            gain: band
+                .gain
+                .clamp(-limits.max_cut as f64, limits.max_boost as f64),
+            q: match band.filter_type {
+                PeqType::Peaking => band.q.clamp(limits.min_q as f64, limits.max_q as f64),
+                _ => band.q,
+            },
+            ..band
+        }
+    }
+    /// peaking filter centered on the largest correctable deviation, as wide as the deviation
+    fn peaking_candidate(&self) -> Option<Band> {
+        let residual = self.residual();
+        let correction =
+            |e: f64| (-e).clamp(-self.limits.max_cut as f64, self.limits.max_boost as f64);
+        let (peak, _) = residual
+            .iter()
+            .enumerate()
+            .max_by(|a, b| correction(*a.1).abs().total_cmp(&correction(*b.1).abs()))?;
+        let half = residual[peak] / 2.0;
+        let same_side = |e: f64| (e - half) * residual[peak].signum() > 0.0;
+        let low = (0..peak)
+            .rev()
+            .find(|i| !same_side(residual[*i]))
+            .unwrap_or(0);
+        let high = (peak..residual.len())
+            .find(|i| !same_side(residual[*i]))
+            .unwrap_or(residual.len() - 1);
+        //ratio of the frequencies at the edges of the deviation
+        let bandwidth = self.frequencies[high] / self.frequencies[low];
+        let q = if bandwidth > 1.0 {
+            bandwidth.sqrt() / (bandwidth - 1.0)
+        } else {
+            self.limits.max_q as f64
+        };
+        Some(self.clamp(Band {
+            filter_type: PeqType::Peaking,
+            freq: self.frequencies[peak],
+            gain: correction(residual[peak]),
+            q,
+        }))
+    }
+    /// shelf at the corner frequency that best corrects the average deviation below
+    /// (or above) it, tried every third of an octave
+    fn shelf_candidate(&self, filter_type: PeqType) -> Option<Band> {
+        let residual = self.residual();
+        let n = self.frequencies.len();
+        (1..n - 1)
+            .step_by((POINTS_PER_OCTAVE / 3.0) as usize)
+            .map(|corner| {
+                let side = match filter_type {
+                    PeqType::Lowshelf => &residual[..corner],
+                    _ => &residual[corner..],
+                };
+                self.clamp(Band {
+                    filter_type,
+                    freq: self.frequencies[corner],
+                    gain: -side.iter().sum::<f64>() / side.len() as f64,
+                    q: SHELF_Q as f64,
+                })
+            })
+            .map(|band| {
+                let rms = self.rms(
+                    None,
+                    Some(&band.response(self.frequencies, self.samplerate)),
+                );
+                (band, rms)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(band, _)| band)
+    }
+    /// coordinate descent on every band, halving the steps whenever nothing improves
+    fn refine(&mut self) {
+        let (mut freq_step, mut gain_step, mut q_step) = (2.0_f64.powf(1.0 / 6.0), 1.0, 1.25_f64);
+        let mut rms = self.rms(None, None);
+        for _ in 0..REFINE_ROUNDS {
+            let mut improved = false;
+            for b in 0..self.bands.len() {
+                let band = self.bands[b].0;
+                let mut moves = vec![
+                    Band {
+                        freq: band.freq * freq_step,
+                        ..band
+                    },
+                    Band {
+                        freq: band.freq / freq_step,
+                        ..band
+                    },
+                    Band {
+                        gain: band.gain + gain_step,
+                        ..band
+                    },
+                    Band {
+                        gain: band.gain - gain_step,
+                        ..band
+                    },
+                ];
+                if band.filter_type == PeqType::Peaking {
+                    moves.push(Band {
+                        q: band.q * q_step,
+                        ..band
+                    });
+                    moves.push(Band {
+                        q: band.q / q_step,
+                        ..band
+                    });
+                }
+                for candidate in moves {
+                    let candidate = self.clamp(candidate);
+                    let response = candidate.response(self.frequencies, self.samplerate);
+                    let candidate_rms = self.rms(Some((b, &response)), None);
+                    if candidate_rms < rms {
+                        rms = candidate_rms;
+                        self.bands[b] = (candidate, response);
+                        improved = true;
+                    }
+                }
+            }
+            if !improved {
+                freq_step = freq_step.sqrt();
+                gain_step /= 2.0;
+                q_step = q_step.sqrt();
+            }
+        }
+    }
+}
+
+/// Fits up to `max_filters` peaking filters, along with a low and a high shelf when allowed,
+/// to bring the measurement toward the target.  Filters are added one at a time where they
+/// help the most and every filter is re-optimised after each addition.
+/// The target is moved to the average level of the measurement, so only its shape matters;
+/// an empty target is flat
+pub fn auto_eq(
+    request: &AutoEqRequest,
+    target: &[(f64, f64)],
+    speaker: &str,
+    samplerate: i32,
+) -> Result<Vec<Filter>, String> {
+    validate_request(request)?;
+    let limits = &request.limits;
+    let measurement = parse_response_text(&request.measurement);
+    let (Some(first), Some(last)) = (measurement.first(), measurement.last()) else {
+        return Err("Measurement has no frequency and level pairs".to_string());
+    };
+    let min_freq = first.0.max(limits.min_freq as f64);
+    let max_freq = last
+        .0
+        .min(limits.max_freq as f64)
+        .min(samplerate as f64 / 2.0 * 0.95);
+    if min_freq >= max_freq {
+        return Err(format!(
+            "Measurement does not cover {} to {} Hz",
+            limits.min_freq, limits.max_freq
+        ));
+    }
+    let steps = ((max_freq / min_freq).log2() * POINTS_PER_OCTAVE).ceil() as usize;
+    let frequencies: Vec<f64> = (0..=steps)
+        .map(|i| min_freq * (max_freq / min_freq).powf(i as f64 / steps as f64))
+        .collect();
+    let mut error: Vec<f64> = frequencies
+        .iter()
+        .map(|f| {
+            interpolate(&measurement, *f)
+                - if target.is_empty() {
+                    0.0
+                } else {
+                    interpolate(target, *f)
+                }
+        })
+        .collect();
+    let offset = error.iter().sum::<f64>() / error.len() as f64;
+    error.iter_mut().for_each(|e| *e -= offset);
+
+    let mut fit = Fit {
+        frequencies: &frequencies,
+        error: &error,
+        limits,
+        samplerate,
+        bands: vec![],
+    };
+    while fit.bands.len() < request.max_filters {
+        let has = |filter_type| fit.bands.iter().any(|(b, _)| b.filter_type == filter_type);
+        let candidates: Vec<Band> = fit
+            .peaking_candidate()
+            .into_iter()
+            .chain(
+                [PeqType::Lowshelf, PeqType::Highshelf]
+                    .into_iter()
+                    .filter(|t| limits.shelves && !has(*t))
+                    .filter_map(|t| fit.shelf_candidate(t)),
+            )
+            .collect();
+        let rms = fit.rms(None, None);
+        let best = candidates
+            .into_iter()
+            .map(|band| {
+                let response = band.response(&frequencies, samplerate);
+                let candidate_rms = fit.rms(None, Some(&response));
+                (band, response, candidate_rms)
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2));
+        match best {
+            Some((band, response, candidate_rms)) if rms - candidate_rms > MIN_IMPROVEMENT => {
+                fit.bands.push((band, response));
+                fit.refine();
+            }
+            _ => break,
+        }
+    }
+    let mut bands: Vec<Band> = fit
+        .bands
+        .into_iter()
+        .map(|(band, _)| band)
+        .filter(|band| band.gain.abs() >= MIN_GAIN)
+        .collect();
+    bands.sort_by(|a, b| a.freq.total_cmp(&b.freq));
+    Ok(bands
+        .into_iter()
+        .map(|band| Filter {
+            freq: band.freq.round() as i32,
+            gain: (band.gain * 10.0).round() as f32 / 10.0,
+            q: (band.q * 100.0).round() as f32 / 100.0,
+            speaker: speaker.to_string(),
+            filter_type: band.filter_type,
+            slope: None,
+            freq_target: None,
+            q_target: None,
+            enabled: true,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{auto_eq, parse_response_text, AutoEqLimits, AutoEqRequest, Band};
+    use crate::processor::PeqType;
+
+    /// 75dB measurement with a room mode, written as a REW text export
+    fn measurement_with_mode(freq: f64, gain: f64, q: f64) -> String {
+        let frequencies: Vec<f64> = (0..=200)
+            .map(|i| 20.0 * 1000.0_f64.powf(i as f64 / 200.0))
+            .collect();
+        let mode = Band {
+            filter_type: PeqType::Peaking,
+            freq,
+            gain,
+            q,
+        }
+        .response(&frequencies, 48000);
+        std::iter::once(
+            "* Measurement data measured by REW\n* Freq(Hz), SPL(dB), Phase(degrees)".to_string(),
+        )
+        .chain(
+            frequencies
+                .iter()
+                .zip(mode.iter())
+                .map(|(f, l)| format!("{:.3}, {:.3}, 0.0", f, 75.0 + l)),
+        )
+        .collect::<Vec<String>>()
+        .join("\n")
+    }
+
+    #[test]
+    fn check_parse_response_text() {
+        let text = "* comment\nFreq(Hz) SPL(dB)\n100\t80.5\n50;78.0\n\n20, 70.0, 12.0";
+        assert_eq!(
+            parse_response_text(text),
+            vec![(20.0, 70.0), (50.0, 78.0), (100.0, 80.5)]
+        );
+    }
+    #[test]
+    fn check_auto_eq_removes_mode() {
+        let request = AutoEqRequest {
+            measurement: measurement_with_mode(60.0, 8.0, 5.0),
+            target: String::new(),
+            max_filters: 3,
+            limits: AutoEqLimits {
+                shelves: false,
+                ..AutoEqLimits::default()
+            },
+        };
+        let filters = auto_eq(&request, &[], "sub1", 48000).unwrap();
+        let largest = filters
+            .iter()
+            .min_by(|a, b| a.gain.total_cmp(&b.gain))
+            .unwrap();
+        assert_eq!(largest.speaker, "sub1");
+        assert_eq!(largest.filter_type, PeqType::Peaking);
+        assert!((largest.freq - 60).abs() <= 3);
+        assert!((largest.gain + 8.0).abs() < 1.0);
+        assert!((largest.q - 5.0).abs() < 1.0);
+    }
+    #[test]
+    fn check_auto_eq_limits() {
+        //a dip can only be boosted by max_boost
+        let request = AutoEqRequest {
+            measurement: measurement_with_mode(200.0, -10.0, 2.0),
+            target: String::new(),
+            max_filters: 1,
+            limits: AutoEqLimits::default(),
+        };
+        let filters = auto_eq(&request, &[], "l", 48000).unwrap();
+        assert_eq!(filters.len(), 1);
+        assert!(filters[0].gain <= 3.0);
+        assert!(auto_eq(
+            &AutoEqRequest {
+                measurement: "no data".to_string(),
+                ..request
+            },
+            &[],
+            "l",
+            48000
+        )
+        .is_err());
+    }
+    #[test]
+    fn check_auto_eq_invalid_limits() {
+        let request = |max_filters: usize, limits: AutoEqLimits| AutoEqRequest {
+            measurement: measurement_with_mode(60.0, 8.0, 5.0),
+            target: String::new(),
+            max_filters,
+            limits,
+        };
+        let defaults = AutoEqLimits::default();
+        let invalid = [
+            request(1000, defaults),
+            request(
+                3,
+                AutoEqLimits {
+                    max_cut: -3.0,
+                    ..defaults
+                },
+            ),
+            request(
+                3,
+                AutoEqLimits {
+                    max_boost: -1.0,
+                    ..defaults
+                },
+            ),
+            request(
+                3,
+                AutoEqLimits {
+                    min_q: 0.0,
+                    ..defaults
+                },
+            ),
+            request(
+                3,
+                AutoEqLimits {
+                    min_q: 5.0,
+                    max_q: 1.0,
+                    ..defaults
+                },
+            ),
+            request(
+                3,
+                AutoEqLimits {
+                    min_freq: 0,
+                    ..defaults
+                },
+            ),
+        ];
+        for request in invalid.iter() {
+            //rejected rather than panicking in the fit
+            assert!(auto_eq(request, &[], "l", 48000).is_err());
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::processor::DeviceType;
 use rocket::serde::Serialize;
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
@@ -38,12 +39,65 @@ pub struct Devices {
     resampler: ResamplerConfig,
 }
 
+/// Properties of a device that don't depend on the channel counts of a configuration
+impl DeviceType {
+    /// rate every filter is designed for
+    pub fn samplerate(&self) -> i32 {
+        96000 //high sample rate; should be transparent
+    }
+
+    fn playback_format(&self) -> &'static str {
+        match self {
+            DeviceType::OktoDac8 | DeviceType::ToppingDm7 => "S32LE",
+            DeviceType::MotuMk5 => "S24LE3",
+            DeviceType::HDMI => "S24LE",
+        }
+    }
+
+    /// outputs the playback device has, so the highest output port is one less
+    pub fn playback_channels(&self) -> usize {
+        match self {
+            DeviceType::OktoDac8 | DeviceType::ToppingDm7 | DeviceType::HDMI => 8,
+            DeviceType::MotuMk5 => 22, //analog, phones, S/PDIF and ADAT outputs
+        }
+    }
+
+    /// bits of resolution the playback device takes, matching its playback format
+    pub fn playback_bits(&self) -> i32 {
+        match self {
+            DeviceType::OktoDac8 | DeviceType::ToppingDm7 => 32,
+            DeviceType::MotuMk5 | DeviceType::HDMI => 24,
+        }
+    }
+}
+
 //in the future, select a device
 impl Devices {
+    pub fn new(device: &DeviceType, input_channels: usize, output_channels: usize) -> Self {
+        match device {
+            DeviceType::MotuMk5 => Devices::motu_mk5(input_channels, output_channels),
+            DeviceType::OktoDac8 => Devices::okto_dac8(input_channels, output_channels),
+            DeviceType::ToppingDm7 => Devices::topping_dm7(input_channels, output_channels),
+            DeviceType::HDMI => Devices::hdmi_osmc_pi(input_channels, output_channels),
+        }
+    }
+
+    pub fn samplerate(&self) -> i32 {
+        self.samplerate
+    }
+
+    pub fn capture_channels(&self) -> usize {
+        self.capture.channels
+    }
+
+    pub fn playback_channels(&self) -> usize {
+        self.playback.channels
+    }
+
     // will input channels be consistent??  will I always get 8 channels of PCM over HDMI even if its stereo?
     pub fn okto_dac8(input_channels: usize, output_channels: usize) -> Self {
         Self {
-            samplerate: 96000, //high sample rate; should be transparent
+            samplerate: DeviceType::OktoDac8.samplerate(),
             chunksize: 2048,
             queuelimit: 4,
             capture_samplerate: 48000, //any source needs to resample 44.1 to 48
@@ -57,7 +111,7 @@ impl Devices {
                 device_type: "Alsa".to_string(),
                 channels: output_channels,
                 device: "hw:DAC8PRO".to_string(),
-                format: Some("S32LE".to_string()),
+                format: Some(DeviceType::OktoDac8.playback_format().to_string()),
             },
             resampler: ResamplerConfig {
                 resampler_type: "Synchronous".to_string(),
@@ -67,7 +121,7 @@ impl Devices {
 
     pub fn hdmi_osmc_pi(input_channels: usize, output_channels: usize) -> Self {
         Self {
-            samplerate: 96000, //high sample rate; should be transparent
+            samplerate: DeviceType::HDMI.samplerate(),
             chunksize: 4096,
             queuelimit: 4,
             capture_samplerate: 48000, //any source needs to resample 44.1 to 48
@@ -81,7 +135,7 @@ impl Devices {
                 device_type: "Alsa".to_string(),
                 channels: output_channels,
                 device: "sysdefault:vc4hdmi".to_string(), //looks like sysdefault is required?  very odd...
-                format: Some("S24LE".to_string()),
+                format: Some(DeviceType::HDMI.playback_format().to_string()),
             },
             resampler: ResamplerConfig {
                 //may need to add a `capture_samplerate` as well, we shall see
@@ -92,7 +146,7 @@ impl Devices {
 
     pub fn topping_dm7(input_channels: usize, output_channels: usize) -> Self {
         Self {
-            samplerate: 96000, //high sample rate; should be transparent
+            samplerate: DeviceType::ToppingDm7.samplerate(),
             chunksize: 2048,
             queuelimit: 4,
             capture_samplerate: 48000, //any source needs to resample 44.1 to 48
@@ -106,7 +160,7 @@ impl Devices {
                 device_type: "Alsa".to_string(),
                 channels: output_channels,
                 device: "hw:DM7".to_string(),
-                format: Some("S32LE".to_string()),
+                format: Some(DeviceType::ToppingDm7.playback_format().to_string()),
             },
             resampler: ResamplerConfig {
                 resampler_type: "Synchronous".to_string(),
@@ -115,7 +169,7 @@ impl Devices {
     }
     pub fn motu_mk5(input_channels: usize, output_channels: usize) -> Self {
         Self {
-            samplerate: 96000, //high sample rate; should be transparent
+            samplerate: DeviceType::MotuMk5.samplerate(),
             chunksize: 2048,
             queuelimit: 4,
             capture_samplerate: 48000, //any source needs to resample 44.1 to 48
@@ -129,7 +183,7 @@ impl Devices {
                 device_type: "Alsa".to_string(),
                 channels: output_channels,
                 device: "hw:UltraLitemk5".to_string(),
-                format: Some("S24LE3".to_string()),
+                format: Some(DeviceType::MotuMk5.playback_format().to_string()),
             },
             resampler: ResamplerConfig {
                 resampler_type: "Synchronous".to_string(),
@@ -137,3 +191,44 @@ impl Devices {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Devices;
+    use crate::processor::DeviceType;
+
+    #[test]
+    fn check_playback_bits() {
+        assert_eq!(DeviceType::OktoDac8.playback_bits(), 32);
+        assert_eq!(DeviceType::ToppingDm7.playback_bits(), 32);
+        assert_eq!(DeviceType::HDMI.playback_bits(), 24);
+        assert_eq!(DeviceType::MotuMk5.playback_bits(), 24);
+    }
+    #[test]
+    fn check_playback_channels() {
+        assert_eq!(DeviceType::OktoDac8.playback_channels(), 8);
+        assert_eq!(DeviceType::MotuMk5.playback_channels(), 22);
+    }
+    #[test]
+    fn check_device_info_matches_config() {
+        for device in [
+            DeviceType::OktoDac8,
+            DeviceType::ToppingDm7,
+            DeviceType::HDMI,
+            DeviceType::MotuMk5,
+        ] {
+            let devices = Devices::new(&device, 8, 8);
+            assert_eq!(devices.samplerate(), device.samplerate());
+            assert_eq!(
+                devices.playback.format.as_deref(),
+                Some(device.playback_format())
+            );
+            let format_bits = if device.playback_format().starts_with("S24") {
+                24
+            } else {
+                32
+            };
+            assert_eq!(device.playback_bits(), format_bits);
+        }
+    }
+}
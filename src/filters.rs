@@ -1,5 +1,17 @@
+use crate::impulses::{ImpulseFileType, ImpulseResponse, SampleFormat};
+use crate::processor::default_crossover_order;
+use crate::processor::CrossoverFamily;
+use crate::processor::DitherType;
+use crate::processor::Driver;
 use crate::processor::Filter;
+use crate::processor::Loudness;
+use crate::processor::PeqType;
+use crate::processor::SelectedDistanceType;
 use crate::processor::Speaker;
+use crate::processor::SpeakerGroup;
+use crate::processor::Upmix;
+use crate::processor::{AllpassOrder, PhaseAlignment};
+use crate::response::{crossover_sections, Biquad};
 use rocket::serde::Serialize;
 use std::collections::BTreeMap;
 pub fn delay_filter_name(speaker_name: &str) -> String {
@@ -13,12 +25,50 @@ pub fn peq_filter_name(speaker_name: &str, peq_index: usize) -> String {
     format!("peq_{}_{}", speaker_name, peq_index)
 }
 
+pub fn loudness_filter_name() -> String {
+    "loudness".to_string()
+}
+
+pub fn dither_filter_name() -> String {
+    "dither".to_string()
+}
+
+pub fn headroom_filter_name() -> String {
+    "headroom".to_string()
+}
+
+pub fn upmix_surround_delay_name() -> String {
+    "upmix_surround_delay".to_string()
+}
+
+pub fn upmix_lfe_lowpass_name() -> String {
+    "upmix_lfe_lowpass".to_string()
+}
+
+pub fn global_filter_name(peq_index: usize) -> String {
+    format!("global_peq_{}", peq_index)
+}
+
+pub fn allpass_filter_name(speaker_name: &str, index: usize) -> String {
+    format!("allpass_{}_{}", speaker_name, index)
+}
+
+pub fn conv_filter_name(speaker_name: &str) -> String {
+    format!("conv_{}", speaker_name)
+}
+
 pub fn crossover_speaker_name(speaker_name: &str) -> String {
     format!("crossover_speaker_{}", speaker_name)
 }
 pub fn crossover_subwoofer_name(speaker_name: &str) -> String {
     format!("crossover_subwoofer{}", speaker_name)
 }
+pub fn driver_highpass_name(driver_name: &str) -> String {
+    format!("driver_highpass_{}", driver_name)
+}
+pub fn driver_lowpass_name(driver_name: &str) -> String {
+    format!("driver_lowpass_{}", driver_name)
+}
 
 /// generates Biquad filters for crossovers for both speakers and subs
 pub fn create_crossover_filters(speakers: &[Speaker]) -> BTreeMap<String, SpeakerAdjust> {
@@ -27,7 +77,7 @@ pub fn create_crossover_filters(speakers: &[Speaker]) -> BTreeMap<String, Speake
             .iter()
             .filter(|s| !s.is_subwoofer)
             .filter(|s| s.crossover.is_some())
-            .map(|speaker| {
+            .flat_map(|speaker| {
                 vec![
                     (
                         crossover_speaker_name(&speaker.speaker),
@@ -35,8 +85,15 @@ pub fn create_crossover_filters(speakers: &[Speaker]) -> BTreeMap<String, Speake
                             filter_type: FilterType::BiquadCombo,
                             parameters: CrossoverParameters {
                                 freq: speaker.crossover.unwrap(), //already filtered out
-                                order: 4,
-                                crossover_type: CrossoverType::ButterworthHighpass,
+                                order: speaker.crossover_speaker_order,
+                                crossover_type: match speaker.crossover_type {
+                                    CrossoverFamily::Butterworth => {
+                                        CrossoverType::ButterworthHighpass
+                                    }
+                                    CrossoverFamily::LinkwitzRiley => {
+                                        CrossoverType::LinkwitzRileyHighpass
+                                    }
+                                },
                             },
                         }),
                     ),
@@ -46,43 +103,135 @@ pub fn create_crossover_filters(speakers: &[Speaker]) -> BTreeMap<String, Speake
                             filter_type: FilterType::BiquadCombo,
                             parameters: CrossoverParameters {
                                 freq: speaker.crossover.unwrap(), //already filtered out
-                                order: 4,
-                                crossover_type: CrossoverType::ButterworthLowpass,
+                                order: speaker.crossover_subwoofer_order,
+                                crossover_type: match speaker.crossover_type {
+                                    CrossoverFamily::Butterworth => {
+                                        CrossoverType::ButterworthLowpass
+                                    }
+                                    CrossoverFamily::LinkwitzRiley => {
+                                        CrossoverType::LinkwitzRileyLowpass
+                                    }
+                                },
                             },
                         }),
                     ),
                 ]
                 .into_iter()
-            })
-            .flatten(),
+            }),
     )
 }
 
+/// generates the band-pass of every driver of a multi-way speaker, as a highpass and a lowpass
+pub fn create_driver_filters(drivers: &[Driver]) -> BTreeMap<String, SpeakerAdjust> {
+    BTreeMap::from_iter(drivers.iter().flat_map(|driver| {
+        driver
+            .highpass
+            .map(|freq| {
+                (
+                    driver_highpass_name(&driver.driver),
+                    SpeakerAdjust::CrossoverFilter(CrossoverFilter {
+                        filter_type: FilterType::BiquadCombo,
+                        parameters: CrossoverParameters {
+                            freq,
+                            order: driver.crossover_order,
+                            crossover_type: match driver.crossover_type {
+                                CrossoverFamily::Butterworth => CrossoverType::ButterworthHighpass,
+                                CrossoverFamily::LinkwitzRiley => {
+                                    CrossoverType::LinkwitzRileyHighpass
+                                }
+                            },
+                        },
+                    }),
+                )
+            })
+            .into_iter()
+            .chain(driver.lowpass.map(|freq| {
+                (
+                    driver_lowpass_name(&driver.driver),
+                    SpeakerAdjust::CrossoverFilter(CrossoverFilter {
+                        filter_type: FilterType::BiquadCombo,
+                        parameters: CrossoverParameters {
+                            freq,
+                            order: driver.crossover_order,
+                            crossover_type: match driver.crossover_type {
+                                CrossoverFamily::Butterworth => CrossoverType::ButterworthLowpass,
+                                CrossoverFamily::LinkwitzRiley => {
+                                    CrossoverType::LinkwitzRileyLowpass
+                                }
+                            },
+                        },
+                    }),
+                )
+            }))
+    }))
+}
+
+/// Speakers as they are played on the outputs: a multi-way speaker is replaced by its drivers,
+/// whose delay and gain are added to the speaker's and whose polarity is relative to the speaker
+pub fn output_speakers(speakers: &[Speaker], drivers: &[Driver]) -> Vec<Speaker> {
+    speakers
+        .iter()
+        .flat_map(|speaker| {
+            let speaker_drivers: Vec<Speaker> = drivers
+                .iter()
+                .filter(|d| d.speaker == speaker.speaker)
+                .map(|d| Speaker {
+                    speaker: d.driver.clone(),
+                    crossover: None, //the speaker's crossover runs ahead of the fan out
+                    delay: speaker.delay + d.delay,
+                    gain: speaker.gain + d.gain,
+                    inverted: speaker.inverted != d.inverted,
+                    impulse_response: None, //the speaker's impulse runs ahead of the driver's PEQ
+                    impulse_response_channel: 0,
+                    output_port: d.output_port,
+                    ..speaker.clone()
+                })
+                .collect();
+            if speaker_drivers.is_empty() {
+                vec![speaker.clone()]
+            } else {
+                speaker_drivers
+            }
+        })
+        .collect()
+}
+
 /// generates PEQ filters
 pub fn create_output_filters(
     speakers: &[Speaker],
     peq_filters: &BTreeMap<&String, Vec<(usize, &Filter)>>,
+    impulse_filters: &BTreeMap<&String, (i32, &ImpulseResponse)>,
+    selected_distance: &SelectedDistanceType,
 ) -> BTreeMap<String, SpeakerAdjust> {
     BTreeMap::from_iter(
         peq_filters
             .iter()
-            .map(|(speaker, peq)| {
-                peq.iter().map(move |(index, f)| {
-                    (
-                        peq_filter_name(&speaker, *index),
-                        SpeakerAdjust::PeakingFilter(PeakingFilter {
-                            filter_type: FilterType::Biquad,
-                            parameters: PeakingParameters {
-                                freq: f.freq,
-                                q: f.q,
-                                gain: f.gain,
-                                peaking_type: PeakingType::Peaking,
-                            },
-                        }),
-                    )
-                })
+            .flat_map(|(speaker, peq)| {
+                peq.iter()
+                    .map(move |(index, f)| (peq_filter_name(speaker, *index), create_peq_filter(f)))
             })
-            .flatten()
+            .chain(impulse_filters.iter().map(|(speaker, (channel, impulse))| {
+                (
+                    conv_filter_name(speaker),
+                    SpeakerAdjust::ConvFilter(ConvFilter {
+                        filter_type: FilterType::Conv,
+                        parameters: match impulse.file_type {
+                            ImpulseFileType::Wav => ConvParameters {
+                                conv_type: ConvType::Wav,
+                                filename: impulse.path.clone(),
+                                channel: Some(*channel),
+                                format: None,
+                            },
+                            ImpulseFileType::Raw => ConvParameters {
+                                conv_type: ConvType::Raw,
+                                filename: impulse.path.clone(),
+                                channel: None,
+                                format: impulse.sample_format,
+                            },
+                        },
+                    }),
+                )
+            }))
             .chain(speakers.iter().map(|s| {
                 (
                     delay_filter_name(&s.speaker),
@@ -90,7 +239,12 @@ pub fn create_output_filters(
                         filter_type: FilterType::Delay,
                         parameters: DelayParameters {
                             delay: s.delay,
-                            unit: DelayUnit::Ms,
+                            unit: match selected_distance {
+                                SelectedDistanceType::SAMPLES => DelayUnit::Samples,
+                                SelectedDistanceType::MILLIMETERS => DelayUnit::Mm,
+                                _ => DelayUnit::Ms,
+                            },
+                            subsample: true, //keeps delays that aren't whole samples exact
                         },
                     }),
                 )
@@ -102,7 +256,8 @@ pub fn create_output_filters(
                         filter_type: FilterType::Gain,
                         parameters: GainParameters {
                             gain: s.gain,
-                            inverted: false,
+                            inverted: s.inverted,
+                            mute: false,
                         },
                     }),
                 )
@@ -110,9 +265,133 @@ pub fn create_output_filters(
     )
 }
 
-pub fn compute_peq_filter<'a>(
-    filters: &'a [Filter],
-) -> BTreeMap<&'a String, Vec<(usize, &'a Filter)>> {
+/// generates the version wide filters, which are shared by every output channel
+pub fn create_global_filters(filters: &[Filter]) -> BTreeMap<String, SpeakerAdjust> {
+    filters
+        .iter()
+        .enumerate()
+        .map(|(index, f)| (global_filter_name(index), create_peq_filter(f)))
+        .collect()
+}
+
+/// generates the all-pass filters run on each aligned speaker's track after the split mixer, ahead of its crossover
+pub fn create_phase_alignment_filters(
+    phase_alignment: &[PhaseAlignment],
+) -> BTreeMap<String, SpeakerAdjust> {
+    phase_alignment
+        .iter()
+        .enumerate()
+        .map(|(index, allpass)| {
+            (
+                allpass_filter_name(&allpass.speaker, index),
+                create_peq_filter(&Filter {
+                    freq: allpass.freq,
+                    gain: 0.0,
+                    q: allpass.q,
+                    speaker: allpass.speaker.clone(),
+                    filter_type: match allpass.order {
+                        AllpassOrder::First => PeqType::AllpassFO,
+                        AllpassOrder::Second => PeqType::Allpass,
+                    },
+                    slope: None,
+                    freq_target: None,
+                    q_target: None,
+                    enabled: true,
+                }),
+            )
+        })
+        .collect()
+}
+
+/// maps a user filter to the matching camilla biquad.
+/// Each biquad type only accepts its own parameters, so unused ones are left off
+fn create_peq_filter(filter: &Filter) -> SpeakerAdjust {
+    let peaking_type = match filter.filter_type {
+        PeqType::Peaking => PeakingType::Peaking,
+        PeqType::Lowshelf => PeakingType::Lowshelf,
+        PeqType::Highshelf => PeakingType::Highshelf,
+        PeqType::Highpass => PeakingType::Highpass,
+        PeqType::Lowpass => PeakingType::Lowpass,
+        PeqType::HighpassFO => PeakingType::HighpassFO,
+        PeqType::LowpassFO => PeakingType::LowpassFO,
+        PeqType::Notch => PeakingType::Notch,
+        PeqType::Bandpass => PeakingType::Bandpass,
+        PeqType::Allpass => PeakingType::Allpass,
+        PeqType::AllpassFO => PeakingType::AllpassFO,
+        PeqType::LinkwitzTransform => {
+            return SpeakerAdjust::LinkwitzFilter(LinkwitzFilter {
+                filter_type: FilterType::Biquad,
+                parameters: LinkwitzParameters {
+                    freq_act: filter.freq,
+                    q_act: filter.q,
+                    freq_target: filter.freq_target,
+                    q_target: filter.q_target,
+                    peaking_type: PeakingType::LinkwitzTransform,
+                },
+            })
+        }
+    };
+    let (q, slope, gain) = match filter.filter_type {
+        PeqType::Lowshelf | PeqType::Highshelf => match filter.slope {
+            Some(slope) => (None, Some(slope), Some(filter.gain)),
+            None => (Some(filter.q), None, Some(filter.gain)),
+        },
+        PeqType::Peaking => (Some(filter.q), None, Some(filter.gain)),
+        PeqType::HighpassFO | PeqType::LowpassFO | PeqType::AllpassFO => (None, None, None),
+        _ => (Some(filter.q), None, None),
+    };
+    SpeakerAdjust::PeakingFilter(PeakingFilter {
+        filter_type: FilterType::Biquad,
+        parameters: PeakingParameters {
+            freq: filter.freq,
+            q,
+            slope,
+            gain,
+            peaking_type,
+        },
+    })
+}
+
+/// copies each group's crossover and gain onto its members, and replaces
+/// filters addressed to a group with one filter per member
+pub fn expand_groups(
+    groups: &[SpeakerGroup],
+    speakers: &mut [Speaker],
+    filters: Vec<Filter>,
+) -> Vec<Filter> {
+    for group in groups.iter() {
+        for speaker in speakers
+            .iter_mut()
+            .filter(|s| group.speakers.contains(&s.speaker))
+        {
+            //a group only sharing PEQ leaves each member's crossover and gain trim alone
+            if let Some(crossover) = group.crossover {
+                speaker.crossover = Some(crossover).filter(|_| group.crossover_enabled);
+            }
+            if let Some(gain) = group.gain {
+                speaker.gain = gain;
+            }
+        }
+    }
+    filters
+        .into_iter()
+        .flat_map(
+            |filter| match groups.iter().find(|g| g.name == filter.speaker) {
+                Some(group) => group
+                    .speakers
+                    .iter()
+                    .map(|speaker| Filter {
+                        speaker: speaker.clone(),
+                        ..filter.clone()
+                    })
+                    .collect(),
+                None => vec![filter],
+            },
+        )
+        .collect()
+}
+
+pub fn compute_peq_filter(filters: &[Filter]) -> BTreeMap<&String, Vec<(usize, &Filter)>> {
     let mut hold_filters: BTreeMap<&String, Vec<(usize, &Filter)>> = BTreeMap::new();
     for (index, filter) in filters.iter().enumerate() {
         hold_filters
@@ -123,6 +402,136 @@ pub fn compute_peq_filter<'a>(
     hold_filters
 }
 
+/// generates the gain filter that makes room for boosts, shared by every output channel
+pub fn create_headroom_filter(headroom: f32) -> BTreeMap<String, SpeakerAdjust> {
+    BTreeMap::from([(
+        headroom_filter_name(),
+        SpeakerAdjust::GainFilter(GainFilter {
+            filter_type: FilterType::Gain,
+            parameters: GainParameters {
+                gain: -headroom,
+                inverted: false,
+                mute: false,
+            },
+        }),
+    )])
+}
+
+/// generates the loudness filter, which is shared by every output channel
+pub fn create_loudness_filter(loudness: &Option<Loudness>) -> BTreeMap<String, SpeakerAdjust> {
+    loudness
+        .iter()
+        .map(|l| {
+            (
+                loudness_filter_name(),
+                SpeakerAdjust::LoudnessFilter(LoudnessFilter {
+                    filter_type: FilterType::Loudness,
+                    parameters: LoudnessParameters {
+                        reference_level: l.reference_level,
+                        high_boost: l.high_boost,
+                        low_boost: l.low_boost,
+                    },
+                }),
+            )
+        })
+        .collect()
+}
+
+/// generates the delay decorrelating the upmixed surrounds and the lowpass of the derived LFE
+pub fn create_upmix_filters(upmix: &Option<Upmix>) -> BTreeMap<String, SpeakerAdjust> {
+    upmix
+        .iter()
+        .flat_map(|u| {
+            std::iter::once((
+                upmix_surround_delay_name(),
+                SpeakerAdjust::DelayFilter(DelayFilter {
+                    filter_type: FilterType::Delay,
+                    parameters: DelayParameters {
+                        delay: u.surround_delay,
+                        unit: DelayUnit::Ms,
+                        subsample: true,
+                    },
+                }),
+            ))
+            .chain(u.lfe_lowpass.map(|freq| {
+                (
+                    upmix_lfe_lowpass_name(),
+                    SpeakerAdjust::CrossoverFilter(CrossoverFilter {
+                        filter_type: FilterType::BiquadCombo,
+                        parameters: CrossoverParameters {
+                            freq,
+                            order: default_crossover_order(),
+                            crossover_type: CrossoverType::LinkwitzRileyLowpass,
+                        },
+                    }),
+                )
+            }))
+        })
+        .collect()
+}
+
+/// generates the dither filter, shared by every output channel.
+/// Nothing is generated for devices taking 32 bits, since truncating from float is harmless there
+pub fn create_dither_filter(dither: &DitherType, bits: i32) -> BTreeMap<String, SpeakerAdjust> {
+    let dither_type = match dither {
+        _ if bits >= 32 => None,
+        DitherType::None => None,
+        DitherType::Flat => Some(DitherFilterType::Flat),
+        DitherType::Highpass => Some(DitherFilterType::Highpass),
+        DitherType::Shibata96 => Some(DitherFilterType::Shibata96),
+    };
+    dither_type
+        .map(|dither_type| {
+            let amplitude = match dither_type {
+                DitherFilterType::Flat => Some(2.0), //triangular dither of 2 LSB
+                _ => None,
+            };
+            (
+                dither_filter_name(),
+                SpeakerAdjust::DitherFilter(DitherFilter {
+                    filter_type: FilterType::Dither,
+                    parameters: DitherParameters {
+                        dither_type,
+                        bits,
+                        amplitude,
+                    },
+                }),
+            )
+        })
+        .into_iter()
+        .collect()
+}
+
+/// silences speakers through their gain filters, leaving the rest of the configuration untouched
+pub fn mute_filters(filters: &mut BTreeMap<String, SpeakerAdjust>, speakers: &[&String]) {
+    for speaker in speakers {
+        if let Some(SpeakerAdjust::GainFilter(gain_filter)) =
+            filters.get_mut(&gain_filter_name(speaker))
+        {
+            gain_filter.parameters.mute = true;
+        }
+    }
+}
+
+/// speakers with an impulse response, along with the channel to read from it.
+/// Impulses that no longer exist are left out rather than sending camilla a missing file
+pub fn compute_impulse_filter<'a>(
+    speakers: &'a [Speaker],
+    impulse_responses: &'a [ImpulseResponse],
+) -> BTreeMap<&'a String, (i32, &'a ImpulseResponse)> {
+    speakers
+        .iter()
+        .filter_map(|s| {
+            s.impulse_response.as_ref().and_then(|filename| {
+                impulse_responses
+                    .iter()
+                    .find(|impulse| &impulse.filename == filename)
+                    .map(|impulse| (&s.speaker, (s.impulse_response_channel, impulse)))
+            })
+        })
+        .collect()
+}
+
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 enum FilterType {
@@ -130,14 +539,35 @@ enum FilterType {
     Biquad,
     BiquadCombo,
     Gain,
+    Conv,
+    Loudness,
+    Dither,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+enum ConvType {
+    Wav,
+    Raw,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+enum DitherFilterType {
+    Flat,
+    Highpass,
+    Shibata96,
 }
 
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 enum DelayUnit {
-    //more may be added later
     #[serde(rename = "ms")]
     Ms,
+    #[serde(rename = "samples")]
+    Samples,
+    #[serde(rename = "mm")]
+    Mm,
 }
 
 #[derive(Serialize)]
@@ -146,6 +576,8 @@ enum CrossoverType {
     //more may be added later
     ButterworthHighpass,
     ButterworthLowpass,
+    LinkwitzRileyHighpass,
+    LinkwitzRileyLowpass,
 }
 
 #[derive(Serialize)]
@@ -153,14 +585,43 @@ enum CrossoverType {
 enum PeakingType {
     //more may be added later
     Peaking,
+    Lowshelf,
+    Highshelf,
+    Highpass,
+    Lowpass,
+    HighpassFO,
+    LowpassFO,
+    Notch,
+    Bandpass,
+    Allpass,
+    AllpassFO,
+    LinkwitzTransform,
 }
 
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 struct PeakingParameters {
     freq: i32,
-    q: f32,
-    gain: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    q: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slope: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gain: Option<f32>,
+    #[serde(rename = "type")]
+    peaking_type: PeakingType,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct LinkwitzParameters {
+    freq_act: i32,
+    q_act: f32,
+    //left off when missing so camilla rejects the transform, rather than playing it as a no-op
+    #[serde(skip_serializing_if = "Option::is_none")]
+    freq_target: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    q_target: Option<f32>,
     #[serde(rename = "type")]
     peaking_type: PeakingType,
 }
@@ -174,11 +635,42 @@ struct CrossoverParameters {
     crossover_type: CrossoverType,
 }
 
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ConvParameters {
+    #[serde(rename = "type")]
+    conv_type: ConvType,
+    filename: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel: Option<i32>, //wav only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<SampleFormat>, //raw only
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct LoudnessParameters {
+    reference_level: f32,
+    high_boost: f32,
+    low_boost: f32,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct DitherParameters {
+    #[serde(rename = "type")]
+    dither_type: DitherFilterType,
+    bits: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amplitude: Option<f32>,
+}
+
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 struct DelayParameters {
     delay: f32,
     unit: DelayUnit,
+    subsample: bool,
 }
 
 #[derive(Serialize)]
@@ -186,6 +678,8 @@ struct DelayParameters {
 struct GainParameters {
     gain: f32,
     inverted: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    mute: bool,
 }
 
 #[derive(Serialize)]
@@ -196,6 +690,38 @@ pub struct PeakingFilter {
     parameters: PeakingParameters,
 }
 
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LinkwitzFilter {
+    #[serde(rename = "type")]
+    filter_type: FilterType,
+    parameters: LinkwitzParameters,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ConvFilter {
+    #[serde(rename = "type")]
+    filter_type: FilterType,
+    parameters: ConvParameters,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LoudnessFilter {
+    #[serde(rename = "type")]
+    filter_type: FilterType,
+    parameters: LoudnessParameters,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct DitherFilter {
+    #[serde(rename = "type")]
+    filter_type: FilterType,
+    parameters: DitherParameters,
+}
+
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct DelayFilter {
@@ -223,9 +749,463 @@ pub struct CrossoverFilter {
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 #[serde(untagged)]
+#[allow(clippy::enum_variant_names)]
 pub enum SpeakerAdjust {
     DelayFilter(DelayFilter),
     PeakingFilter(PeakingFilter),
+    LinkwitzFilter(LinkwitzFilter),
     CrossoverFilter(CrossoverFilter),
+    ConvFilter(ConvFilter),
+    LoudnessFilter(LoudnessFilter),
     GainFilter(GainFilter),
+    DitherFilter(DitherFilter),
+}
+
+impl SpeakerAdjust {
+    /// the biquads camilla builds from this filter, for computing its response.
+    /// Delay, convolution, loudness and dither are not biquads and give nothing
+    pub fn biquads(&self, samplerate: i32) -> Vec<Biquad> {
+        match self {
+            SpeakerAdjust::PeakingFilter(PeakingFilter { parameters, .. }) => {
+                let PeakingParameters {
+                    freq,
+                    q,
+                    slope,
+                    gain,
+                    peaking_type,
+                } = parameters;
+                let freq = *freq as f32;
+                let q = q.unwrap_or(0.707);
+                let gain = gain.unwrap_or(0.0);
+                vec![match peaking_type {
+                    PeakingType::Peaking => Biquad::peaking(freq, gain, q, samplerate),
+                    PeakingType::Lowshelf => Biquad::lowshelf(freq, gain, q, *slope, samplerate),
+                    PeakingType::Highshelf => Biquad::highshelf(freq, gain, q, *slope, samplerate),
+                    PeakingType::Highpass => Biquad::highpass(freq, q, samplerate),
+                    PeakingType::Lowpass => Biquad::lowpass(freq, q, samplerate),
+                    PeakingType::HighpassFO => Biquad::highpass_first_order(freq, samplerate),
+                    PeakingType::LowpassFO => Biquad::lowpass_first_order(freq, samplerate),
+                    PeakingType::Notch => Biquad::notch(freq, q, samplerate),
+                    PeakingType::Bandpass => Biquad::bandpass(freq, q, samplerate),
+                    PeakingType::Allpass => Biquad::allpass(freq, q, samplerate),
+                    PeakingType::AllpassFO => Biquad::allpass_first_order(freq, samplerate),
+                    PeakingType::LinkwitzTransform => return vec![], //only built as LinkwitzFilter
+                }]
+            }
+            SpeakerAdjust::LinkwitzFilter(LinkwitzFilter { parameters, .. }) => {
+                match (parameters.freq_target, parameters.q_target) {
+                    (Some(freq_target), Some(q_target)) => vec![Biquad::linkwitz_transform(
+                        parameters.freq_act as f32,
+                        parameters.q_act,
+                        freq_target as f32,
+                        q_target,
+                        samplerate,
+                    )],
+                    _ => vec![], //camilla won't load a transform without its targets
+                }
+            }
+            SpeakerAdjust::CrossoverFilter(CrossoverFilter { parameters, .. }) => {
+                let freq = parameters.freq as f32;
+                let (family, highpass) = match parameters.crossover_type {
+                    CrossoverType::ButterworthHighpass => (CrossoverFamily::Butterworth, true),
+                    CrossoverType::ButterworthLowpass => (CrossoverFamily::Butterworth, false),
+                    CrossoverType::LinkwitzRileyHighpass => (CrossoverFamily::LinkwitzRiley, true),
+                    CrossoverType::LinkwitzRileyLowpass => (CrossoverFamily::LinkwitzRiley, false),
+                };
+                crossover_sections(family, parameters.order)
+                    .into_iter()
+                    .map(|q| match (q, highpass) {
+                        (Some(q), true) => Biquad::highpass(freq, q, samplerate),
+                        (Some(q), false) => Biquad::lowpass(freq, q, samplerate),
+                        (None, true) => Biquad::highpass_first_order(freq, samplerate),
+                        (None, false) => Biquad::lowpass_first_order(freq, samplerate),
+                    })
+                    .collect()
+            }
+            SpeakerAdjust::GainFilter(GainFilter { parameters, .. }) => {
+                vec![Biquad::gain(parameters.gain, parameters.inverted)]
+            }
+            SpeakerAdjust::DelayFilter(_)
+            | SpeakerAdjust::ConvFilter(_)
+            | SpeakerAdjust::LoudnessFilter(_)
+            | SpeakerAdjust::DitherFilter(_) => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compute_impulse_filter, compute_peq_filter, create_crossover_filters, create_dither_filter,
+        create_driver_filters, create_loudness_filter, create_output_filters,
+        create_phase_alignment_filters, expand_groups, mute_filters, output_speakers,
+    };
+    use crate::impulses::{ImpulseFileType, ImpulseResponse, SampleFormat};
+    use crate::processor::{
+        AllpassOrder, CrossoverFamily, DitherType, Driver, Filter, Loudness, PeqType,
+        PhaseAlignment, SelectedDistanceType, Speaker, SpeakerGroup,
+    };
+    use rocket::serde::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn check_shelf_filters() {
+        let filters = vec![
+            Filter {
+                freq: 100,
+                gain: 3.0,
+                q: 0.707,
+                speaker: "l".to_string(),
+                filter_type: PeqType::Lowshelf,
+                slope: Some(6.0),
+                ..Default::default()
+            },
+            Filter {
+                freq: 5000,
+                gain: -2.0,
+                q: 0.707,
+                speaker: "l".to_string(),
+                filter_type: PeqType::Highshelf,
+                ..Default::default()
+            },
+            Filter {
+                freq: 1000,
+                gain: 1.0,
+                q: 2.0,
+                speaker: "l".to_string(),
+                slope: Some(6.0), //ignored for peaking
+                ..Default::default()
+            },
+        ];
+        let result = create_output_filters(
+            &[],
+            &compute_peq_filter(&filters),
+            &BTreeMap::new(),
+            &SelectedDistanceType::MS,
+        );
+        assert_eq!(
+            json::to_string(&result).unwrap(),
+            r#"{"peq_l_0":{"type":"Biquad","parameters":{"freq":100,"slope":6.0,"gain":3.0,"type":"Lowshelf"}},"peq_l_1":{"type":"Biquad","parameters":{"freq":5000,"q":0.707,"gain":-2.0,"type":"Highshelf"}},"peq_l_2":{"type":"Biquad","parameters":{"freq":1000,"q":2.0,"gain":1.0,"type":"Peaking"}}}"#
+        );
+    }
+
+    #[test]
+    fn check_biquad_filters() {
+        let filters = vec![
+            Filter {
+                freq: 20,
+                gain: 0.0,
+                q: 0.707,
+                speaker: "sub1".to_string(),
+                filter_type: PeqType::Highpass,
+                ..Default::default()
+            },
+            Filter {
+                freq: 200,
+                gain: 0.0,
+                q: 0.707,
+                speaker: "sub1".to_string(),
+                filter_type: PeqType::LowpassFO,
+                ..Default::default()
+            },
+            Filter {
+                freq: 40,
+                gain: 0.0,
+                q: 0.8,
+                speaker: "sub1".to_string(),
+                filter_type: PeqType::LinkwitzTransform,
+                freq_target: Some(20),
+                q_target: Some(0.5),
+                ..Default::default()
+            },
+        ];
+        let result = create_output_filters(
+            &[],
+            &compute_peq_filter(&filters),
+            &BTreeMap::new(),
+            &SelectedDistanceType::MS,
+        );
+        assert_eq!(
+            json::to_string(&result).unwrap(),
+            r#"{"peq_sub1_0":{"type":"Biquad","parameters":{"freq":20,"q":0.707,"type":"Highpass"}},"peq_sub1_1":{"type":"Biquad","parameters":{"freq":200,"type":"LowpassFO"}},"peq_sub1_2":{"type":"Biquad","parameters":{"freq_act":40,"q_act":0.8,"freq_target":20,"q_target":0.5,"type":"LinkwitzTransform"}}}"#
+        );
+    }
+
+    #[test]
+    fn check_crossover_filters() {
+        let speakers = vec![
+            Speaker {
+                speaker: "l".to_string(),
+                crossover: Some(80),
+                delay: 0.0,
+                gain: 0.0,
+                is_subwoofer: false,
+                crossover_type: CrossoverFamily::LinkwitzRiley,
+                crossover_speaker_order: 2,
+                crossover_subwoofer_order: 8,
+                ..Default::default()
+            },
+            Speaker {
+                speaker: "sub1".to_string(),
+                crossover: None,
+                delay: 0.0,
+                gain: 0.0,
+                is_subwoofer: true,
+                ..Default::default()
+            },
+        ];
+        let result = create_crossover_filters(&speakers);
+        assert_eq!(
+            json::to_string(&result).unwrap(),
+            r#"{"crossover_speaker_l":{"type":"BiquadCombo","parameters":{"freq":80,"order":2,"type":"LinkwitzRileyHighpass"}},"crossover_subwooferl":{"type":"BiquadCombo","parameters":{"freq":80,"order":8,"type":"LinkwitzRileyLowpass"}}}"#
+        );
+    }
+    #[test]
+    fn check_conv_filters() {
+        let speakers: Vec<Speaker> = ["l", "r", "c"]
+            .iter()
+            .map(|name| Speaker {
+                speaker: name.to_string(),
+                crossover: None,
+                delay: 0.0,
+                gain: 0.0,
+                is_subwoofer: false,
+                impulse_response: match *name {
+                    "l" => Some("room.wav".to_string()),
+                    "r" => Some("right.dbl".to_string()),
+                    _ => Some("deleted.wav".to_string()),
+                },
+                impulse_response_channel: if *name == "l" { 1 } else { 0 },
+                ..Default::default()
+            })
+            .collect();
+        let impulse_responses = vec![
+            ImpulseResponse {
+                filename: "room.wav".to_string(),
+                path: "/impulses/room.wav".to_string(),
+                file_type: ImpulseFileType::Wav,
+                sample_format: None,
+                sample_rate: 96000,
+                channels: 2,
+                length: 4096,
+                upload_date: "".to_string(),
+            },
+            ImpulseResponse {
+                filename: "right.dbl".to_string(),
+                path: "/impulses/right.dbl".to_string(),
+                file_type: ImpulseFileType::Raw,
+                sample_format: Some(SampleFormat::FLOAT64LE),
+                sample_rate: 96000,
+                channels: 1,
+                length: 4096,
+                upload_date: "".to_string(),
+            },
+        ];
+        let impulse_filters = compute_impulse_filter(&speakers, &impulse_responses);
+        assert_eq!(impulse_filters.len(), 2); //missing impulse is skipped
+        let result = create_output_filters(
+            &[],
+            &BTreeMap::new(),
+            &impulse_filters,
+            &SelectedDistanceType::MS,
+        );
+        assert_eq!(
+            json::to_string(&result).unwrap(),
+            r#"{"conv_l":{"type":"Conv","parameters":{"type":"Wav","filename":"/impulses/room.wav","channel":1}},"conv_r":{"type":"Conv","parameters":{"type":"Raw","filename":"/impulses/right.dbl","format":"FLOAT64LE"}}}"#
+        );
+    }
+    #[test]
+    fn check_inverted_gain_filters() {
+        let speakers = vec![
+            Speaker {
+                speaker: "l".to_string(),
+                crossover: Some(80),
+                delay: 0.0,
+                gain: 0.0,
+                is_subwoofer: false,
+                ..Default::default()
+            },
+            Speaker {
+                speaker: "sub1".to_string(),
+                crossover: None,
+                delay: 0.0,
+                gain: -2.0,
+                is_subwoofer: true,
+                inverted: true,
+                ..Default::default()
+            },
+        ];
+        let mut result = create_output_filters(
+            &speakers,
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+            &SelectedDistanceType::SAMPLES,
+        );
+        assert_eq!(
+            json::to_string(&result).unwrap(),
+            r#"{"delay_l":{"type":"Delay","parameters":{"delay":0.0,"unit":"samples","subsample":true}},"delay_sub1":{"type":"Delay","parameters":{"delay":0.0,"unit":"samples","subsample":true}},"gain_l":{"type":"Gain","parameters":{"gain":0.0,"inverted":false}},"gain_sub1":{"type":"Gain","parameters":{"gain":-2.0,"inverted":true}}}"#
+        );
+        mute_filters(&mut result, &[&speakers[1].speaker]);
+        assert_eq!(
+            json::to_string(&result["gain_sub1"]).unwrap(),
+            r#"{"type":"Gain","parameters":{"gain":-2.0,"inverted":true,"mute":true}}"#
+        );
+    }
+    #[test]
+    fn check_loudness_filter() {
+        assert!(create_loudness_filter(&None).is_empty());
+        let result = create_loudness_filter(&Some(Loudness {
+            reference_level: -25.0,
+            high_boost: 7.0,
+            low_boost: 10.0,
+        }));
+        assert_eq!(
+            json::to_string(&result).unwrap(),
+            r#"{"loudness":{"type":"Loudness","parameters":{"reference_level":-25.0,"high_boost":7.0,"low_boost":10.0}}}"#
+        );
+    }
+    #[test]
+    fn check_dither_filter() {
+        assert!(create_dither_filter(&DitherType::Highpass, 32).is_empty());
+        assert!(create_dither_filter(&DitherType::None, 24).is_empty());
+        assert_eq!(
+            json::to_string(&create_dither_filter(&DitherType::Highpass, 24)).unwrap(),
+            r#"{"dither":{"type":"Dither","parameters":{"type":"Highpass","bits":24}}}"#
+        );
+        assert_eq!(
+            json::to_string(&create_dither_filter(&DitherType::Flat, 16)).unwrap(),
+            r#"{"dither":{"type":"Dither","parameters":{"type":"Flat","bits":16,"amplitude":2.0}}}"#
+        );
+    }
+    #[test]
+    fn check_expand_groups() {
+        let speaker = |name: &str| Speaker {
+            speaker: name.to_string(),
+            crossover: Some(60),
+            delay: 0.0,
+            gain: -1.0,
+            is_subwoofer: false,
+            ..Default::default()
+        };
+        let filter = |speaker: &str, freq: i32| Filter {
+            freq,
+            gain: -3.0,
+            q: 1.0,
+            speaker: speaker.to_string(),
+            ..Default::default()
+        };
+        let groups = vec![
+            SpeakerGroup {
+                name: "fronts".to_string(),
+                speakers: vec!["l".to_string(), "r".to_string()],
+                crossover: Some(80),
+                gain: Some(2.0),
+                crossover_enabled: true,
+            },
+            SpeakerGroup {
+                name: "center".to_string(),
+                speakers: vec!["c".to_string()],
+                crossover: None,
+                gain: None,
+                crossover_enabled: true,
+            },
+        ];
+        let mut speakers = vec![speaker("l"), speaker("r"), speaker("c")];
+        let filters = expand_groups(
+            &groups,
+            &mut speakers,
+            vec![filter("c", 100), filter("fronts", 200), filter("l", 300)],
+        );
+        let result: Vec<(&str, i32)> = filters
+            .iter()
+            .map(|f| (f.speaker.as_str(), f.freq))
+            .collect();
+        assert_eq!(result, vec![("c", 100), ("l", 200), ("r", 200), ("l", 300)]);
+        let result: Vec<(Option<i32>, f32)> =
+            speakers.iter().map(|s| (s.crossover, s.gain)).collect();
+        assert_eq!(
+            result,
+            vec![(Some(80), 2.0), (Some(80), 2.0), (Some(60), -1.0)]
+        );
+    }
+    #[test]
+    fn check_phase_alignment_filters() {
+        let allpass = |order: AllpassOrder| PhaseAlignment {
+            speaker: "l".to_string(),
+            freq: 80,
+            order,
+            q: 0.5,
+        };
+        let result = create_phase_alignment_filters(&[
+            allpass(AllpassOrder::Second),
+            allpass(AllpassOrder::First),
+        ]);
+        assert_eq!(
+            json::to_string(&result).unwrap(),
+            r#"{"allpass_l_0":{"type":"Biquad","parameters":{"freq":80,"q":0.5,"type":"Allpass"}},"allpass_l_1":{"type":"Biquad","parameters":{"freq":80,"type":"AllpassFO"}}}"#
+        );
+    }
+    #[test]
+    fn check_driver_filters() {
+        let speakers = vec![
+            Speaker {
+                speaker: "l".to_string(),
+                crossover: Some(80),
+                delay: 1.0,
+                gain: -1.0,
+                is_subwoofer: false,
+                impulse_response: Some("l.wav".to_string()),
+                inverted: true,
+                ..Default::default()
+            },
+            Speaker {
+                speaker: "sub1".to_string(),
+                crossover: None,
+                delay: 0.0,
+                gain: 0.0,
+                is_subwoofer: true,
+                ..Default::default()
+            },
+        ];
+        let drivers = vec![
+            Driver {
+                speaker: "l".to_string(),
+                driver: "l_woofer".to_string(),
+                highpass: None,
+                lowpass: Some(2000),
+                crossover_type: CrossoverFamily::LinkwitzRiley,
+                crossover_order: 4,
+                delay: 0.5,
+                gain: 0.0,
+                inverted: false,
+                output_port: None,
+            },
+            Driver {
+                speaker: "l".to_string(),
+                driver: "l_tweeter".to_string(),
+                highpass: Some(2000),
+                lowpass: None,
+                crossover_type: CrossoverFamily::LinkwitzRiley,
+                crossover_order: 4,
+                delay: 0.0,
+                gain: -3.0,
+                inverted: true,
+                output_port: None,
+            },
+        ];
+        let result = output_speakers(&speakers, &drivers);
+        let names: Vec<&str> = result.iter().map(|s| s.speaker.as_str()).collect();
+        assert_eq!(names, vec!["l_woofer", "l_tweeter", "sub1"]);
+        assert_eq!(result[0].delay, 1.5);
+        assert!(result[0].inverted);
+        assert_eq!(result[1].gain, -4.0);
+        assert!(!result[1].inverted); //inverted twice
+        assert!(result[1].impulse_response.is_none());
+
+        let result = create_driver_filters(&drivers);
+        assert_eq!(
+            json::to_string(&result).unwrap(),
+            r#"{"driver_highpass_l_tweeter":{"type":"BiquadCombo","parameters":{"freq":2000,"order":4,"type":"LinkwitzRileyHighpass"}},"driver_lowpass_l_woofer":{"type":"BiquadCombo","parameters":{"freq":2000,"order":4,"type":"LinkwitzRileyLowpass"}}}"#
+        );
+    }
 }
@@ -0,0 +1,346 @@
+use rocket::serde::{Deserialize, Serialize};
+
+/// roughly 5 seconds at 96khz; longer impulses are very expensive for camilla to convolve
+pub const MAX_IMPULSE_LENGTH: i32 = 524288;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, sqlx::Type)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum ImpulseFileType {
+    Wav,
+    Raw,
+}
+
+/// sample formats camilla can read from raw impulse files, see https://github.com/HEnquist/camilladsp#conv
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, sqlx::Type, FromFormField)]
+#[serde(crate = "rocket::serde")]
+#[allow(clippy::upper_case_acronyms)]
+pub enum SampleFormat {
+    S16LE,
+    S24LE,
+    S24LE3,
+    S32LE,
+    FLOAT32LE,
+    FLOAT64LE,
+}
+
+impl SampleFormat {
+    fn bytes_per_sample(&self) -> usize {
+        match self {
+            SampleFormat::S16LE => 2,
+            SampleFormat::S24LE3 => 3,
+            SampleFormat::S24LE | SampleFormat::S32LE | SampleFormat::FLOAT32LE => 4,
+            SampleFormat::FLOAT64LE => 8,
+        }
+    }
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+/// Metadata for an uploaded impulse response; maps to the `impulse_responses` table
+pub struct ImpulseResponse {
+    pub filename: String,
+    #[serde(skip)]
+    pub path: String, //absolute location on the server, this is what camilla reads
+    pub file_type: ImpulseFileType,
+    pub sample_format: Option<SampleFormat>, //only needed for raw files, wav files describe themselves
+    pub sample_rate: i32,
+    pub channels: i32,
+    pub length: i32, //in samples per channel
+    pub upload_date: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ImpulseInfo {
+    pub channels: i32,
+    pub sample_rate: i32,
+    pub length: i32,
+}
+
+/// filenames are used directly on disk, so only allow a conservative set of characters
+pub fn validate_impulse_filename(filename: &str) -> Result<ImpulseFileType, String> {
+    if filename.is_empty()
+        || filename.starts_with('.')
+        || !filename
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    {
+        return Err(format!("Invalid impulse filename {}", filename));
+    }
+    if filename.to_lowercase().ends_with(".wav") {
+        Ok(ImpulseFileType::Wav)
+    } else {
+        Ok(ImpulseFileType::Raw)
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// reads the RIFF header and fmt/data chunks of a wav file
+pub fn parse_wav(bytes: &[u8]) -> Result<ImpulseInfo, String> {
+    if bytes.get(0..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"WAVE") {
+        return Err("Not a RIFF/WAVE file".to_string());
+    }
+    let mut offset = 12;
+    let mut format: Option<(u16, u16, u32, u16, u16)> = None;
+    let mut data_size: Option<u32> = None;
+    while let (Some(chunk_id), Some(chunk_size)) =
+        (bytes.get(offset..offset + 4), read_u32(bytes, offset + 4))
+    {
+        let body = offset + 8;
+        match chunk_id {
+            b"fmt " => {
+                let truncated = || "Truncated fmt chunk".to_string();
+                let mut audio_format = read_u16(bytes, body).ok_or_else(truncated)?;
+                let channels = read_u16(bytes, body + 2).ok_or_else(truncated)?;
+                let sample_rate = read_u32(bytes, body + 4).ok_or_else(truncated)?;
+                let block_align = read_u16(bytes, body + 12).ok_or_else(truncated)?;
+                let bits_per_sample = read_u16(bytes, body + 14).ok_or_else(truncated)?;
+                if audio_format == WAVE_FORMAT_EXTENSIBLE {
+                    //first two bytes of the sub format GUID hold the actual format
+                    audio_format = read_u16(bytes, body + 24).ok_or_else(truncated)?;
+                }
+                format = Some((
+                    audio_format,
+                    channels,
+                    sample_rate,
+                    block_align,
+                    bits_per_sample,
+                ));
+            }
+            b"data" => {
+                //truncated files report more data than they hold
+                data_size = Some(chunk_size.min(bytes.len().saturating_sub(body) as u32));
+                break;
+            }
+            _ => {}
+        }
+        //chunks are padded to an even number of bytes
+        offset = body + chunk_size as usize + (chunk_size as usize % 2);
+    }
+    let (audio_format, channels, sample_rate, block_align, bits_per_sample) =
+        format.ok_or("Missing fmt chunk")?;
+    let data_size = data_size.ok_or("Missing data chunk")?;
+    match (audio_format, bits_per_sample) {
+        (WAVE_FORMAT_PCM, 16 | 24 | 32) | (WAVE_FORMAT_IEEE_FLOAT, 32 | 64) => {}
+        _ => {
+            return Err(format!(
+                "Unsupported wav sample format {} with {} bits",
+                audio_format, bits_per_sample
+            ))
+        }
+    }
+    if channels == 0 || block_align == 0 {
+        return Err("Wav file has no channels".to_string());
+    }
+    Ok(ImpulseInfo {
+        channels: channels as i32,
+        sample_rate: sample_rate as i32,
+        length: (data_size / block_align as u32) as i32,
+    })
+}
+
+/// raw files carry no header, so the format and sample rate come from the upload request
+pub fn parse_raw(
+    bytes: &[u8],
+    sample_format: SampleFormat,
+    sample_rate: i32,
+) -> Result<ImpulseInfo, String> {
+    let bytes_per_sample = sample_format.bytes_per_sample();
+    if !bytes.len().is_multiple_of(bytes_per_sample) {
+        return Err(format!(
+            "Raw file size {} is not a multiple of {} bytes",
+            bytes.len(),
+            bytes_per_sample
+        ));
+    }
+    Ok(ImpulseInfo {
+        channels: 1,
+        sample_rate,
+        length: (bytes.len() / bytes_per_sample) as i32,
+    })
+}
+
+/// checks that an impulse can be used by camilla at all, independent of any configuration
+pub fn validate_impulse_info(info: &ImpulseInfo) -> Result<(), String> {
+    if info.sample_rate <= 0 {
+        return Err(format!("Invalid sample rate {}", info.sample_rate));
+    }
+    if info.length == 0 {
+        return Err("Impulse response is empty".to_string());
+    }
+    if info.length > MAX_IMPULSE_LENGTH {
+        return Err(format!(
+            "Impulse response has {} samples, the maximum is {}",
+            info.length, MAX_IMPULSE_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+/// checks that an impulse referenced by a speaker matches the device and has the selected channel
+pub fn validate_impulse_for_speaker(
+    impulse: &ImpulseResponse,
+    channel: i32,
+    samplerate: i32,
+) -> Result<(), String> {
+    if impulse.sample_rate != samplerate {
+        return Err(format!(
+            "Impulse response {} has sample rate {} but the device runs at {}",
+            impulse.filename, impulse.sample_rate, samplerate
+        ));
+    }
+    if channel < 0 || channel >= impulse.channels {
+        return Err(format!(
+            "Impulse response {} has {} channels, channel {} does not exist",
+            impulse.filename, impulse.channels, channel
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_raw, parse_wav, validate_impulse_filename, validate_impulse_for_speaker,
+        validate_impulse_info, ImpulseFileType, ImpulseInfo, ImpulseResponse, SampleFormat,
+        MAX_IMPULSE_LENGTH,
+    };
+
+    fn create_wav(
+        audio_format: u16,
+        channels: u16,
+        sample_rate: u32,
+        bits: u16,
+        frames: u32,
+    ) -> Vec<u8> {
+        let block_align = channels * bits / 8;
+        let data_size = frames * block_align as u32;
+        let mut bytes = vec![];
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&audio_format.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&bits.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_size.to_le_bytes());
+        bytes.extend(std::iter::repeat_n(0, data_size as usize));
+        bytes
+    }
+
+    #[test]
+    fn test_parse_wav_float() {
+        let wav = create_wav(3, 2, 96000, 32, 1024);
+        assert_eq!(
+            parse_wav(&wav).unwrap(),
+            ImpulseInfo {
+                channels: 2,
+                sample_rate: 96000,
+                length: 1024
+            }
+        );
+    }
+    #[test]
+    fn test_parse_wav_pcm() {
+        let wav = create_wav(1, 1, 48000, 24, 10);
+        assert_eq!(
+            parse_wav(&wav).unwrap(),
+            ImpulseInfo {
+                channels: 1,
+                sample_rate: 48000,
+                length: 10
+            }
+        );
+    }
+    #[test]
+    fn test_parse_wav_unsupported() {
+        let wav = create_wav(1, 1, 48000, 8, 10);
+        assert!(parse_wav(&wav).is_err());
+        assert!(parse_wav(b"not a wav file").is_err());
+        let wav = create_wav(3, 1, 48000, 32, 10);
+        assert!(parse_wav(&wav[0..30]).is_err());
+    }
+    #[test]
+    fn test_parse_raw() {
+        let bytes = vec![0; 80];
+        assert_eq!(
+            parse_raw(&bytes, SampleFormat::FLOAT64LE, 96000).unwrap(),
+            ImpulseInfo {
+                channels: 1,
+                sample_rate: 96000,
+                length: 10
+            }
+        );
+        assert!(parse_raw(&bytes, SampleFormat::S24LE3, 96000).is_err());
+    }
+    #[test]
+    fn test_validate_impulse_filename() {
+        assert_eq!(
+            validate_impulse_filename("left_96k.wav").unwrap(),
+            ImpulseFileType::Wav
+        );
+        assert_eq!(
+            validate_impulse_filename("left.dbl").unwrap(),
+            ImpulseFileType::Raw
+        );
+        assert!(validate_impulse_filename("../left.wav").is_err());
+        assert!(validate_impulse_filename(".hidden").is_err());
+        assert!(validate_impulse_filename("").is_err());
+    }
+    #[test]
+    fn test_validate_impulse_info() {
+        assert!(validate_impulse_info(&ImpulseInfo {
+            channels: 1,
+            sample_rate: 96000,
+            length: 0
+        })
+        .is_err());
+        assert!(validate_impulse_info(&ImpulseInfo {
+            channels: 1,
+            sample_rate: 96000,
+            length: MAX_IMPULSE_LENGTH + 1
+        })
+        .is_err());
+        assert!(validate_impulse_info(&ImpulseInfo {
+            channels: 1,
+            sample_rate: 96000,
+            length: 4096
+        })
+        .is_ok());
+    }
+    #[test]
+    fn test_validate_impulse_for_speaker() {
+        let impulse = ImpulseResponse {
+            filename: "left.wav".to_string(),
+            path: "/tmp/left.wav".to_string(),
+            file_type: ImpulseFileType::Wav,
+            sample_format: None,
+            sample_rate: 96000,
+            channels: 2,
+            length: 4096,
+            upload_date: "".to_string(),
+        };
+        assert!(validate_impulse_for_speaker(&impulse, 1, 96000).is_ok());
+        assert!(validate_impulse_for_speaker(&impulse, 2, 96000).is_err());
+        assert!(validate_impulse_for_speaker(&impulse, 0, 48000).is_err());
+    }
+}
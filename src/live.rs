@@ -0,0 +1,92 @@
+use crate::processor::Speaker;
+use rocket::serde::Serialize;
+use std::collections::BTreeSet;
+
+#[derive(Serialize, Clone, Default)]
+#[serde(crate = "rocket::serde")]
+/// Runtime only mute and solo state for the applied version.
+/// This is never saved; applying a version clears it
+pub struct LiveSettings {
+    pub muted: BTreeSet<String>,
+    pub soloed: BTreeSet<String>,
+}
+
+impl LiveSettings {
+    /// speakers that should be silent: anything explicitly muted,
+    /// and every speaker that is not soloed when at least one speaker is soloed
+    pub fn muted_speakers<'a>(&self, speakers: &'a [Speaker]) -> Vec<&'a String> {
+        speakers
+            .iter()
+            .filter(|s| {
+                self.muted.contains(&s.speaker)
+                    || (!self.soloed.is_empty() && !self.soloed.contains(&s.speaker))
+            })
+            .map(|s| &s.speaker)
+            .collect()
+    }
+
+    /// muted or soloed speakers that are not part of the given speakers
+    pub fn unknown_speakers(&self, speakers: &[Speaker]) -> Vec<&String> {
+        self.muted
+            .iter()
+            .chain(self.soloed.iter())
+            .filter(|name| !speakers.iter().any(|s| &s.speaker == *name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LiveSettings;
+    use crate::processor::Speaker;
+    use std::collections::BTreeSet;
+
+    fn create_speakers() -> Vec<Speaker> {
+        ["l", "r", "c", "sub1"]
+            .iter()
+            .map(|name| Speaker {
+                speaker: name.to_string(),
+                crossover: None,
+                delay: 0.0,
+                gain: 0.0,
+                is_subwoofer: *name == "sub1",
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_muted_speakers_default() {
+        let speakers = create_speakers();
+        let live = LiveSettings::default();
+        assert!(live.muted_speakers(&speakers).is_empty());
+    }
+    #[test]
+    fn test_muted_speakers_mute() {
+        let speakers = create_speakers();
+        let live = LiveSettings {
+            muted: BTreeSet::from(["c".to_string()]),
+            soloed: BTreeSet::new(),
+        };
+        assert_eq!(live.muted_speakers(&speakers), vec!["c"]);
+    }
+    #[test]
+    fn test_muted_speakers_solo() {
+        let speakers = create_speakers();
+        let live = LiveSettings {
+            muted: BTreeSet::from(["l".to_string()]),
+            soloed: BTreeSet::from(["l".to_string(), "sub1".to_string()]),
+        };
+        //muting wins over solo
+        assert_eq!(live.muted_speakers(&speakers), vec!["l", "r", "c"]);
+    }
+    #[test]
+    fn test_unknown_speakers() {
+        let speakers = create_speakers();
+        let live = LiveSettings {
+            muted: BTreeSet::from(["sl".to_string()]),
+            soloed: BTreeSet::from(["l".to_string()]),
+        };
+        assert_eq!(live.unknown_speakers(&speakers), vec!["sl"]);
+    }
+}
@@ -1,35 +1,65 @@
 #[macro_use]
 extern crate rocket;
 use chrono::Utc;
+use rocket::data::{Data, ToByteUnit};
 use rocket::fairing::{self, AdHoc};
 use rocket::fs::{relative, FileServer};
-use rocket::response::status::BadRequest;
+use rocket::http::Status;
+use rocket::response::status::{BadRequest, Custom};
 use rocket::serde::{json, json::Json, Serialize};
+use rocket::tokio::sync::Mutex;
 use rocket::{Build, Rocket, State};
 use rocket_db_pools::sqlx::{self};
 use rocket_db_pools::{Connection, Database};
 use std::collections::BTreeMap;
+use std::path::Path;
 use tungstenite::{connect, Message};
 use url::Url;
+mod autoeq;
 mod devices;
 mod filters;
+mod impulses;
+mod live;
 mod mixers;
 mod pipeline;
 mod processor;
+mod response;
+mod rew;
+mod targets;
+mod validation;
 
+use autoeq::{auto_eq, interpolate, parse_response_text, AutoEqRequest};
 use devices::Devices;
-use filters::{compute_peq_filter, create_crossover_filters, create_output_filters, SpeakerAdjust};
+use filters::{
+    compute_impulse_filter, compute_peq_filter, create_crossover_filters, create_dither_filter,
+    create_driver_filters, create_global_filters, create_headroom_filter, create_loudness_filter,
+    create_output_filters, create_phase_alignment_filters, create_upmix_filters, expand_groups,
+    global_filter_name, mute_filters, output_speakers, SpeakerAdjust,
+};
+use impulses::{
+    parse_raw, parse_wav, validate_impulse_filename, validate_impulse_info, ImpulseFileType,
+    ImpulseResponse, SampleFormat,
+};
+use live::LiveSettings;
 use mixers::{
     combine_inputs, combine_mixer_name, get_speaker_counts, input_speaker_count,
     output_speaker_count_no_mixer, split_inputs, split_mixer_name, Mixer, SpeakerCounts,
 };
-use pipeline::{create_crossover_pipeline, create_per_speaker_pipeline, Pipeline};
+use pipeline::{
+    create_crossover_pipeline, create_per_speaker_pipeline, create_phase_alignment_pipeline,
+    create_upmix_pipeline, Pipeline,
+};
 use processor::{
-    DeviceType, Filter, ProcessorSettings, ProcessorSettingsForCamilla, SelectedDistanceType,
-    Speaker, SpeakerForUI,
+    ChannelRole, DeviceType, DitherType, Driver, Filter, InputLayout, LfeInput, Loudness,
+    PhaseAlignment, ProcessorSettings, ProcessorSettingsForCamilla, SelectedDistanceType, Speaker,
+    SpeakerForUI, SpeakerGroup, Upmix, FEET_PER_MS, METERS_PER_MS,
 };
 
 use crate::pipeline::create_per_speaker_pipeline_no_mixer;
+use response::{compute_response, peak_gains, FrequencyResponse, Headroom};
+use rew::{export_filter_text, parse_filter_text, ExportFormat, ImportedFilters};
+use targets::{group_target_points, validate_target_curve, TargetCurve, TargetPoint};
+use validation::{validate_settings, validate_speaker_references, ValidationError};
 
 #[derive(Database)]
 #[database("settings")]
@@ -72,6 +102,31 @@ struct ConfigVersion {
 struct SelectedDistanceAndDevice {
     selected_distance: SelectedDistanceType,
     device: DeviceType,
+    dither: DitherType,
+    headroom_compensation: bool,
+    input_layout: InputLayout,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+/// A version with imported filters, along with the filter lines that could not be imported
+struct ImportedConfiguration {
+    settings: ProcessorSettings,
+    unmapped: Vec<String>,
+}
+
+#[derive(Responder)]
+/// Errors from saving a configuration.
+/// Invalid configurations are rejected with 422 and every problem that was found
+enum SaveConfigurationError {
+    BadRequest(BadRequest<String>),
+    Invalid(Custom<Json<Vec<ValidationError>>>),
+}
+
+impl From<BadRequest<String>> for SaveConfigurationError {
+    fn from(e: BadRequest<String>) -> Self {
+        SaveConfigurationError::BadRequest(e)
+    }
 }
 
 #[derive(Serialize)]
@@ -85,14 +140,19 @@ struct SetConfig {
 /// this is used purely to store state and pass to mixer and filter creators
 struct ConfigurationMapping<'a> {
     peq_filters: BTreeMap<&'a String, Vec<(usize, &'a Filter)>>,
+    impulse_filters: BTreeMap<&'a String, (i32, &'a ImpulseResponse)>,
     speaker_counts: SpeakerCounts,
 }
 
 struct CamillaSettings {
     websocket_url: String,
     //config_file_location: String,
+    impulse_directory: String, //uploaded impulse responses are stored here, camilla reads them directly
 }
 
+/// upload limit for impulse responses, in mebibytes
+const MAX_IMPULSE_UPLOAD_MIB: usize = 64;
+
 /// runs on every startup, idempotent table creation
 async fn run_migrations(rocket: Rocket<Build>) -> fairing::Result {
     match Settings::fetch(&rocket) {
@@ -126,18 +186,116 @@ async fn load_latest_config(rocket: Rocket<Build>) -> fairing::Result {
     }
 }
 
-/// settings stored in sqlite are converted to the appropriate camilla configuration
+impl CamillaConfig {
+    /// worst case gain of every output channel, see `response::peak_gains`
+    fn peak_gains(&self) -> Vec<f32> {
+        peak_gains(
+            &self.pipeline,
+            &self.filters,
+            &self.mixers,
+            self.devices.capture_channels(),
+            self.devices.playback_channels(),
+            self.devices.samplerate(),
+        )
+    }
+
+    /// response of every speaker and driver output, see `response::compute_response`
+    fn response(&self, settings: &ProcessorSettingsForCamilla) -> FrequencyResponse {
+        compute_response(
+            &self.pipeline,
+            &self.filters,
+            &self.mixers,
+            self.devices.capture_channels(),
+            settings,
+            self.devices.samplerate(),
+        )
+    }
+}
+
+/// settings stored in sqlite are converted to the appropriate camilla configuration.
+/// With headroom compensation, every output is lowered by the highest peak gain so nothing clips
 fn convert_processor_settings_to_camilla(
     settings: &ProcessorSettingsForCamilla,
+) -> Result<(CamillaConfig, Headroom), json::serde_json::Error> {
+    let config = create_camilla_config(settings, None)?;
+    let peak_gains = config.peak_gains();
+    let peak_gain = peak_gains.iter().cloned().fold(0.0, f32::max);
+    if settings.headroom_compensation && peak_gain > 0.0 {
+        let config = create_camilla_config(settings, Some(peak_gain))?;
+        Ok((
+            config,
+            Headroom {
+                peak_gains,
+                applied: peak_gain,
+            },
+        ))
+    } else {
+        Ok((
+            config,
+            Headroom {
+                peak_gains,
+                applied: 0.0,
+            },
+        ))
+    }
+}
+
+fn create_camilla_config(
+    settings: &ProcessorSettingsForCamilla,
+    headroom: Option<f32>,
 ) -> Result<CamillaConfig, json::serde_json::Error> {
+    //roles in use make up the wave layout, the LFE channel is there whenever the source has one
+    let roles_in_use: Vec<ChannelRole> = settings
+        .speakers
+        .iter()
+        .filter(|s| !s.is_subwoofer)
+        .filter_map(|s| s.role)
+        .chain(settings.lfe.enabled.then_some(ChannelRole::Lfe))
+        .collect();
+    let layout = settings
+        .input_layout
+        .roles(&settings.custom_layout, &roles_in_use);
     let configuration_mapping = ConfigurationMapping {
         peq_filters: compute_peq_filter(&settings.filters),
-        speaker_counts: get_speaker_counts(&settings.speakers),
+        impulse_filters: compute_impulse_filter(&settings.speakers, &settings.impulse_responses),
+        speaker_counts: get_speaker_counts(
+            &settings.speakers,
+            &settings.drivers,
+            &settings.lfe,
+            &layout,
+            &settings.upmix,
+        ),
     };
 
-    let split_mixer = split_inputs(&settings.speakers, &configuration_mapping.speaker_counts);
-    let output_filters =
-        create_output_filters(&settings.speakers, &configuration_mapping.peq_filters);
+    let split_mixer = split_inputs(
+        &settings.speakers,
+        &settings.drivers,
+        &configuration_mapping.speaker_counts,
+    );
+    let mut output_filters = create_output_filters(
+        &output_speakers(&settings.speakers, &settings.drivers),
+        &configuration_mapping.peq_filters,
+        &configuration_mapping.impulse_filters,
+        &settings.selected_distance,
+    );
+    let global_filters = create_global_filters(&settings.global_filters);
+    let headroom_filter = headroom.map(create_headroom_filter).unwrap_or_default();
+    let loudness_filter = create_loudness_filter(&settings.loudness);
+    let shared_filters: Vec<String> = settings
+        .global_filters
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| f.enabled)
+        .map(|(index, _)| global_filter_name(index))
+        .chain(headroom_filter.keys().cloned())
+        .chain(loudness_filter.keys().cloned())
+        .collect();
+    output_filters.extend(global_filters);
+    output_filters.extend(headroom_filter);
+    output_filters.extend(loudness_filter);
+    let dither_filter = create_dither_filter(&settings.dither, settings.device.playback_bits());
+    let final_filters: Vec<String> = dither_filter.keys().cloned().collect();
+    output_filters.extend(dither_filter);
 
     match split_mixer {
         Some((split_mixer, input_channel_mapping, output_channel_mapping)) => {
@@ -148,42 +306,43 @@ fn convert_processor_settings_to_camilla(
             );
             let mut per_speaker_pipeline = create_per_speaker_pipeline(
                 &output_channel_mapping,
+                &settings.drivers,
                 &configuration_mapping.peq_filters,
+                &configuration_mapping.impulse_filters,
+                &shared_filters,
+                &final_filters,
             );
 
-            let devices = match settings.device {
-                DeviceType::MotuMk5 => Devices::motu_mk5(
-                    split_mixer.channels.num_in_channel,
-                    combine_mixer.channels.num_out_channel,
-                ),
-                DeviceType::OktoDac8 => Devices::okto_dac8(
-                    split_mixer.channels.num_in_channel,
-                    combine_mixer.channels.num_out_channel,
-                ),
-                DeviceType::ToppingDm7 => Devices::topping_dm7(
-                    split_mixer.channels.num_in_channel,
-                    combine_mixer.channels.num_out_channel,
-                ),
-                DeviceType::HDMI => Devices::hdmi_osmc_pi(
-                    split_mixer.channels.num_in_channel,
-                    combine_mixer.channels.num_out_channel,
-                ),
-            };
-
-            let mixers: BTreeMap<String, Mixer> = BTreeMap::from_iter(
-                vec![
-                    (split_mixer_name(), split_mixer),
-                    (combine_mixer_name(), combine_mixer),
-                ]
-                .into_iter(),
+            let devices = Devices::new(
+                &settings.device,
+                split_mixer.channels.num_in_channel,
+                combine_mixer.channels.num_out_channel,
             );
+
+            let mixers: BTreeMap<String, Mixer> = BTreeMap::from_iter(vec![
+                (split_mixer_name(), split_mixer),
+                (combine_mixer_name(), combine_mixer),
+            ]);
             let mut filters: BTreeMap<String, SpeakerAdjust> =
                 create_crossover_filters(&settings.speakers);
+            filters.extend(create_phase_alignment_filters(&settings.phase_alignment));
+            filters.extend(create_driver_filters(&settings.drivers));
+            filters.extend(create_upmix_filters(&settings.upmix));
             let mut pipeline = create_crossover_pipeline(
                 split_mixer_name(),
                 combine_mixer_name(),
                 &input_channel_mapping,
             );
+            //upmix and phase alignment steps run on the split tracks, ahead of the crossovers
+            pipeline.splice(
+                1..1,
+                create_upmix_pipeline(&input_channel_mapping, &settings.speakers, &settings.upmix)
+                    .into_iter()
+                    .chain(create_phase_alignment_pipeline(
+                        &input_channel_mapping,
+                        &settings.phase_alignment,
+                    )),
+            );
             pipeline.append(&mut per_speaker_pipeline);
             filters.extend(output_filters);
 
@@ -202,17 +361,15 @@ fn convert_processor_settings_to_camilla(
             let per_speaker_pipeline = create_per_speaker_pipeline_no_mixer(
                 &settings.speakers,
                 &configuration_mapping.peq_filters,
+                &configuration_mapping.impulse_filters,
+                &shared_filters,
+                &final_filters,
             );
             let result = CamillaConfig {
                 pipeline: per_speaker_pipeline,
                 filters: output_filters,
                 mixers: BTreeMap::new(),
-                devices: match settings.device {
-                    DeviceType::MotuMk5 => Devices::motu_mk5(input_channels, output_channels),
-                    DeviceType::OktoDac8 => Devices::okto_dac8(input_channels, output_channels),
-                    DeviceType::ToppingDm7 => Devices::topping_dm7(input_channels, output_channels),
-                    DeviceType::HDMI => Devices::hdmi_osmc_pi(input_channels, output_channels),
-                },
+                devices: Devices::new(&settings.device, input_channels, output_channels),
             };
             Ok(result)
         }
@@ -228,7 +385,10 @@ async fn get_selected_distance_and_device(
         SelectedDistanceAndDevice,
         r#"SELECT 
             selected_distance as "selected_distance: crate::processor::SelectedDistanceType",
-            device as "device: crate::processor::DeviceType"
+            device as "device: crate::processor::DeviceType",
+            dither as "dither: crate::processor::DitherType",
+            headroom_compensation as "headroom_compensation: bool",
+            input_layout as "input_layout: crate::processor::InputLayout"
             from versions where version=?"#,
         version
     )
@@ -237,6 +397,68 @@ async fn get_selected_distance_and_device(
     Ok(selected_distance_and_device)
 }
 
+/// reads the loudness settings for the specific configuration version, if loudness is enabled
+async fn get_loudness(db: &Settings, version: i32) -> Result<Option<Loudness>, sqlx::Error> {
+    let loudness = sqlx::query_as!(
+        Loudness,
+        r#"SELECT 
+            loudness_reference_level as "reference_level!: f32",
+            loudness_high_boost as "high_boost!: f32",
+            loudness_low_boost as "low_boost!: f32"
+            from versions where version=? and loudness_reference_level is not null"#,
+        version
+    )
+    .fetch_optional(&**db)
+    .await?;
+    Ok(loudness)
+}
+
+/// reads the upmix settings for the specific configuration version, if the source is upmixed
+async fn get_upmix(db: &Settings, version: i32) -> Result<Option<Upmix>, sqlx::Error> {
+    let upmix = sqlx::query_as!(
+        Upmix,
+        r#"SELECT 
+            upmix_center_gain as "center_gain!: f32",
+            upmix_surround_gain as "surround_gain!: f32",
+            upmix_surround_delay as "surround_delay!: f32",
+            upmix_lfe_gain as "lfe_gain!: f32",
+            upmix_lfe_lowpass as "lfe_lowpass?: i32"
+            from versions where version=? and upmix_center_gain is not null"#,
+        version
+    )
+    .fetch_optional(&**db)
+    .await?;
+    Ok(upmix)
+}
+
+/// reads the LFE input settings for the specific configuration version
+async fn get_lfe(db: &Settings, version: i32) -> Result<LfeInput, sqlx::Error> {
+    let lfe = sqlx::query_as!(
+        LfeInput,
+        r#"SELECT 
+            lfe_enabled as "enabled: bool",
+            lfe_channel as "channel?: i32",
+            lfe_gain as "gain: f32"
+            from versions where version=?"#,
+        version
+    )
+    .fetch_one(&**db)
+    .await?;
+    Ok(lfe)
+}
+
+/// reads the roles of the input channels, in order, of a version with a custom input layout
+async fn get_custom_layout(db: &Settings, version: i32) -> Result<Vec<ChannelRole>, sqlx::Error> {
+    let roles = sqlx::query!(
+        r#"SELECT role as "role: crate::processor::ChannelRole"
+        from custom_input_layout where version=? order by channel_index"#,
+        version
+    )
+    .fetch_all(&**db)
+    .await?;
+    Ok(roles.into_iter().map(|r| r.role).collect())
+}
+
 /// reads filters for the specific configration version
 async fn get_filters(db: &Settings, version: i32) -> Result<Vec<Filter>, sqlx::Error> {
     let filters = sqlx::query_as!(
@@ -244,7 +466,12 @@ async fn get_filters(db: &Settings, version: i32) -> Result<Vec<Filter>, sqlx::E
         r#"SELECT speaker, 
         freq as "freq: i32", 
         gain as "gain: f32", 
-        q as "q: f32"
+        q as "q: f32",
+        filter_type as "filter_type: crate::processor::PeqType",
+        slope as "slope?: f32",
+        freq_target as "freq_target?: i32",
+        q_target as "q_target?: f32",
+        enabled as "enabled: bool"
         from filters where version=?"#,
         version
     )
@@ -253,6 +480,103 @@ async fn get_filters(db: &Settings, version: i32) -> Result<Vec<Filter>, sqlx::E
     Ok(filters)
 }
 
+/// reads the filters applied to every speaker for the specific configuration version
+async fn get_global_filters(db: &Settings, version: i32) -> Result<Vec<Filter>, sqlx::Error> {
+    let filters = sqlx::query_as!(
+        Filter,
+        r#"SELECT '' as "speaker!: String", 
+        freq as "freq: i32", 
+        gain as "gain: f32", 
+        q as "q: f32",
+        filter_type as "filter_type: crate::processor::PeqType",
+        slope as "slope?: f32",
+        freq_target as "freq_target?: i32",
+        q_target as "q_target?: f32",
+        enabled as "enabled: bool"
+        from global_filters where version=? order by filter_index"#,
+        version
+    )
+    .fetch_all(&**db)
+    .await?;
+    Ok(filters)
+}
+
+/// reads the all-pass filters run on each speaker's split track for the specific configuration version
+async fn get_phase_alignment(
+    db: &Settings,
+    version: i32,
+) -> Result<Vec<PhaseAlignment>, sqlx::Error> {
+    let phase_alignment = sqlx::query_as!(
+        PhaseAlignment,
+        r#"SELECT speaker, 
+        freq as "freq: i32", 
+        allpass_order as "order: crate::processor::AllpassOrder",
+        q as "q: f32"
+        from phase_alignment where version=? order by filter_index"#,
+        version
+    )
+    .fetch_all(&**db)
+    .await?;
+    Ok(phase_alignment)
+}
+
+/// reads the drivers of the multi-way speakers for the specific configuration version
+async fn get_drivers(db: &Settings, version: i32) -> Result<Vec<Driver>, sqlx::Error> {
+    let drivers = sqlx::query_as!(
+        Driver,
+        r#"SELECT speaker, 
+        driver, 
+        highpass as "highpass?: i32", 
+        lowpass as "lowpass?: i32", 
+        crossover_type as "crossover_type: crate::processor::CrossoverFamily",
+        crossover_order as "crossover_order: i32",
+        delay as "delay: f32",
+        gain as "gain: f32",
+        inverted as "inverted: bool",
+        output_port as "output_port?: i32"
+        from drivers where version=? order by driver_index"#,
+        version
+    )
+    .fetch_all(&**db)
+    .await?;
+    Ok(drivers)
+}
+
+/// reads the linked speaker groups, with their members, for the specific configuration version
+async fn get_groups(db: &Settings, version: i32) -> Result<Vec<SpeakerGroup>, sqlx::Error> {
+    let groups = sqlx::query!(
+        r#"SELECT 
+        group_name, 
+        crossover as "crossover?: i32", 
+        gain as "gain?: f32",
+        crossover_enabled as "crossover_enabled: bool"
+        from speaker_groups where version=? order by group_index"#,
+        version
+    )
+    .fetch_all(&**db)
+    .await?;
+    let members = sqlx::query!(
+        r#"SELECT group_name, speaker from speaker_group_members where version=?"#,
+        version
+    )
+    .fetch_all(&**db)
+    .await?;
+    Ok(groups
+        .into_iter()
+        .map(|group| SpeakerGroup {
+            speakers: members
+                .iter()
+                .filter(|m| m.group_name == group.group_name)
+                .map(|m| m.speaker.clone())
+                .collect(),
+            name: group.group_name,
+            crossover: group.crossover,
+            gain: group.gain,
+            crossover_enabled: group.crossover_enabled,
+        })
+        .collect())
+}
+
 /// reads speakers that map to camilla speakers for the specific configration version
 async fn get_speakers_for_camilla(
     db: &Settings,
@@ -265,7 +589,15 @@ async fn get_speakers_for_camilla(
         crossover as "crossover: i32", 
         delay as "delay: f32", 
         gain as "gain: f32", 
-        is_subwoofer as "is_subwoofer: bool"
+        is_subwoofer as "is_subwoofer: bool",
+        crossover_type as "crossover_type: crate::processor::CrossoverFamily",
+        crossover_speaker_order as "crossover_speaker_order: i32",
+        crossover_subwoofer_order as "crossover_subwoofer_order: i32",
+        impulse_response,
+        impulse_response_channel as "impulse_response_channel: i32",
+        inverted as "inverted: bool",
+        role as "role?: crate::processor::ChannelRole",
+        output_port as "output_port?: i32"
         from speakers_for_camilla where version=?"#,
         version
     )
@@ -287,7 +619,17 @@ async fn get_speakers_for_ui(
         crossover as "crossover?: i32", 
         distance as "distance: f32", 
         gain as "gain: f32", 
-        is_subwoofer as "is_subwoofer: bool"
+        is_subwoofer as "is_subwoofer: bool",
+        crossover_type as "crossover_type: crate::processor::CrossoverFamily",
+        crossover_speaker_order as "crossover_speaker_order: i32",
+        crossover_subwoofer_order as "crossover_subwoofer_order: i32",
+        impulse_response,
+        impulse_response_channel as "impulse_response_channel: i32",
+        inverted as "inverted: bool",
+        crossover_enabled as "crossover_enabled: bool",
+        target,
+        role as "role?: crate::processor::ChannelRole",
+        output_port as "output_port?: i32"
         from speakers_settings_for_ui where version=?"#,
         version,
     )
@@ -296,19 +638,95 @@ async fn get_speakers_for_ui(
     Ok(speakers)
 }
 
+/// reads impulse responses referenced by speakers in the specific configuration version
+async fn get_impulse_responses_for_version(
+    db: &Settings,
+    version: i32,
+) -> Result<Vec<ImpulseResponse>, sqlx::Error> {
+    let impulse_responses = sqlx::query_as!(
+        ImpulseResponse,
+        r#"SELECT 
+        filename, 
+        path, 
+        file_type as "file_type: crate::impulses::ImpulseFileType", 
+        sample_format as "sample_format?: crate::impulses::SampleFormat", 
+        sample_rate as "sample_rate: i32", 
+        channels as "channels: i32", 
+        length as "length: i32", 
+        upload_date
+        from impulse_responses where filename in (
+            SELECT impulse_response from speakers_for_camilla where version=?
+        )"#,
+        version
+    )
+    .fetch_all(&**db)
+    .await?;
+    Ok(impulse_responses)
+}
+
+/// reads a single impulse response by its filename
+async fn get_impulse_response(
+    db: &mut sqlx::SqliteConnection,
+    filename: &str,
+) -> Result<Option<ImpulseResponse>, sqlx::Error> {
+    let impulse_response = sqlx::query_as!(
+        ImpulseResponse,
+        r#"SELECT 
+        filename, 
+        path, 
+        file_type as "file_type: crate::impulses::ImpulseFileType", 
+        sample_format as "sample_format?: crate::impulses::SampleFormat", 
+        sample_rate as "sample_rate: i32", 
+        channels as "channels: i32", 
+        length as "length: i32", 
+        upload_date
+        from impulse_responses where filename=?"#,
+        filename
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(impulse_response)
+}
+
 /// gets configuration for camilla from database
 async fn get_config_for_camilla_from_db(
     db: &Settings,
     version: i32,
 ) -> Result<ProcessorSettingsForCamilla, sqlx::Error> {
-    let SelectedDistanceAndDevice { device, .. } =
-        get_selected_distance_and_device(db, version).await?;
-    let filters = get_filters(db, version).await?;
-    let speakers = get_speakers_for_camilla(db, version).await?;
+    let SelectedDistanceAndDevice {
+        selected_distance,
+        device,
+        dither,
+        headroom_compensation,
+        input_layout,
+    } = get_selected_distance_and_device(db, version).await?;
+    let groups = get_groups(db, version).await?;
+    let mut speakers = get_speakers_for_camilla(db, version).await?;
+    let filters = expand_groups(&groups, &mut speakers, get_filters(db, version).await?);
+    let global_filters = get_global_filters(db, version).await?;
+    let phase_alignment = get_phase_alignment(db, version).await?;
+    let drivers = get_drivers(db, version).await?;
+    let impulse_responses = get_impulse_responses_for_version(db, version).await?;
+    let loudness = get_loudness(db, version).await?;
+    let lfe = get_lfe(db, version).await?;
+    let custom_layout = get_custom_layout(db, version).await?;
+    let upmix = get_upmix(db, version).await?;
     Ok(ProcessorSettingsForCamilla {
         filters,
+        global_filters,
+        phase_alignment,
         speakers,
+        drivers,
+        lfe,
+        input_layout,
+        custom_layout,
         device,
+        selected_distance,
+        impulse_responses,
+        loudness,
+        upmix,
+        dither,
+        headroom_compensation,
     })
 }
 
@@ -317,20 +735,39 @@ async fn get_config_from_db(db: &Settings, version: i32) -> Result<ProcessorSett
     let SelectedDistanceAndDevice {
         selected_distance,
         device,
-    } = get_selected_distance_and_device(&db, version).await?;
-    let filters = get_filters(&db, version).await?;
-    let speakers = get_speakers_for_ui(&db, version).await?;
+        dither,
+        headroom_compensation,
+        input_layout,
+    } = get_selected_distance_and_device(db, version).await?;
+    let filters = get_filters(db, version).await?;
+    let global_filters = get_global_filters(db, version).await?;
+    let phase_alignment = get_phase_alignment(db, version).await?;
+    let speakers = get_speakers_for_ui(db, version).await?;
+    let drivers = get_drivers(db, version).await?;
+    let groups = get_groups(db, version).await?;
+    let loudness = get_loudness(db, version).await?;
+    let lfe = get_lfe(db, version).await?;
+    let custom_layout = get_custom_layout(db, version).await?;
+    let upmix = get_upmix(db, version).await?;
     Ok(ProcessorSettings {
         filters,
+        global_filters,
+        phase_alignment,
         speakers,
+        drivers,
+        lfe,
+        input_layout,
+        custom_layout,
+        groups,
         selected_distance,
         device,
+        loudness,
+        upmix,
+        dither,
+        headroom_compensation,
     })
 }
 
-const METERS_PER_MS: f32 = 0.3430;
-const FEET_PER_MS: f32 = 1.1164;
-
 /// given the speaker with the largest distance,
 /// the current speakers distance, and the speed of sound,
 /// gets the number of millisecond delay
@@ -342,7 +779,8 @@ fn convert_distance_to_delay(
     (largest_distance - current_distance) / distance_per_ms
 }
 
-/// apply delays to each speaker given the distance type (MS, METERS, FEET)
+/// apply delays to each speaker given the distance type.
+/// Samples and millimetres are left for camilla to convert
 fn update_speaker_delays(
     selected_distance: &SelectedDistanceType,
     speakers: &[SpeakerForUI],
@@ -351,7 +789,7 @@ fn update_speaker_delays(
         speakers.iter().fold(
             0.0,
             |accum: f32, speaker: &SpeakerForUI| match selected_distance {
-                SelectedDistanceType::MS => 0.0,
+                SelectedDistanceType::MS | SelectedDistanceType::SAMPLES => 0.0,
                 _ => {
                     if accum < speaker.distance {
                         speaker.distance
@@ -363,28 +801,30 @@ fn update_speaker_delays(
         );
     speakers
         .iter()
-        .map(|speaker| match selected_distance {
-            SelectedDistanceType::METERS => Speaker {
-                speaker: speaker.speaker.clone(), //Hate doing this, but no great ways to have two arrays sharing same string reference.  Using &' str errors on the sqlx macro
-                crossover: speaker.crossover,
-                delay: convert_distance_to_delay(max_distance, speaker.distance, METERS_PER_MS),
-                gain: speaker.gain,
-                is_subwoofer: speaker.is_subwoofer,
-            },
-            SelectedDistanceType::FEET => Speaker {
-                speaker: speaker.speaker.clone(), //Hate doing this, but no great ways to have two arrays sharing same string reference.  Using &' str errors on the sqlx macro
-                crossover: speaker.crossover,
-                delay: convert_distance_to_delay(max_distance, speaker.distance, FEET_PER_MS),
-                gain: speaker.gain,
-                is_subwoofer: speaker.is_subwoofer,
-            },
-            SelectedDistanceType::MS => Speaker {
-                speaker: speaker.speaker.clone(), //Hate doing this, but no great ways to have two arrays sharing same string reference.  Using &' str errors on the sqlx macro
-                crossover: speaker.crossover,
-                delay: speaker.distance,
-                gain: speaker.gain,
-                is_subwoofer: speaker.is_subwoofer,
+        .map(|speaker| Speaker {
+            speaker: speaker.speaker.clone(), //Hate doing this, but no great ways to have two arrays sharing same string reference.  Using &' str errors on the sqlx macro
+            //camilla only sees the crossovers that are switched on
+            crossover: speaker.crossover.filter(|_| speaker.crossover_enabled),
+            delay: match selected_distance {
+                SelectedDistanceType::METERS => {
+                    convert_distance_to_delay(max_distance, speaker.distance, METERS_PER_MS)
+                }
+                SelectedDistanceType::FEET => {
+                    convert_distance_to_delay(max_distance, speaker.distance, FEET_PER_MS)
+                }
+                SelectedDistanceType::MILLIMETERS => max_distance - speaker.distance,
+                SelectedDistanceType::MS | SelectedDistanceType::SAMPLES => speaker.distance,
             },
+            gain: speaker.gain,
+            is_subwoofer: speaker.is_subwoofer,
+            crossover_type: speaker.crossover_type,
+            crossover_speaker_order: speaker.crossover_speaker_order,
+            crossover_subwoofer_order: speaker.crossover_subwoofer_order,
+            impulse_response: speaker.impulse_response.clone(),
+            impulse_response_channel: speaker.impulse_response_channel,
+            inverted: speaker.inverted,
+            role: speaker.role,
+            output_port: speaker.output_port,
         })
         .collect()
 }
@@ -442,7 +882,7 @@ async fn config_latest(db: &Settings) -> Result<Json<ProcessorSettings>, BadRequ
     .map_err(|e| BadRequest(e.to_string()))?;
     get_config_from_db(db, version)
         .await
-        .map(|v| Json(v))
+        .map(Json)
         .map_err(|e| BadRequest(e.to_string()))
 }
 
@@ -453,10 +893,160 @@ async fn config_version(
 ) -> Result<Json<ProcessorSettings>, BadRequest<String>> {
     get_config_from_db(db, version)
         .await
-        .map(|v| Json(v))
+        .map(Json)
         .map_err(|e| BadRequest(e.to_string()))
 }
 
+#[post("/config/<version>/import/<speaker>", data = "<text>")]
+/// Replaces the PEQ of a speaker or driver in the version with filters from Room EQ Wizard or Equalizer APO filter text.
+/// Nothing is saved; the returned configuration can be reviewed and then saved with PUT /config
+async fn import_filters(
+    db: &Settings,
+    version: i32,
+    speaker: &str,
+    text: String,
+) -> Result<Json<ImportedConfiguration>, BadRequest<String>> {
+    let mut settings = get_config_from_db(db, version)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?;
+    if !has_peq(&settings, speaker) {
+        return Err(BadRequest(format!(
+            "Speaker or driver {} is not part of version {}",
+            speaker, version
+        )));
+    }
+    let ImportedFilters { filters, unmapped } = parse_filter_text(&text, speaker);
+    settings.filters.retain(|f| f.speaker != speaker);
+    settings.filters.extend(filters);
+    Ok(Json(ImportedConfiguration { settings, unmapped }))
+}
+
+/// speakers, and the drivers of multi-way speakers, each have PEQ of their own
+fn has_peq(settings: &ProcessorSettings, name: &str) -> bool {
+    settings.speakers.iter().any(|s| s.speaker == name)
+        || settings.drivers.iter().any(|d| d.driver == name)
+}
+
+#[post(
+    "/config/<version>/autoeq/<speaker>",
+    format = "json",
+    data = "<request>"
+)]
+/// Fits PEQ filters for a speaker or driver in the version from a measurement and a target curve.
+/// Nothing is saved; the filters can be added to the configuration and saved with PUT /config
+async fn auto_eq_filters(
+    db: &Settings,
+    version: i32,
+    speaker: &str,
+    request: Json<AutoEqRequest>,
+) -> Result<Json<Vec<Filter>>, BadRequest<String>> {
+    let settings = get_config_from_db(db, version)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?;
+    if !has_peq(&settings, speaker) {
+        return Err(BadRequest(format!(
+            "Speaker or driver {} is not part of version {}",
+            speaker, version
+        )));
+    }
+    let target = match settings.speakers.iter().find(|s| s.speaker == speaker) {
+        Some(SpeakerForUI {
+            target: Some(name), ..
+        }) if request.target.trim().is_empty() => {
+            let mut connection = db.acquire().await.map_err(|e| BadRequest(e.to_string()))?;
+            get_target_curve(&mut connection, name)
+                .await
+                .map_err(|e| BadRequest(e.to_string()))?
+                .map(|curve| curve.levels())
+                .unwrap_or_default()
+        }
+        _ => parse_response_text(&request.target),
+    };
+    let samplerate = settings.device.samplerate();
+    let request = request.into_inner();
+    let speaker = speaker.to_string();
+    //fitting re-optimises every filter on each addition, so it runs off the async workers
+    rocket::tokio::task::spawn_blocking(move || auto_eq(&request, &target, &speaker, samplerate))
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?
+        .map(Json)
+        .map_err(BadRequest)
+}
+
+#[get("/config/<version>/export?<format>&<full>&<speaker>")]
+/// Renders the PEQ of the version as Equalizer APO config or Room EQ Wizard filter text.
+/// With `full=true` crossovers, delays and gains are included as well, and `speaker` limits it
+/// to one speaker or driver, which REW text needs
+async fn export_filters(
+    db: &Settings,
+    version: i32,
+    format: ExportFormat,
+    full: Option<bool>,
+    speaker: Option<&str>,
+) -> Result<String, BadRequest<String>> {
+    let settings = get_config_for_camilla_from_db(db, version)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?;
+    export_filter_text(&settings, version, format, full.unwrap_or(false), speaker)
+        .map_err(BadRequest)
+}
+
+#[get("/config/<version>/response")]
+/// Computed magnitude, phase and group delay of every output channel at the device sample rate,
+/// to inspect a version before applying it
+async fn config_response(
+    db: &Settings,
+    version: i32,
+) -> Result<Json<FrequencyResponse>, BadRequest<String>> {
+    let settings = get_config_for_camilla_from_db(db, version)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?;
+    //the response of the config camilla would receive, with headroom compensation applied
+    let (config, _) =
+        convert_processor_settings_to_camilla(&settings).map_err(|e| BadRequest(e.to_string()))?;
+    let mut response = config.response(&settings);
+    let speaker_targets = sqlx::query!(
+        r#"SELECT speaker, target as "target!: String" from speakers_settings_for_ui where version=? and target is not null"#,
+        version
+    )
+    .fetch_all(&**db)
+    .await
+    .map_err(|e| BadRequest(e.to_string()))?;
+    let mut connection = db.acquire().await.map_err(|e| BadRequest(e.to_string()))?;
+    let curves = get_target_curves(&mut connection)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?;
+    for channel in response.channels.iter_mut() {
+        channel.target = speaker_targets
+            .iter()
+            .find(|t| t.speaker == channel.speaker)
+            .and_then(|t| curves.iter().find(|c| c.name == t.target))
+            .map(|curve| {
+                let levels = curve.levels();
+                response
+                    .frequencies
+                    .iter()
+                    .map(|f| interpolate(&levels, *f as f64) as f32)
+                    .collect()
+            });
+    }
+    Ok(Json(response))
+}
+
+#[get("/config/<version>/headroom")]
+/// Worst case peak gain of every output channel, and the headroom taken when compensation is enabled
+async fn config_headroom(
+    db: &Settings,
+    version: i32,
+) -> Result<Json<Headroom>, BadRequest<String>> {
+    let settings = get_config_for_camilla_from_db(db, version)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?;
+    let (_, headroom) =
+        convert_processor_settings_to_camilla(&settings).map_err(|e| BadRequest(e.to_string()))?;
+    Ok(Json(headroom))
+}
+
 #[post("/config/apply/<version>", format = "application/json")]
 /// Configurations can be saved without actually be implemented or applied to camilla.  
 /// This endpoint applies the selected version to camilla
@@ -464,8 +1054,13 @@ async fn apply_config_version(
     db: &Settings,
     version: i32,
     camilla_settings: &State<CamillaSettings>,
-) -> Result<(), BadRequest<String>> {
-    apply_config_to_camilla(db, version, camilla_settings).await?;
+    live_settings: &State<Mutex<LiveSettings>>,
+) -> Result<Json<Headroom>, BadRequest<String>> {
+    //held while camilla is updated, so a mute or solo can't be sent on top of the old version
+    let mut live_settings = live_settings.lock().await;
+    let headroom = apply_config_to_camilla(db, version, camilla_settings).await?;
+    //the applied version is sent as saved, so any live mute/solo no longer applies
+    *live_settings = LiveSettings::default();
 
     let _ = sqlx::query!("DELETE from applied_version")
         .execute(&**db)
@@ -476,23 +1071,32 @@ async fn apply_config_version(
         .await
         .map_err(|e| BadRequest(e.to_string()))?;
 
-    Ok(())
+    Ok(Json(headroom))
 }
 
 async fn apply_config_to_camilla(
     db: &Settings,
     version: i32,
     camilla_settings: &CamillaSettings,
-) -> Result<(), BadRequest<String>> {
+) -> Result<Headroom, BadRequest<String>> {
     let settings = get_config_for_camilla_from_db(db, version)
         .await
         .map_err(|e| {
             println!("{}", e);
             BadRequest(e.to_string())
         })?;
-    let config =
+    let (config, headroom) =
         convert_processor_settings_to_camilla(&settings).map_err(|e| BadRequest(e.to_string()))?;
-    let config_as_str = json::to_string(&config).map_err(|e| BadRequest(e.to_string()))?;
+    send_config_to_camilla(&config, camilla_settings)?;
+    Ok(headroom)
+}
+
+/// sends a full configuration to camilla over the websocket
+fn send_config_to_camilla(
+    config: &CamillaConfig,
+    camilla_settings: &CamillaSettings,
+) -> Result<(), BadRequest<String>> {
+    let config_as_str = json::to_string(config).map_err(|e| BadRequest(e.to_string()))?;
     let ws_url =
         Url::parse(&camilla_settings.websocket_url).map_err(|e| BadRequest(e.to_string()))?;
     let (mut socket, _response) = connect(ws_url).map_err(|e| BadRequest(e.to_string()))?;
@@ -512,16 +1116,82 @@ async fn apply_config_to_camilla(
 async fn write_configuration(
     mut db: Connection<Settings>,
     settings: Json<ProcessorSettings>,
-) -> Result<Json<Version>, BadRequest<String>> {
+) -> Result<Json<Version>, SaveConfigurationError> {
+    let samplerate = settings.device.samplerate();
+    let mut errors = validate_settings(&settings, samplerate);
+    for speaker in settings.speakers.iter() {
+        let impulse = match &speaker.impulse_response {
+            Some(filename) => get_impulse_response(&mut db, filename)
+                .await
+                .map_err(|e| BadRequest(e.to_string()))?,
+            None => None,
+        };
+        let target = match &speaker.target {
+            Some(name) => get_target_curve(&mut db, name)
+                .await
+                .map_err(|e| BadRequest(e.to_string()))?,
+            None => None,
+        };
+        errors.extend(validate_speaker_references(
+            speaker,
+            impulse.as_ref(),
+            target.as_ref(),
+            samplerate,
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(SaveConfigurationError::Invalid(Custom(
+            Status::UnprocessableEntity,
+            Json(errors),
+        )));
+    }
     let version_date = Utc::now().to_string();
+    let loudness_reference_level = settings.loudness.as_ref().map(|l| l.reference_level);
+    let loudness_high_boost = settings.loudness.as_ref().map(|l| l.high_boost);
+    let loudness_low_boost = settings.loudness.as_ref().map(|l| l.low_boost);
+    let upmix_center_gain = settings.upmix.map(|u| u.center_gain);
+    let upmix_surround_gain = settings.upmix.map(|u| u.surround_gain);
+    let upmix_surround_delay = settings.upmix.map(|u| u.surround_delay);
+    let upmix_lfe_gain = settings.upmix.map(|u| u.lfe_gain);
+    let upmix_lfe_lowpass = settings.upmix.and_then(|u| u.lfe_lowpass);
     let ConfigVersion { version } = sqlx::query_as!(
         ConfigVersion,
         r#"INSERT INTO versions (
-            version_date, selected_distance, device
-        ) VALUES (?, ?, ?) RETURNING version as "version: i32""#,
+            version_date, 
+            selected_distance, 
+            device, 
+            loudness_reference_level, 
+            loudness_high_boost, 
+            loudness_low_boost,
+            dither,
+            headroom_compensation,
+            lfe_enabled,
+            lfe_channel,
+            lfe_gain,
+            input_layout,
+            upmix_center_gain,
+            upmix_surround_gain,
+            upmix_surround_delay,
+            upmix_lfe_gain,
+            upmix_lfe_lowpass
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING version as "version: i32""#,
         version_date,
         settings.selected_distance,
-        settings.device
+        settings.device,
+        loudness_reference_level,
+        loudness_high_boost,
+        loudness_low_boost,
+        settings.dither,
+        settings.headroom_compensation,
+        settings.lfe.enabled,
+        settings.lfe.channel,
+        settings.lfe.gain,
+        settings.input_layout,
+        upmix_center_gain,
+        upmix_surround_gain,
+        upmix_surround_delay,
+        upmix_lfe_gain,
+        upmix_lfe_lowpass
     )
     .fetch_one(&mut **db)
     .await
@@ -530,12 +1200,74 @@ async fn write_configuration(
     for (index, filter) in settings.filters.iter().enumerate() {
         let index_i32 = index as i32;
         let _ = sqlx::query!(
-            "INSERT INTO filters (version, filter_index, speaker, freq, gain, q) VALUES (?, ?, ?, ?, ?, ?)",
-            version, index_i32, filter.speaker, filter.freq, filter.gain, filter.q
+            "INSERT INTO filters (version, filter_index, speaker, freq, gain, q, filter_type, slope, freq_target, q_target, enabled) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            version, index_i32, filter.speaker, filter.freq, filter.gain, filter.q, filter.filter_type, filter.slope, filter.freq_target, filter.q_target, filter.enabled
+        )
+        .execute(&mut **db)
+        .await;
+    }
+    for (index, filter) in settings.global_filters.iter().enumerate() {
+        let index_i32 = index as i32;
+        let _ = sqlx::query!(
+            "INSERT INTO global_filters (version, filter_index, freq, gain, q, filter_type, slope, freq_target, q_target, enabled) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            version, index_i32, filter.freq, filter.gain, filter.q, filter.filter_type, filter.slope, filter.freq_target, filter.q_target, filter.enabled
         )
         .execute(&mut **db)
         .await;
     }
+    for (index, allpass) in settings.phase_alignment.iter().enumerate() {
+        let index_i32 = index as i32;
+        let _ = sqlx::query!(
+            "INSERT INTO phase_alignment (version, filter_index, speaker, freq, allpass_order, q) VALUES (?, ?, ?, ?, ?, ?)",
+            version, index_i32, allpass.speaker, allpass.freq, allpass.order, allpass.q
+        )
+        .execute(&mut **db)
+        .await;
+    }
+    for (index, role) in settings.custom_layout.iter().enumerate() {
+        let index_i32 = index as i32;
+        let _ = sqlx::query!(
+            "INSERT INTO custom_input_layout (version, channel_index, role) VALUES (?, ?, ?)",
+            version,
+            index_i32,
+            role
+        )
+        .execute(&mut **db)
+        .await;
+    }
+    for (index, driver) in settings.drivers.iter().enumerate() {
+        let index_i32 = index as i32;
+        let _ = sqlx::query!(
+            "INSERT INTO drivers (version, driver_index, speaker, driver, highpass, lowpass, crossover_type, crossover_order, delay, gain, inverted, output_port) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            version, index_i32, driver.speaker, driver.driver, driver.highpass, driver.lowpass, driver.crossover_type, driver.crossover_order, driver.delay, driver.gain, driver.inverted, driver.output_port
+        )
+        .execute(&mut **db)
+        .await;
+    }
+    for (index, group) in settings.groups.iter().enumerate() {
+        let index_i32 = index as i32;
+        let _ = sqlx::query!(
+            "INSERT INTO speaker_groups (version, group_index, group_name, crossover, gain, crossover_enabled) VALUES (?, ?, ?, ?, ?, ?)",
+            version,
+            index_i32,
+            group.name,
+            group.crossover,
+            group.gain,
+            group.crossover_enabled
+        )
+        .execute(&mut **db)
+        .await;
+        for speaker in group.speakers.iter() {
+            let _ = sqlx::query!(
+                "INSERT INTO speaker_group_members (version, group_name, speaker) VALUES (?, ?, ?)",
+                version,
+                group.name,
+                speaker
+            )
+            .execute(&mut **db)
+            .await;
+        }
+    }
     for speaker in settings.speakers.iter() {
         let _ = sqlx::query!(
             "INSERT INTO speakers_settings_for_ui (
@@ -544,14 +1276,34 @@ async fn write_configuration(
             crossover, 
             distance, 
             gain, 
-            is_subwoofer
-        ) VALUES (?, ?, ?, ?, ?, ?)",
+            is_subwoofer,
+            crossover_type,
+            crossover_speaker_order,
+            crossover_subwoofer_order,
+            impulse_response,
+            impulse_response_channel,
+            inverted,
+            crossover_enabled,
+            target,
+            role,
+            output_port
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             version,
             speaker.speaker,
             speaker.crossover,
             speaker.distance,
             speaker.gain,
-            speaker.is_subwoofer
+            speaker.is_subwoofer,
+            speaker.crossover_type,
+            speaker.crossover_speaker_order,
+            speaker.crossover_subwoofer_order,
+            speaker.impulse_response,
+            speaker.impulse_response_channel,
+            speaker.inverted,
+            speaker.crossover_enabled,
+            speaker.target,
+            speaker.role,
+            speaker.output_port
         )
         .execute(&mut **db)
         .await;
@@ -565,14 +1317,30 @@ async fn write_configuration(
                 crossover, 
                 delay, 
                 gain, 
-                is_subwoofer
-            ) VALUES (?, ?, ?, ?, ?, ?)",
+                is_subwoofer,
+                crossover_type,
+                crossover_speaker_order,
+                crossover_subwoofer_order,
+                impulse_response,
+                impulse_response_channel,
+                inverted,
+                role,
+                output_port
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             version,
             speaker.speaker,
             speaker.crossover,
             speaker.delay,
             speaker.gain,
-            speaker.is_subwoofer
+            speaker.is_subwoofer,
+            speaker.crossover_type,
+            speaker.crossover_speaker_order,
+            speaker.crossover_subwoofer_order,
+            speaker.impulse_response,
+            speaker.impulse_response_channel,
+            speaker.inverted,
+            speaker.role,
+            speaker.output_port
         )
         .execute(&mut **db)
         .await;
@@ -595,6 +1363,24 @@ async fn delete_configuration(
     let _ = sqlx::query!("DELETE FROM filters WHERE version=?", version)
         .execute(&mut **db)
         .await;
+    let _ = sqlx::query!("DELETE FROM global_filters WHERE version=?", version)
+        .execute(&mut **db)
+        .await;
+    let _ = sqlx::query!("DELETE FROM phase_alignment WHERE version=?", version)
+        .execute(&mut **db)
+        .await;
+    let _ = sqlx::query!("DELETE FROM drivers WHERE version=?", version)
+        .execute(&mut **db)
+        .await;
+    let _ = sqlx::query!("DELETE FROM custom_input_layout WHERE version=?", version)
+        .execute(&mut **db)
+        .await;
+    let _ = sqlx::query!("DELETE FROM speaker_groups WHERE version=?", version)
+        .execute(&mut **db)
+        .await;
+    let _ = sqlx::query!("DELETE FROM speaker_group_members WHERE version=?", version)
+        .execute(&mut **db)
+        .await;
     let _ = sqlx::query!(
         "DELETE FROM speakers_settings_for_ui WHERE version=?",
         version
@@ -611,6 +1397,399 @@ async fn delete_configuration(
     Ok(())
 }
 
+/// re-sends the applied version to camilla with live mute/solo applied, without saving anything
+async fn apply_live_settings_to_camilla(
+    db: &Settings,
+    camilla_settings: &CamillaSettings,
+    live_settings: &LiveSettings,
+) -> Result<(), BadRequest<String>> {
+    let version = get_applied_version(db).await?;
+    let settings = get_config_for_camilla_from_db(db, version)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?;
+    if let Some(speaker) = live_settings.unknown_speakers(&settings.speakers).first() {
+        return Err(BadRequest(format!(
+            "Speaker {} is not in the applied version",
+            speaker
+        )));
+    }
+    let (mut config, _) =
+        convert_processor_settings_to_camilla(&settings).map_err(|e| BadRequest(e.to_string()))?;
+    //a multi-way speaker is muted through the gain of each of its drivers
+    let muted_outputs: Vec<&String> = live_settings
+        .muted_speakers(&settings.speakers)
+        .into_iter()
+        .flat_map(|speaker| {
+            let drivers: Vec<&String> = settings
+                .drivers
+                .iter()
+                .filter(|d| &d.speaker == speaker)
+                .map(|d| &d.driver)
+                .collect();
+            if drivers.is_empty() {
+                vec![speaker]
+            } else {
+                drivers
+            }
+        })
+        .collect();
+    mute_filters(&mut config.filters, &muted_outputs);
+    send_config_to_camilla(&config, camilla_settings)
+}
+
+/// applies a change to the live settings, only keeping it if camilla accepted the new configuration.
+/// The lock is held until camilla answers, so concurrent changes are applied one after another
+async fn update_live_settings(
+    db: &Settings,
+    camilla_settings: &CamillaSettings,
+    live_settings: &Mutex<LiveSettings>,
+    update: impl FnOnce(&mut LiveSettings),
+) -> Result<Json<LiveSettings>, BadRequest<String>> {
+    let mut live_settings = live_settings.lock().await;
+    let mut updated_settings = live_settings.clone();
+    update(&mut updated_settings);
+    apply_live_settings_to_camilla(db, camilla_settings, &updated_settings).await?;
+    *live_settings = updated_settings.clone();
+    Ok(Json(updated_settings))
+}
+
+#[get("/live")]
+/// Mute and solo state of the applied version; this is not saved with any version
+async fn get_live_settings(live_settings: &State<Mutex<LiveSettings>>) -> Json<LiveSettings> {
+    Json(live_settings.lock().await.clone())
+}
+
+#[put("/live/mute/<speaker>")]
+async fn mute_speaker(
+    db: &Settings,
+    speaker: &str,
+    camilla_settings: &State<CamillaSettings>,
+    live_settings: &State<Mutex<LiveSettings>>,
+) -> Result<Json<LiveSettings>, BadRequest<String>> {
+    update_live_settings(db, camilla_settings, live_settings, |v| {
+        v.muted.insert(speaker.to_string());
+    })
+    .await
+}
+
+#[delete("/live/mute/<speaker>")]
+async fn unmute_speaker(
+    db: &Settings,
+    speaker: &str,
+    camilla_settings: &State<CamillaSettings>,
+    live_settings: &State<Mutex<LiveSettings>>,
+) -> Result<Json<LiveSettings>, BadRequest<String>> {
+    update_live_settings(db, camilla_settings, live_settings, |v| {
+        v.muted.remove(speaker);
+    })
+    .await
+}
+
+#[put("/live/solo/<speaker>")]
+async fn solo_speaker(
+    db: &Settings,
+    speaker: &str,
+    camilla_settings: &State<CamillaSettings>,
+    live_settings: &State<Mutex<LiveSettings>>,
+) -> Result<Json<LiveSettings>, BadRequest<String>> {
+    update_live_settings(db, camilla_settings, live_settings, |v| {
+        v.soloed.insert(speaker.to_string());
+    })
+    .await
+}
+
+#[delete("/live/solo/<speaker>")]
+async fn unsolo_speaker(
+    db: &Settings,
+    speaker: &str,
+    camilla_settings: &State<CamillaSettings>,
+    live_settings: &State<Mutex<LiveSettings>>,
+) -> Result<Json<LiveSettings>, BadRequest<String>> {
+    update_live_settings(db, camilla_settings, live_settings, |v| {
+        v.soloed.remove(speaker);
+    })
+    .await
+}
+
+#[delete("/live")]
+/// Clears all mutes and solos, restoring the applied version as saved
+async fn clear_live_settings(
+    db: &Settings,
+    camilla_settings: &State<CamillaSettings>,
+    live_settings: &State<Mutex<LiveSettings>>,
+) -> Result<Json<LiveSettings>, BadRequest<String>> {
+    update_live_settings(db, camilla_settings, live_settings, |v| {
+        *v = LiveSettings::default();
+    })
+    .await
+}
+
+#[get("/impulses")]
+async fn get_impulses(db: &Settings) -> Result<Json<Vec<ImpulseResponse>>, BadRequest<String>> {
+    let impulse_responses = sqlx::query_as!(
+        ImpulseResponse,
+        r#"SELECT 
+        filename, 
+        path, 
+        file_type as "file_type: crate::impulses::ImpulseFileType", 
+        sample_format as "sample_format?: crate::impulses::SampleFormat", 
+        sample_rate as "sample_rate: i32", 
+        channels as "channels: i32", 
+        length as "length: i32", 
+        upload_date
+        from impulse_responses"#,
+    )
+    .fetch_all(&**db)
+    .await
+    .map_err(|e| BadRequest(e.to_string()))?;
+    Ok(Json(impulse_responses))
+}
+
+#[post("/impulses/<filename>?<format>&<samplerate>", data = "<data>")]
+/// Uploads an impulse response.  Wav files describe themselves;
+/// any other file is treated as raw and needs the sample format and sample rate.
+/// Existing files are not overwritten since saved versions may reference them.
+async fn upload_impulse(
+    mut db: Connection<Settings>,
+    filename: &str,
+    format: Option<SampleFormat>,
+    samplerate: Option<i32>,
+    data: Data<'_>,
+    camilla_settings: &State<CamillaSettings>,
+) -> Result<Json<ImpulseResponse>, BadRequest<String>> {
+    let file_type = validate_impulse_filename(filename).map_err(BadRequest)?;
+    if get_impulse_response(&mut db, filename)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?
+        .is_some()
+    {
+        return Err(BadRequest(format!(
+            "Impulse response {} already exists",
+            filename
+        )));
+    }
+    let bytes = data
+        .open(MAX_IMPULSE_UPLOAD_MIB.mebibytes())
+        .into_bytes()
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?;
+    if !bytes.is_complete() {
+        return Err(BadRequest(format!(
+            "Impulse response is larger than {} MiB",
+            MAX_IMPULSE_UPLOAD_MIB
+        )));
+    }
+    let info = match (file_type, format, samplerate) {
+        (ImpulseFileType::Wav, _, _) => parse_wav(&bytes),
+        (ImpulseFileType::Raw, Some(format), Some(samplerate)) => {
+            parse_raw(&bytes, format, samplerate)
+        }
+        (ImpulseFileType::Raw, _, _) => {
+            Err("Raw impulse responses require a format and samplerate".to_string())
+        }
+    }
+    .map_err(BadRequest)?;
+    validate_impulse_info(&info).map_err(BadRequest)?;
+
+    let directory = Path::new(&camilla_settings.impulse_directory);
+    rocket::tokio::fs::create_dir_all(directory)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?;
+    let path = rocket::tokio::fs::canonicalize(directory)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?
+        .join(filename)
+        .to_string_lossy()
+        .to_string();
+    rocket::tokio::fs::write(&path, bytes.into_inner())
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?;
+
+    let impulse_response = ImpulseResponse {
+        filename: filename.to_string(),
+        path,
+        file_type,
+        sample_format: match file_type {
+            ImpulseFileType::Wav => None,
+            ImpulseFileType::Raw => format,
+        },
+        sample_rate: info.sample_rate,
+        channels: info.channels,
+        length: info.length,
+        upload_date: Utc::now().to_string(),
+    };
+    let _ = sqlx::query!(
+        "INSERT INTO impulse_responses (
+            filename, 
+            path, 
+            file_type, 
+            sample_format, 
+            sample_rate, 
+            channels, 
+            length, 
+            upload_date
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        impulse_response.filename,
+        impulse_response.path,
+        impulse_response.file_type,
+        impulse_response.sample_format,
+        impulse_response.sample_rate,
+        impulse_response.channels,
+        impulse_response.length,
+        impulse_response.upload_date
+    )
+    .execute(&mut **db)
+    .await
+    .map_err(|e| BadRequest(e.to_string()))?;
+    Ok(Json(impulse_response))
+}
+
+#[delete("/impulses/<filename>")]
+/// Impulse responses that are used by a saved version cannot be deleted
+async fn delete_impulse(
+    mut db: Connection<Settings>,
+    filename: &str,
+) -> Result<(), BadRequest<String>> {
+    let impulse_response = get_impulse_response(&mut db, filename)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?
+        .ok_or_else(|| BadRequest(format!("Impulse response {} not found", filename)))?;
+    let usage = sqlx::query!(
+        r#"SELECT count(*) as "count!: i32" from speakers_for_camilla where impulse_response=?"#,
+        filename
+    )
+    .fetch_one(&mut **db)
+    .await
+    .map_err(|e| BadRequest(e.to_string()))?;
+    if usage.count > 0 {
+        return Err(BadRequest(format!(
+            "Impulse response {} is used by a saved configuration",
+            filename
+        )));
+    }
+    let _ = sqlx::query!("DELETE FROM impulse_responses WHERE filename=?", filename)
+        .execute(&mut **db)
+        .await;
+    let _ = rocket::tokio::fs::remove_file(&impulse_response.path).await;
+    Ok(())
+}
+
+/// reads every target curve, ordered by name
+async fn get_target_curves(
+    db: &mut sqlx::SqliteConnection,
+) -> Result<Vec<TargetCurve>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT name, freq as "freq: f32", gain as "gain: f32" 
+        from target_curves order by name, point_index"#
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(group_target_points(
+        rows.into_iter().map(|r| (r.name, r.freq, r.gain)).collect(),
+    ))
+}
+
+async fn get_target_curve(
+    db: &mut sqlx::SqliteConnection,
+    name: &str,
+) -> Result<Option<TargetCurve>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT name, freq as "freq: f32", gain as "gain: f32" 
+        from target_curves where name=? order by point_index"#,
+        name
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(group_target_points(rows.into_iter().map(|r| (r.name, r.freq, r.gain)).collect()).pop())
+}
+
+/// target curves referenced by a saved version can't be changed or deleted, so the
+/// version keeps describing what it was tuned toward
+async fn check_target_unused(
+    db: &mut sqlx::SqliteConnection,
+    name: &str,
+) -> Result<(), BadRequest<String>> {
+    let usage = sqlx::query!(
+        r#"SELECT count(*) as "count!: i32" from speakers_settings_for_ui where target=?"#,
+        name
+    )
+    .fetch_one(db)
+    .await
+    .map_err(|e| BadRequest(e.to_string()))?;
+    if usage.count > 0 {
+        return Err(BadRequest(format!(
+            "Target curve {} is used by a saved configuration",
+            name
+        )));
+    }
+    Ok(())
+}
+
+#[get("/targets")]
+async fn get_targets(
+    mut db: Connection<Settings>,
+) -> Result<Json<Vec<TargetCurve>>, BadRequest<String>> {
+    get_target_curves(&mut db)
+        .await
+        .map(Json)
+        .map_err(|e| BadRequest(e.to_string()))
+}
+
+#[get("/targets/<name>")]
+async fn get_target(
+    mut db: Connection<Settings>,
+    name: &str,
+) -> Result<Json<TargetCurve>, BadRequest<String>> {
+    get_target_curve(&mut db, name)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?
+        .map(Json)
+        .ok_or_else(|| BadRequest(format!("Target curve {} not found", name)))
+}
+
+#[put("/targets/<name>", format = "json", data = "<points>")]
+/// Creates or replaces a target curve
+async fn write_target(
+    mut db: Connection<Settings>,
+    name: &str,
+    points: Json<Vec<TargetPoint>>,
+) -> Result<Json<TargetCurve>, BadRequest<String>> {
+    validate_target_curve(name, &points).map_err(BadRequest)?;
+    check_target_unused(&mut db, name).await?;
+    let _ = sqlx::query!("DELETE FROM target_curves WHERE name=?", name)
+        .execute(&mut **db)
+        .await;
+    for (index, point) in points.iter().enumerate() {
+        let index_i32 = index as i32;
+        let _ = sqlx::query!(
+            "INSERT INTO target_curves (name, point_index, freq, gain) VALUES (?, ?, ?, ?)",
+            name,
+            index_i32,
+            point.freq,
+            point.gain
+        )
+        .execute(&mut **db)
+        .await;
+    }
+    Ok(Json(TargetCurve {
+        name: name.to_string(),
+        points: points.into_inner(),
+    }))
+}
+
+#[delete("/targets/<name>")]
+async fn delete_target(mut db: Connection<Settings>, name: &str) -> Result<(), BadRequest<String>> {
+    get_target_curve(&mut db, name)
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?
+        .ok_or_else(|| BadRequest(format!("Target curve {} not found", name)))?;
+    check_target_unused(&mut db, name).await?;
+    let _ = sqlx::query!("DELETE FROM target_curves WHERE name=?", name)
+        .execute(&mut **db)
+        .await;
+    Ok(())
+}
+
 #[launch]
 fn rocket() -> _ {
     let mut args = std::env::args();
@@ -619,13 +1798,21 @@ fn rocket() -> _ {
     //Websocket URL
     let websocket_url = args.next().unwrap_or("ws://127.0.0.1:1234".to_string());
 
-    let camilla_settings = CamillaSettings { websocket_url };
     let html_files = args
         .next()
         .unwrap_or(relative!("avprocessor-ui/build").to_string());
+
+    //Impulse response location
+    let impulse_directory = args.next().unwrap_or(relative!("impulses").to_string());
+
+    let camilla_settings = CamillaSettings {
+        websocket_url,
+        impulse_directory,
+    };
     rocket::build()
         .mount("/", FileServer::from(html_files))
         .manage(camilla_settings)
+        .manage(Mutex::new(LiveSettings::default()))
         .attach(Settings::init())
         .attach(AdHoc::try_on_ignite("DB Migrations", run_migrations))
         .attach(AdHoc::try_on_ignite(
@@ -637,10 +1824,28 @@ fn rocket() -> _ {
             routes![
                 config_latest,
                 config_version,
+                import_filters,
+                auto_eq_filters,
+                export_filters,
+                config_response,
+                config_headroom,
                 write_configuration,
                 apply_config_version,
                 delete_configuration,
-                get_versions
+                get_versions,
+                get_impulses,
+                upload_impulse,
+                delete_impulse,
+                get_targets,
+                get_target,
+                write_target,
+                delete_target,
+                get_live_settings,
+                mute_speaker,
+                unmute_speaker,
+                solo_speaker,
+                unsolo_speaker,
+                clear_live_settings
             ],
         )
 }
@@ -652,8 +1857,8 @@ mod tests {
     use super::FEET_PER_MS;
     use super::METERS_PER_MS;
     use crate::processor::{
-        DeviceType, Filter, ProcessorSettingsForCamilla, SelectedDistanceType, Speaker,
-        SpeakerForUI,
+        DeviceType, DitherType, Filter, InputLayout, LfeInput, PeqType,
+        ProcessorSettingsForCamilla, SelectedDistanceType, Speaker, SpeakerForUI,
     };
     use rocket::serde::json;
 
@@ -666,6 +1871,7 @@ mod tests {
                 distance: 0.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "r".to_string(),
@@ -673,6 +1879,7 @@ mod tests {
                 distance: 0.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "c".to_string(),
@@ -680,6 +1887,7 @@ mod tests {
                 distance: 1.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "sub1".to_string(),
@@ -687,6 +1895,7 @@ mod tests {
                 distance: 3.0,
                 gain: 1.0,
                 is_subwoofer: true,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "sub2".to_string(),
@@ -694,6 +1903,7 @@ mod tests {
                 distance: 3.0,
                 gain: 1.0,
                 is_subwoofer: true,
+                ..Default::default()
             },
         ];
         let speakers = update_speaker_delays(&SelectedDistanceType::METERS, &speakers);
@@ -713,6 +1923,7 @@ mod tests {
                 distance: 0.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "r".to_string(),
@@ -720,6 +1931,7 @@ mod tests {
                 distance: 0.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "c".to_string(),
@@ -727,6 +1939,7 @@ mod tests {
                 distance: 1.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "sub1".to_string(),
@@ -734,6 +1947,7 @@ mod tests {
                 distance: 3.0,
                 gain: 1.0,
                 is_subwoofer: true,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "sub2".to_string(),
@@ -741,6 +1955,7 @@ mod tests {
                 distance: 3.0,
                 gain: 1.0,
                 is_subwoofer: true,
+                ..Default::default()
             },
         ];
         let speakers = update_speaker_delays(&SelectedDistanceType::FEET, &speakers);
@@ -759,6 +1974,7 @@ mod tests {
                 distance: 0.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "r".to_string(),
@@ -766,6 +1982,7 @@ mod tests {
                 distance: 0.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "c".to_string(),
@@ -773,6 +1990,7 @@ mod tests {
                 distance: 1.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "sub1".to_string(),
@@ -780,6 +1998,7 @@ mod tests {
                 distance: 3.0,
                 gain: 1.0,
                 is_subwoofer: true,
+                ..Default::default()
             },
             SpeakerForUI {
                 speaker: "sub2".to_string(),
@@ -787,6 +2006,7 @@ mod tests {
                 distance: 3.0,
                 gain: 1.0,
                 is_subwoofer: true,
+                ..Default::default()
             },
         ];
         let speakers = update_speaker_delays(&SelectedDistanceType::MS, &speakers);
@@ -797,26 +2017,155 @@ mod tests {
         assert_eq!(speakers[4].delay, 3.0);
     }
     #[test]
+    fn test_update_speaker_delays_samples() {
+        let speakers: Vec<SpeakerForUI> = vec![
+            SpeakerForUI {
+                speaker: "l".to_string(),
+                crossover: Some(80),
+                distance: 0.0,
+                gain: 1.0,
+                is_subwoofer: false,
+                ..Default::default()
+            },
+            SpeakerForUI {
+                speaker: "r".to_string(),
+                crossover: Some(80),
+                distance: 0.0,
+                gain: 1.0,
+                is_subwoofer: false,
+                ..Default::default()
+            },
+            SpeakerForUI {
+                speaker: "c".to_string(),
+                crossover: Some(80),
+                distance: 1.0,
+                gain: 1.0,
+                is_subwoofer: false,
+                ..Default::default()
+            },
+            SpeakerForUI {
+                speaker: "sub1".to_string(),
+                crossover: None,
+                distance: 3.0,
+                gain: 1.0,
+                is_subwoofer: true,
+                ..Default::default()
+            },
+            SpeakerForUI {
+                speaker: "sub2".to_string(),
+                crossover: None,
+                distance: 3.0,
+                gain: 1.0,
+                is_subwoofer: true,
+                ..Default::default()
+            },
+        ];
+        let speakers = update_speaker_delays(&SelectedDistanceType::SAMPLES, &speakers);
+        assert_eq!(speakers[0].delay, 0.0);
+        assert_eq!(speakers[1].delay, 0.0);
+        assert_eq!(speakers[2].delay, 1.0);
+        assert_eq!(speakers[3].delay, 3.0);
+        assert_eq!(speakers[4].delay, 3.0);
+    }
+    #[test]
+    fn test_update_speaker_delays_millimeters() {
+        let speakers: Vec<SpeakerForUI> = vec![
+            SpeakerForUI {
+                speaker: "l".to_string(),
+                crossover: Some(80),
+                distance: 0.0,
+                gain: 1.0,
+                is_subwoofer: false,
+                ..Default::default()
+            },
+            SpeakerForUI {
+                speaker: "r".to_string(),
+                crossover: Some(80),
+                distance: 0.0,
+                gain: 1.0,
+                is_subwoofer: false,
+                ..Default::default()
+            },
+            SpeakerForUI {
+                speaker: "c".to_string(),
+                crossover: Some(80),
+                distance: 1.0,
+                gain: 1.0,
+                is_subwoofer: false,
+                ..Default::default()
+            },
+            SpeakerForUI {
+                speaker: "sub1".to_string(),
+                crossover: None,
+                distance: 3.0,
+                gain: 1.0,
+                is_subwoofer: true,
+                ..Default::default()
+            },
+            SpeakerForUI {
+                speaker: "sub2".to_string(),
+                crossover: None,
+                distance: 3.0,
+                gain: 1.0,
+                is_subwoofer: true,
+                ..Default::default()
+            },
+        ];
+        let speakers = update_speaker_delays(&SelectedDistanceType::MILLIMETERS, &speakers);
+        //millimetres are passed to camilla without converting to ms
+        assert_eq!(speakers[0].delay, 3.0);
+        assert_eq!(speakers[1].delay, 3.0);
+        assert_eq!(speakers[2].delay, 2.0);
+        assert_eq!(speakers[3].delay, 0.0);
+        assert_eq!(speakers[4].delay, 0.0);
+    }
+    #[test]
+    fn test_update_speaker_delays_bypassed_crossover() {
+        let speaker = |name: &str, crossover_enabled: bool| SpeakerForUI {
+            speaker: name.to_string(),
+            crossover: Some(80),
+            distance: 0.0,
+            gain: 1.0,
+            is_subwoofer: false,
+            crossover_enabled,
+            ..Default::default()
+        };
+        let speakers = vec![speaker("l", true), speaker("r", false)];
+        let speakers = update_speaker_delays(&SelectedDistanceType::MS, &speakers);
+        assert_eq!(speakers[0].crossover, Some(80));
+        assert_eq!(speakers[1].crossover, None);
+    }
+    #[test]
     fn check_processor_to_camilla_one_sub() {
         let settings = ProcessorSettingsForCamilla {
+            global_filters: vec![],
+            phase_alignment: vec![],
+            drivers: vec![],
+            lfe: LfeInput::default(),
+            input_layout: InputLayout::SpeakerOrder,
+            custom_layout: vec![],
+            upmix: None,
             filters: vec![
                 Filter {
                     freq: 1000,
                     gain: 2.0,
                     q: 0.707,
                     speaker: "l".to_string(),
+                    ..Default::default()
                 },
                 Filter {
                     freq: 2000,
                     gain: 2.0,
                     q: 0.707,
                     speaker: "l".to_string(),
+                    ..Default::default()
                 },
                 Filter {
                     freq: 2000,
                     gain: 1.0,
                     q: 0.707,
                     speaker: "r".to_string(),
+                    ..Default::default()
                 },
             ],
             speakers: vec![
@@ -826,6 +2175,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "c".to_string(),
@@ -833,6 +2183,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "r".to_string(),
@@ -840,6 +2191,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "sub1".to_string(),
@@ -847,38 +2199,54 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: true,
+                    ..Default::default()
                 },
             ],
             //selected_distance: SelectedDistanceType::MS,
             device: DeviceType::OktoDac8,
+            selected_distance: SelectedDistanceType::MS,
+            impulse_responses: vec![],
+            loudness: None,
+            dither: DitherType::Highpass,
+            headroom_compensation: false,
         };
         assert_eq!(
-            json::to_string(&convert_processor_settings_to_camilla(&settings).unwrap()).unwrap(),
-            r#"{"mixers":{"combine_sub":{"channels":{"in":7,"out":4},"mapping":[{"sources":[{"channel":2,"gain":0,"inverted":false}],"dest":1},{"sources":[{"channel":0,"gain":0,"inverted":false}],"dest":0},{"sources":[{"channel":4,"gain":0,"inverted":false}],"dest":2},{"sources":[{"channel":1,"gain":0,"inverted":false},{"channel":3,"gain":0,"inverted":false},{"channel":5,"gain":0,"inverted":false},{"channel":6,"gain":0,"inverted":false}],"dest":3}]},"split_non_sub":{"channels":{"in":4,"out":7},"mapping":[{"sources":[{"channel":1,"gain":0,"inverted":false}],"dest":2},{"sources":[{"channel":1,"gain":0,"inverted":false}],"dest":3},{"sources":[{"channel":0,"gain":0,"inverted":false}],"dest":0},{"sources":[{"channel":0,"gain":0,"inverted":false}],"dest":1},{"sources":[{"channel":2,"gain":0,"inverted":false}],"dest":4},{"sources":[{"channel":2,"gain":0,"inverted":false}],"dest":5},{"sources":[{"channel":3,"gain":10,"inverted":false}],"dest":6}]}},"filters":{"crossover_speaker_c":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_speaker_l":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_speaker_r":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_subwooferc":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"crossover_subwooferl":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"crossover_subwooferr":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"delay_c":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_l":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_r":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_sub1":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"gain_c":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_l":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_r":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub1":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"peq_l_0":{"type":"Biquad","parameters":{"freq":1000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_l_1":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_r_2":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":1.0,"type":"Peaking"}}},"pipeline":[{"type":"Mixer","name":"split_non_sub"},{"type":"Filter","channel":2,"names":["crossover_speaker_c"]},{"type":"Filter","channel":3,"names":["crossover_subwooferc"]},{"type":"Filter","channel":0,"names":["crossover_speaker_l"]},{"type":"Filter","channel":1,"names":["crossover_subwooferl"]},{"type":"Filter","channel":4,"names":["crossover_speaker_r"]},{"type":"Filter","channel":5,"names":["crossover_subwooferr"]},{"type":"Mixer","name":"combine_sub"},{"type":"Filter","channel":1,"names":["delay_c","gain_c"]},{"type":"Filter","channel":0,"names":["peq_l_0","peq_l_1","delay_l","gain_l"]},{"type":"Filter","channel":2,"names":["peq_r_2","delay_r","gain_r"]},{"type":"Filter","channel":3,"names":["delay_sub1","gain_sub1"]}],"devices":{"samplerate":96000,"capture_samplerate":48000,"chunksize":2048,"queuelimit":4,"capture":{"type":"Alsa","channels":4,"device":"hw:Loopback,1","format":"S32LE"},"playback":{"type":"Alsa","channels":4,"device":"hw:DAC8PRO","format":"S32LE"},"resampler":{"type":"Synchronous"}}}"#
+            json::to_string(&convert_processor_settings_to_camilla(&settings).unwrap().0).unwrap(),
+            r#"{"mixers":{"combine_sub":{"channels":{"in":7,"out":4},"mapping":[{"sources":[{"channel":2,"gain":0.0,"inverted":false}],"dest":1},{"sources":[{"channel":0,"gain":0.0,"inverted":false}],"dest":0},{"sources":[{"channel":4,"gain":0.0,"inverted":false}],"dest":2},{"sources":[{"channel":1,"gain":0.0,"inverted":false},{"channel":3,"gain":0.0,"inverted":false},{"channel":5,"gain":0.0,"inverted":false},{"channel":6,"gain":0.0,"inverted":false}],"dest":3}]},"split_non_sub":{"channels":{"in":4,"out":7},"mapping":[{"sources":[{"channel":1,"gain":0.0,"inverted":false}],"dest":2},{"sources":[{"channel":1,"gain":0.0,"inverted":false}],"dest":3},{"sources":[{"channel":0,"gain":0.0,"inverted":false}],"dest":0},{"sources":[{"channel":0,"gain":0.0,"inverted":false}],"dest":1},{"sources":[{"channel":2,"gain":0.0,"inverted":false}],"dest":4},{"sources":[{"channel":2,"gain":0.0,"inverted":false}],"dest":5},{"sources":[{"channel":3,"gain":10.0,"inverted":false}],"dest":6}]}},"filters":{"crossover_speaker_c":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_speaker_l":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_speaker_r":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_subwooferc":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"crossover_subwooferl":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"crossover_subwooferr":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"delay_c":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_l":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_r":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_sub1":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"gain_c":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_l":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_r":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub1":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"peq_l_0":{"type":"Biquad","parameters":{"freq":1000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_l_1":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_r_2":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":1.0,"type":"Peaking"}}},"pipeline":[{"type":"Mixer","name":"split_non_sub"},{"type":"Filter","channel":2,"names":["crossover_speaker_c"]},{"type":"Filter","channel":3,"names":["crossover_subwooferc"]},{"type":"Filter","channel":0,"names":["crossover_speaker_l"]},{"type":"Filter","channel":1,"names":["crossover_subwooferl"]},{"type":"Filter","channel":4,"names":["crossover_speaker_r"]},{"type":"Filter","channel":5,"names":["crossover_subwooferr"]},{"type":"Mixer","name":"combine_sub"},{"type":"Filter","channel":1,"names":["delay_c","gain_c"]},{"type":"Filter","channel":0,"names":["peq_l_0","peq_l_1","delay_l","gain_l"]},{"type":"Filter","channel":2,"names":["peq_r_2","delay_r","gain_r"]},{"type":"Filter","channel":3,"names":["delay_sub1","gain_sub1"]}],"devices":{"samplerate":96000,"capture_samplerate":48000,"chunksize":2048,"queuelimit":4,"capture":{"type":"Alsa","channels":4,"device":"hw:Loopback,1","format":"S32LE"},"playback":{"type":"Alsa","channels":4,"device":"hw:DAC8PRO","format":"S32LE"},"resampler":{"type":"Synchronous"}}}"#
         )
     }
 
     #[test]
     fn check_processor_to_camilla_two_sub() {
         let settings = ProcessorSettingsForCamilla {
+            global_filters: vec![],
+            phase_alignment: vec![],
+            drivers: vec![],
+            lfe: LfeInput::default(),
+            input_layout: InputLayout::SpeakerOrder,
+            custom_layout: vec![],
+            upmix: None,
             filters: vec![
                 Filter {
                     freq: 1000,
                     gain: 2.0,
                     q: 0.707,
                     speaker: "l".to_string(),
+                    ..Default::default()
                 },
                 Filter {
                     freq: 2000,
                     gain: 2.0,
                     q: 0.707,
                     speaker: "l".to_string(),
+                    ..Default::default()
                 },
                 Filter {
                     freq: 2000,
                     gain: 1.0,
                     q: 0.707,
                     speaker: "r".to_string(),
+                    ..Default::default()
                 },
             ],
             speakers: vec![
@@ -888,6 +2256,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "c".to_string(),
@@ -895,6 +2264,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "r".to_string(),
@@ -902,6 +2272,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "sub1".to_string(),
@@ -909,6 +2280,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: true,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "sub2".to_string(),
@@ -916,38 +2288,54 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: true,
+                    ..Default::default()
                 },
             ],
             //selected_distance: SelectedDistanceType::MS,
             device: DeviceType::OktoDac8,
+            selected_distance: SelectedDistanceType::MS,
+            impulse_responses: vec![],
+            loudness: None,
+            dither: DitherType::Highpass,
+            headroom_compensation: false,
         };
 
         assert_eq!(
-            json::to_string(&convert_processor_settings_to_camilla(&settings).unwrap()).unwrap(),
-            r#"{"mixers":{"combine_sub":{"channels":{"in":7,"out":5},"mapping":[{"sources":[{"channel":2,"gain":0,"inverted":false}],"dest":1},{"sources":[{"channel":0,"gain":0,"inverted":false}],"dest":0},{"sources":[{"channel":4,"gain":0,"inverted":false}],"dest":2},{"sources":[{"channel":1,"gain":0,"inverted":false},{"channel":3,"gain":0,"inverted":false},{"channel":5,"gain":0,"inverted":false},{"channel":6,"gain":0,"inverted":false}],"dest":3},{"sources":[{"channel":1,"gain":0,"inverted":false},{"channel":3,"gain":0,"inverted":false},{"channel":5,"gain":0,"inverted":false},{"channel":6,"gain":0,"inverted":false}],"dest":4}]},"split_non_sub":{"channels":{"in":4,"out":7},"mapping":[{"sources":[{"channel":1,"gain":0,"inverted":false}],"dest":2},{"sources":[{"channel":1,"gain":0,"inverted":false}],"dest":3},{"sources":[{"channel":0,"gain":0,"inverted":false}],"dest":0},{"sources":[{"channel":0,"gain":0,"inverted":false}],"dest":1},{"sources":[{"channel":2,"gain":0,"inverted":false}],"dest":4},{"sources":[{"channel":2,"gain":0,"inverted":false}],"dest":5},{"sources":[{"channel":3,"gain":10,"inverted":false}],"dest":6}]}},"filters":{"crossover_speaker_c":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_speaker_l":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_speaker_r":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_subwooferc":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"crossover_subwooferl":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"crossover_subwooferr":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"delay_c":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_l":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_r":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_sub1":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_sub2":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"gain_c":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_l":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_r":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub1":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub2":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"peq_l_0":{"type":"Biquad","parameters":{"freq":1000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_l_1":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_r_2":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":1.0,"type":"Peaking"}}},"pipeline":[{"type":"Mixer","name":"split_non_sub"},{"type":"Filter","channel":2,"names":["crossover_speaker_c"]},{"type":"Filter","channel":3,"names":["crossover_subwooferc"]},{"type":"Filter","channel":0,"names":["crossover_speaker_l"]},{"type":"Filter","channel":1,"names":["crossover_subwooferl"]},{"type":"Filter","channel":4,"names":["crossover_speaker_r"]},{"type":"Filter","channel":5,"names":["crossover_subwooferr"]},{"type":"Mixer","name":"combine_sub"},{"type":"Filter","channel":1,"names":["delay_c","gain_c"]},{"type":"Filter","channel":0,"names":["peq_l_0","peq_l_1","delay_l","gain_l"]},{"type":"Filter","channel":2,"names":["peq_r_2","delay_r","gain_r"]},{"type":"Filter","channel":3,"names":["delay_sub1","gain_sub1"]},{"type":"Filter","channel":4,"names":["delay_sub2","gain_sub2"]}],"devices":{"samplerate":96000,"capture_samplerate":48000,"chunksize":2048,"queuelimit":4,"capture":{"type":"Alsa","channels":4,"device":"hw:Loopback,1","format":"S32LE"},"playback":{"type":"Alsa","channels":5,"device":"hw:DAC8PRO","format":"S32LE"},"resampler":{"type":"Synchronous"}}}"#
+            json::to_string(&convert_processor_settings_to_camilla(&settings).unwrap().0).unwrap(),
+            r#"{"mixers":{"combine_sub":{"channels":{"in":7,"out":5},"mapping":[{"sources":[{"channel":2,"gain":0.0,"inverted":false}],"dest":1},{"sources":[{"channel":0,"gain":0.0,"inverted":false}],"dest":0},{"sources":[{"channel":4,"gain":0.0,"inverted":false}],"dest":2},{"sources":[{"channel":1,"gain":0.0,"inverted":false},{"channel":3,"gain":0.0,"inverted":false},{"channel":5,"gain":0.0,"inverted":false},{"channel":6,"gain":0.0,"inverted":false}],"dest":3},{"sources":[{"channel":1,"gain":0.0,"inverted":false},{"channel":3,"gain":0.0,"inverted":false},{"channel":5,"gain":0.0,"inverted":false},{"channel":6,"gain":0.0,"inverted":false}],"dest":4}]},"split_non_sub":{"channels":{"in":4,"out":7},"mapping":[{"sources":[{"channel":1,"gain":0.0,"inverted":false}],"dest":2},{"sources":[{"channel":1,"gain":0.0,"inverted":false}],"dest":3},{"sources":[{"channel":0,"gain":0.0,"inverted":false}],"dest":0},{"sources":[{"channel":0,"gain":0.0,"inverted":false}],"dest":1},{"sources":[{"channel":2,"gain":0.0,"inverted":false}],"dest":4},{"sources":[{"channel":2,"gain":0.0,"inverted":false}],"dest":5},{"sources":[{"channel":3,"gain":10.0,"inverted":false}],"dest":6}]}},"filters":{"crossover_speaker_c":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_speaker_l":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_speaker_r":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_subwooferc":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"crossover_subwooferl":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"crossover_subwooferr":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"delay_c":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_l":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_r":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_sub1":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_sub2":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"gain_c":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_l":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_r":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub1":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub2":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"peq_l_0":{"type":"Biquad","parameters":{"freq":1000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_l_1":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_r_2":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":1.0,"type":"Peaking"}}},"pipeline":[{"type":"Mixer","name":"split_non_sub"},{"type":"Filter","channel":2,"names":["crossover_speaker_c"]},{"type":"Filter","channel":3,"names":["crossover_subwooferc"]},{"type":"Filter","channel":0,"names":["crossover_speaker_l"]},{"type":"Filter","channel":1,"names":["crossover_subwooferl"]},{"type":"Filter","channel":4,"names":["crossover_speaker_r"]},{"type":"Filter","channel":5,"names":["crossover_subwooferr"]},{"type":"Mixer","name":"combine_sub"},{"type":"Filter","channel":1,"names":["delay_c","gain_c"]},{"type":"Filter","channel":0,"names":["peq_l_0","peq_l_1","delay_l","gain_l"]},{"type":"Filter","channel":2,"names":["peq_r_2","delay_r","gain_r"]},{"type":"Filter","channel":3,"names":["delay_sub1","gain_sub1"]},{"type":"Filter","channel":4,"names":["delay_sub2","gain_sub2"]}],"devices":{"samplerate":96000,"capture_samplerate":48000,"chunksize":2048,"queuelimit":4,"capture":{"type":"Alsa","channels":4,"device":"hw:Loopback,1","format":"S32LE"},"playback":{"type":"Alsa","channels":5,"device":"hw:DAC8PRO","format":"S32LE"},"resampler":{"type":"Synchronous"}}}"#
         )
     }
     #[test]
     fn check_processor_to_camilla_two_sub_no_crossover() {
         let settings = ProcessorSettingsForCamilla {
+            global_filters: vec![],
+            phase_alignment: vec![],
+            drivers: vec![],
+            lfe: LfeInput::default(),
+            input_layout: InputLayout::SpeakerOrder,
+            custom_layout: vec![],
+            upmix: None,
             filters: vec![
                 Filter {
                     freq: 1000,
                     gain: 2.0,
                     q: 0.707,
                     speaker: "l".to_string(),
+                    ..Default::default()
                 },
                 Filter {
                     freq: 2000,
                     gain: 2.0,
                     q: 0.707,
                     speaker: "l".to_string(),
+                    ..Default::default()
                 },
                 Filter {
                     freq: 2000,
                     gain: 1.0,
                     q: 0.707,
                     speaker: "r".to_string(),
+                    ..Default::default()
                 },
             ],
             speakers: vec![
@@ -957,6 +2345,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "c".to_string(),
@@ -964,6 +2353,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "r".to_string(),
@@ -971,6 +2361,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "sub1".to_string(),
@@ -978,6 +2369,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: true,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "sub2".to_string(),
@@ -985,38 +2377,54 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: true,
+                    ..Default::default()
                 },
             ],
             //selected_distance: SelectedDistanceType::MS,
             device: DeviceType::OktoDac8,
+            selected_distance: SelectedDistanceType::MS,
+            impulse_responses: vec![],
+            loudness: None,
+            dither: DitherType::Highpass,
+            headroom_compensation: false,
         };
 
         assert_eq!(
-            json::to_string(&convert_processor_settings_to_camilla(&settings).unwrap()).unwrap(),
-            r#"{"mixers":{"combine_sub":{"channels":{"in":4,"out":5},"mapping":[{"sources":[{"channel":1,"gain":0,"inverted":false}],"dest":1},{"sources":[{"channel":0,"gain":0,"inverted":false}],"dest":0},{"sources":[{"channel":2,"gain":0,"inverted":false}],"dest":2},{"sources":[{"channel":3,"gain":0,"inverted":false}],"dest":3},{"sources":[{"channel":3,"gain":0,"inverted":false}],"dest":4}]},"split_non_sub":{"channels":{"in":4,"out":4},"mapping":[{"sources":[{"channel":1,"gain":0,"inverted":false}],"dest":1},{"sources":[{"channel":0,"gain":0,"inverted":false}],"dest":0},{"sources":[{"channel":2,"gain":0,"inverted":false}],"dest":2},{"sources":[{"channel":3,"gain":10,"inverted":false}],"dest":3}]}},"filters":{"delay_c":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_l":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_r":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_sub1":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_sub2":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"gain_c":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_l":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_r":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub1":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub2":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"peq_l_0":{"type":"Biquad","parameters":{"freq":1000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_l_1":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_r_2":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":1.0,"type":"Peaking"}}},"pipeline":[{"type":"Mixer","name":"split_non_sub"},{"type":"Mixer","name":"combine_sub"},{"type":"Filter","channel":1,"names":["delay_c","gain_c"]},{"type":"Filter","channel":0,"names":["peq_l_0","peq_l_1","delay_l","gain_l"]},{"type":"Filter","channel":2,"names":["peq_r_2","delay_r","gain_r"]},{"type":"Filter","channel":3,"names":["delay_sub1","gain_sub1"]},{"type":"Filter","channel":4,"names":["delay_sub2","gain_sub2"]}],"devices":{"samplerate":96000,"capture_samplerate":48000,"chunksize":2048,"queuelimit":4,"capture":{"type":"Alsa","channels":4,"device":"hw:Loopback,1","format":"S32LE"},"playback":{"type":"Alsa","channels":5,"device":"hw:DAC8PRO","format":"S32LE"},"resampler":{"type":"Synchronous"}}}"#
+            json::to_string(&convert_processor_settings_to_camilla(&settings).unwrap().0).unwrap(),
+            r#"{"mixers":{"combine_sub":{"channels":{"in":4,"out":5},"mapping":[{"sources":[{"channel":1,"gain":0.0,"inverted":false}],"dest":1},{"sources":[{"channel":0,"gain":0.0,"inverted":false}],"dest":0},{"sources":[{"channel":2,"gain":0.0,"inverted":false}],"dest":2},{"sources":[{"channel":3,"gain":0.0,"inverted":false}],"dest":3},{"sources":[{"channel":3,"gain":0.0,"inverted":false}],"dest":4}]},"split_non_sub":{"channels":{"in":4,"out":4},"mapping":[{"sources":[{"channel":1,"gain":0.0,"inverted":false}],"dest":1},{"sources":[{"channel":0,"gain":0.0,"inverted":false}],"dest":0},{"sources":[{"channel":2,"gain":0.0,"inverted":false}],"dest":2},{"sources":[{"channel":3,"gain":10.0,"inverted":false}],"dest":3}]}},"filters":{"delay_c":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_l":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_r":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_sub1":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_sub2":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"gain_c":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_l":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_r":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub1":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub2":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"peq_l_0":{"type":"Biquad","parameters":{"freq":1000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_l_1":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_r_2":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":1.0,"type":"Peaking"}}},"pipeline":[{"type":"Mixer","name":"split_non_sub"},{"type":"Mixer","name":"combine_sub"},{"type":"Filter","channel":1,"names":["delay_c","gain_c"]},{"type":"Filter","channel":0,"names":["peq_l_0","peq_l_1","delay_l","gain_l"]},{"type":"Filter","channel":2,"names":["peq_r_2","delay_r","gain_r"]},{"type":"Filter","channel":3,"names":["delay_sub1","gain_sub1"]},{"type":"Filter","channel":4,"names":["delay_sub2","gain_sub2"]}],"devices":{"samplerate":96000,"capture_samplerate":48000,"chunksize":2048,"queuelimit":4,"capture":{"type":"Alsa","channels":4,"device":"hw:Loopback,1","format":"S32LE"},"playback":{"type":"Alsa","channels":5,"device":"hw:DAC8PRO","format":"S32LE"},"resampler":{"type":"Synchronous"}}}"#
         )
     }
     #[test]
     fn check_processor_to_camilla_two_sub_partial_crossover() {
         let settings = ProcessorSettingsForCamilla {
+            global_filters: vec![],
+            phase_alignment: vec![],
+            drivers: vec![],
+            lfe: LfeInput::default(),
+            input_layout: InputLayout::SpeakerOrder,
+            custom_layout: vec![],
+            upmix: None,
             filters: vec![
                 Filter {
                     freq: 1000,
                     gain: 2.0,
                     q: 0.707,
                     speaker: "l".to_string(),
+                    ..Default::default()
                 },
                 Filter {
                     freq: 2000,
                     gain: 2.0,
                     q: 0.707,
                     speaker: "l".to_string(),
+                    ..Default::default()
                 },
                 Filter {
                     freq: 2000,
                     gain: 1.0,
                     q: 0.707,
                     speaker: "r".to_string(),
+                    ..Default::default()
                 },
             ],
             speakers: vec![
@@ -1026,6 +2434,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "c".to_string(),
@@ -1033,6 +2442,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "r".to_string(),
@@ -1040,6 +2450,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: false,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "sub1".to_string(),
@@ -1047,6 +2458,7 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: true,
+                    ..Default::default()
                 },
                 Speaker {
                     speaker: "sub2".to_string(),
@@ -1054,15 +2466,206 @@ mod tests {
                     delay: 10.0,
                     gain: 1.0,
                     is_subwoofer: true,
+                    ..Default::default()
                 },
             ],
             //selected_distance: SelectedDistanceType::MS,
             device: DeviceType::OktoDac8,
+            selected_distance: SelectedDistanceType::MS,
+            impulse_responses: vec![],
+            loudness: None,
+            dither: DitherType::Highpass,
+            headroom_compensation: false,
         };
 
         assert_eq!(
-            json::to_string(&convert_processor_settings_to_camilla(&settings).unwrap()).unwrap(),
-            r#"{"mixers":{"combine_sub":{"channels":{"in":5,"out":5},"mapping":[{"sources":[{"channel":2,"gain":0,"inverted":false}],"dest":1},{"sources":[{"channel":0,"gain":0,"inverted":false}],"dest":0},{"sources":[{"channel":3,"gain":0,"inverted":false}],"dest":2},{"sources":[{"channel":1,"gain":0,"inverted":false},{"channel":4,"gain":0,"inverted":false}],"dest":3},{"sources":[{"channel":1,"gain":0,"inverted":false},{"channel":4,"gain":0,"inverted":false}],"dest":4}]},"split_non_sub":{"channels":{"in":4,"out":5},"mapping":[{"sources":[{"channel":1,"gain":0,"inverted":false}],"dest":2},{"sources":[{"channel":0,"gain":0,"inverted":false}],"dest":0},{"sources":[{"channel":0,"gain":0,"inverted":false}],"dest":1},{"sources":[{"channel":2,"gain":0,"inverted":false}],"dest":3},{"sources":[{"channel":3,"gain":10,"inverted":false}],"dest":4}]}},"filters":{"crossover_speaker_l":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_subwooferl":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"delay_c":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_l":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_r":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_sub1":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"delay_sub2":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms"}},"gain_c":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_l":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_r":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub1":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub2":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"peq_l_0":{"type":"Biquad","parameters":{"freq":1000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_l_1":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_r_2":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":1.0,"type":"Peaking"}}},"pipeline":[{"type":"Mixer","name":"split_non_sub"},{"type":"Filter","channel":0,"names":["crossover_speaker_l"]},{"type":"Filter","channel":1,"names":["crossover_subwooferl"]},{"type":"Mixer","name":"combine_sub"},{"type":"Filter","channel":1,"names":["delay_c","gain_c"]},{"type":"Filter","channel":0,"names":["peq_l_0","peq_l_1","delay_l","gain_l"]},{"type":"Filter","channel":2,"names":["peq_r_2","delay_r","gain_r"]},{"type":"Filter","channel":3,"names":["delay_sub1","gain_sub1"]},{"type":"Filter","channel":4,"names":["delay_sub2","gain_sub2"]}],"devices":{"samplerate":96000,"capture_samplerate":48000,"chunksize":2048,"queuelimit":4,"capture":{"type":"Alsa","channels":4,"device":"hw:Loopback,1","format":"S32LE"},"playback":{"type":"Alsa","channels":5,"device":"hw:DAC8PRO","format":"S32LE"},"resampler":{"type":"Synchronous"}}}"#
+            json::to_string(&convert_processor_settings_to_camilla(&settings).unwrap().0).unwrap(),
+            r#"{"mixers":{"combine_sub":{"channels":{"in":5,"out":5},"mapping":[{"sources":[{"channel":2,"gain":0.0,"inverted":false}],"dest":1},{"sources":[{"channel":0,"gain":0.0,"inverted":false}],"dest":0},{"sources":[{"channel":3,"gain":0.0,"inverted":false}],"dest":2},{"sources":[{"channel":1,"gain":0.0,"inverted":false},{"channel":4,"gain":0.0,"inverted":false}],"dest":3},{"sources":[{"channel":1,"gain":0.0,"inverted":false},{"channel":4,"gain":0.0,"inverted":false}],"dest":4}]},"split_non_sub":{"channels":{"in":4,"out":5},"mapping":[{"sources":[{"channel":1,"gain":0.0,"inverted":false}],"dest":2},{"sources":[{"channel":0,"gain":0.0,"inverted":false}],"dest":0},{"sources":[{"channel":0,"gain":0.0,"inverted":false}],"dest":1},{"sources":[{"channel":2,"gain":0.0,"inverted":false}],"dest":3},{"sources":[{"channel":3,"gain":10.0,"inverted":false}],"dest":4}]}},"filters":{"crossover_speaker_l":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthHighpass"}},"crossover_subwooferl":{"type":"BiquadCombo","parameters":{"freq":80,"order":4,"type":"ButterworthLowpass"}},"delay_c":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_l":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_r":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_sub1":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"delay_sub2":{"type":"Delay","parameters":{"delay":10.0,"unit":"ms","subsample":true}},"gain_c":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_l":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_r":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub1":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"gain_sub2":{"type":"Gain","parameters":{"gain":1.0,"inverted":false}},"peq_l_0":{"type":"Biquad","parameters":{"freq":1000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_l_1":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":2.0,"type":"Peaking"}},"peq_r_2":{"type":"Biquad","parameters":{"freq":2000,"q":0.707,"gain":1.0,"type":"Peaking"}}},"pipeline":[{"type":"Mixer","name":"split_non_sub"},{"type":"Filter","channel":0,"names":["crossover_speaker_l"]},{"type":"Filter","channel":1,"names":["crossover_subwooferl"]},{"type":"Mixer","name":"combine_sub"},{"type":"Filter","channel":1,"names":["delay_c","gain_c"]},{"type":"Filter","channel":0,"names":["peq_l_0","peq_l_1","delay_l","gain_l"]},{"type":"Filter","channel":2,"names":["peq_r_2","delay_r","gain_r"]},{"type":"Filter","channel":3,"names":["delay_sub1","gain_sub1"]},{"type":"Filter","channel":4,"names":["delay_sub2","gain_sub2"]}],"devices":{"samplerate":96000,"capture_samplerate":48000,"chunksize":2048,"queuelimit":4,"capture":{"type":"Alsa","channels":4,"device":"hw:Loopback,1","format":"S32LE"},"playback":{"type":"Alsa","channels":5,"device":"hw:DAC8PRO","format":"S32LE"},"resampler":{"type":"Synchronous"}}}"#
         )
     }
+
+    #[test]
+    fn check_processor_to_camilla_headroom() {
+        let speaker = |name: &str, crossover: Option<i32>, is_subwoofer: bool| Speaker {
+            speaker: name.to_string(),
+            crossover,
+            delay: 0.0,
+            gain: 0.0,
+            is_subwoofer,
+            ..Default::default()
+        };
+        let mut settings = ProcessorSettingsForCamilla {
+            global_filters: vec![],
+            phase_alignment: vec![],
+            drivers: vec![],
+            lfe: LfeInput::default(),
+            input_layout: InputLayout::SpeakerOrder,
+            custom_layout: vec![],
+            upmix: None,
+            filters: vec![],
+            speakers: vec![speaker("l", Some(80), false), speaker("sub1", None, true)],
+            device: DeviceType::OktoDac8,
+            selected_distance: SelectedDistanceType::MS,
+            impulse_responses: vec![],
+            loudness: None,
+            dither: DitherType::Highpass,
+            headroom_compensation: false,
+        };
+        let (_, headroom) = convert_processor_settings_to_camilla(&settings).unwrap();
+        assert_eq!(headroom.peak_gains.len(), 2);
+        //highpassed speaker never goes above 0dB
+        assert!(headroom.peak_gains[0].abs() < 0.1);
+        //subwoofer sums the +10dB LFE with the lowpassed speaker: 20*log10(10^0.5+1)
+        assert!((headroom.peak_gains[1] - 12.39).abs() < 0.1);
+        assert_eq!(headroom.applied, 0.0);
+
+        settings.headroom_compensation = true;
+        let (config, headroom) = convert_processor_settings_to_camilla(&settings).unwrap();
+        assert_eq!(headroom.applied, headroom.peak_gains[1]);
+        let config = json::to_string(&config).unwrap();
+        assert!(config.contains(r#""headroom":{"type":"Gain","parameters":{"gain":-12.3"#));
+        assert!(config.contains(r#""names":["headroom","delay_sub1","gain_sub1"]"#));
+    }
+    #[test]
+    fn check_processor_to_camilla_response() {
+        let settings = ProcessorSettingsForCamilla {
+            global_filters: vec![],
+            phase_alignment: vec![],
+            drivers: vec![],
+            lfe: LfeInput::default(),
+            input_layout: InputLayout::SpeakerOrder,
+            custom_layout: vec![],
+            upmix: None,
+            filters: vec![Filter {
+                freq: 1000,
+                gain: -3.0,
+                q: 2.0,
+                speaker: "l".to_string(),
+                ..Default::default()
+            }],
+            speakers: vec![Speaker {
+                speaker: "l".to_string(),
+                crossover: Some(80),
+                delay: 1.5,
+                gain: -2.0,
+                inverted: true,
+                ..Default::default()
+            }],
+            device: DeviceType::OktoDac8,
+            selected_distance: SelectedDistanceType::MS,
+            impulse_responses: vec![],
+            loudness: None,
+            dither: DitherType::Highpass,
+            headroom_compensation: false,
+        };
+        let (config, _) = convert_processor_settings_to_camilla(&settings).unwrap();
+        let result = config.response(&settings);
+        assert_eq!(result.samplerate, 96000);
+        assert_eq!(result.frequencies.len(), 256);
+        assert_eq!(result.frequencies[0], 10.0);
+        assert!((result.frequencies[255] - 24000.0).abs() < 0.1);
+        assert_eq!(result.channels.len(), 1);
+        let channel = &result.channels[0];
+        assert_eq!(channel.speaker, "l");
+        assert_eq!(channel.delay, 1.5);
+        //well above the crossover only the gain is left, and inverting flips the phase
+        let index = result
+            .frequencies
+            .iter()
+            .position(|f| *f > 10000.0)
+            .unwrap();
+        assert!((channel.magnitude[index] + 2.0).abs() < 0.1);
+        assert!((channel.phase[index].abs() - 180.0).abs() < 5.0);
+        //without a subwoofer the crossover isn't applied, so the bass is left alone
+        assert!((channel.magnitude[0] + 2.0).abs() < 0.1);
+        assert!(channel.group_delay[0].abs() < 0.1);
+    }
+
+    #[test]
+    fn check_processor_to_camilla_response_one_sub() {
+        let speaker = |name: &str, crossover: Option<i32>, is_subwoofer: bool| Speaker {
+            speaker: name.to_string(),
+            crossover,
+            is_subwoofer,
+            ..Default::default()
+        };
+        let mut settings = ProcessorSettingsForCamilla {
+            global_filters: vec![],
+            phase_alignment: vec![],
+            drivers: vec![],
+            lfe: LfeInput::default(),
+            input_layout: InputLayout::SpeakerOrder,
+            custom_layout: vec![],
+            upmix: None,
+            filters: vec![],
+            speakers: vec![speaker("l", Some(80), false), speaker("sub1", None, true)],
+            device: DeviceType::OktoDac8,
+            selected_distance: SelectedDistanceType::MS,
+            impulse_responses: vec![],
+            loudness: None,
+            dither: DitherType::Highpass,
+            headroom_compensation: false,
+        };
+        let (config, _) = convert_processor_settings_to_camilla(&settings).unwrap();
+        let result = config.response(&settings);
+        let speakers: Vec<&str> = result.channels.iter().map(|c| c.speaker.as_str()).collect();
+        assert_eq!(speakers, vec!["l", "sub1"]);
+        //highpass removes low bass from the speaker
+        assert!(result.channels[0].magnitude[0] < -40.0);
+        assert!(result.channels[0].group_delay[0] > 0.0);
+        //subwoofer sums the +10dB LFE with the lowpassed speaker: 20*log10(10^0.5+1)
+        assert!((result.channels[1].magnitude[0] - 12.39).abs() < 0.1);
+        assert!(result.channels[1].group_delay[0] > 0.0);
+
+        //headroom taken off the config shows up in the response
+        settings.headroom_compensation = true;
+        let (config, _) = convert_processor_settings_to_camilla(&settings).unwrap();
+        let result = config.response(&settings);
+        assert!(result.channels[1].magnitude[0].abs() < 0.1);
+        assert!((result.channels[0].magnitude[255] + 12.39).abs() < 0.1);
+    }
+
+    #[test]
+    fn check_processor_to_camilla_global_filters() {
+        let speaker = |name: &str, is_subwoofer: bool| Speaker {
+            speaker: name.to_string(),
+            crossover: None,
+            delay: 0.0,
+            gain: 0.0,
+            is_subwoofer,
+            ..Default::default()
+        };
+        let settings = ProcessorSettingsForCamilla {
+            filters: vec![],
+            phase_alignment: vec![],
+            drivers: vec![],
+            lfe: LfeInput::default(),
+            input_layout: InputLayout::SpeakerOrder,
+            custom_layout: vec![],
+            upmix: None,
+            global_filters: vec![Filter {
+                freq: 100,
+                gain: 3.0,
+                q: 0.707,
+                speaker: String::new(),
+                filter_type: PeqType::Lowshelf,
+                slope: Some(6.0),
+                ..Default::default()
+            }],
+            speakers: vec![speaker("l", false), speaker("sub1", true)],
+            device: DeviceType::OktoDac8,
+            selected_distance: SelectedDistanceType::MS,
+            impulse_responses: vec![],
+            loudness: None,
+            dither: DitherType::None,
+            headroom_compensation: false,
+        };
+        let (config, _) = convert_processor_settings_to_camilla(&settings).unwrap();
+        let config = json::to_string(&config).unwrap();
+        assert!(config.contains(
+            r#""global_peq_0":{"type":"Biquad","parameters":{"freq":100,"slope":6.0,"gain":3.0,"type":"Lowshelf"}}"#
+        ));
+        assert!(config.contains(r#""names":["global_peq_0","delay_l","gain_l"]"#));
+        assert!(config.contains(r#""names":["global_peq_0","delay_sub1","gain_sub1"]"#));
+    }
 }
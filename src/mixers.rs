@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 
-use crate::processor::Speaker;
+use crate::processor::{ChannelRole, Driver, LfeInput, Speaker, Upmix};
 use rocket::serde::Serialize;
 
 pub fn split_mixer_name() -> String {
@@ -19,18 +19,18 @@ pub(crate) struct ChannelCount {
     pub(crate) num_out_channel: usize,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 #[serde(crate = "rocket::serde")]
-struct Source {
-    channel: usize,
-    gain: i32, //this should be 0, there is a "Gain" filter https://github.com/HEnquist/camilladsp/blob/master/exampleconfigs/pulseconfig.yml#L26 this is ONLY for inputs not output
-    inverted: bool, //always false in my case
+pub(crate) struct Source {
+    pub(crate) channel: usize,
+    pub(crate) gain: f32, //this should be 0, there is a "Gain" filter https://github.com/HEnquist/camilladsp/blob/master/exampleconfigs/pulseconfig.yml#L26 this is ONLY for inputs not output
+    pub(crate) inverted: bool, //always false in my case
 }
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 pub(crate) struct Mapping {
-    sources: Vec<Source>, //inputs.  This will be used for crossover (all sources will be mapped to subwoofers)
-    dest: usize,          //index of destination speaker
+    pub(crate) sources: Vec<Source>, //inputs.  This will be used for crossover (all sources will be mapped to subwoofers)
+    pub(crate) dest: usize,          //index of destination speaker
 }
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
@@ -39,34 +39,157 @@ pub struct Mixer {
     pub(crate) mapping: Vec<Mapping>,
 }
 
-const NUM_INPUT_SUBWOOFERS: usize = 1;
-
 pub struct SpeakerCounts {
     speakers_exclude_sub: usize,
-    input_subwoofers: usize,
     output_subwoofers: usize,
+    output_channels: usize,
+    output_ports: BTreeMap<String, usize>, //port of each speaker, or driver of a multi-way speaker
+    port_mapped: bool, //some outputs are set to a port, which always needs a mixer
+    input_channels: usize,
+    speaker_input_channels: Vec<usize>, //input channel of each non-sub speaker, in order
+    role_based: bool,                   //inputs are routed by role, which always needs a mixer
+    lfe_dropped: bool, //the LFE input is skipped with no subwoofers to play it, which needs a mixer
+    lfe_channel: usize,
+    lfe_gain: f32,
+    upmix: Option<Upmix>,
     pub(crate) input_subwoofer_speakers: Vec<String>,
 }
 
-pub fn get_speaker_counts(speakers: &[Speaker]) -> SpeakerCounts {
+/// an upmixed source is stereo whatever the layout
+const UPMIX_LAYOUT: [ChannelRole; 2] = [ChannelRole::FrontLeft, ChannelRole::FrontRight];
+
+/// `layout` is the role of each input channel in order; when empty the inputs are the
+/// non-sub speakers in order with the LFE at its configured channel
+pub fn get_speaker_counts(
+    speakers: &[Speaker],
+    drivers: &[Driver],
+    lfe: &LfeInput,
+    layout: &[ChannelRole],
+    upmix: &Option<Upmix>,
+) -> SpeakerCounts {
+    let layout = if upmix.is_some() {
+        &UPMIX_LAYOUT[..]
+    } else {
+        layout
+    };
     let output_subwoofers = speakers
         .iter()
         .enumerate()
         .filter(|(_, s)| s.is_subwoofer)
         .count();
 
-    let input_subwoofers = if output_subwoofers > 0 {
-        NUM_INPUT_SUBWOOFERS
+    let speakers_exclude_sub = speakers.len() - output_subwoofers;
+
+    //a multi-way speaker has an output per driver, the subwoofers come last
+    let outputs: Vec<(&String, Option<i32>)> = speakers
+        .iter()
+        .filter(|s| !s.is_subwoofer)
+        .flat_map(|s| {
+            let speaker_drivers: Vec<(&String, Option<i32>)> = drivers
+                .iter()
+                .filter(|d| d.speaker == s.speaker)
+                .map(|d| (&d.driver, d.output_port))
+                .collect();
+            if speaker_drivers.is_empty() {
+                vec![(&s.speaker, s.output_port)]
+            } else {
+                speaker_drivers
+            }
+        })
+        .chain(
+            speakers
+                .iter()
+                .filter(|s| s.is_subwoofer)
+                .map(|s| (&s.speaker, s.output_port)),
+        )
+        .collect();
+    //outputs without a port take the lowest ports the others leave free
+    let claimed_ports: Vec<usize> = outputs
+        .iter()
+        .filter_map(|(_, port)| port.map(|port| port as usize))
+        .collect();
+    let mut free_ports = (0..).filter(|port| !claimed_ports.contains(port));
+    let output_ports: BTreeMap<String, usize> = outputs
+        .iter()
+        .map(|(output, port)| {
+            (
+                output.to_string(),
+                port.map(|port| port as usize)
+                    .unwrap_or_else(|| free_ports.next().unwrap_or_default()),
+            )
+        })
+        .collect();
+
+    let layout_lfe_channel = layout.iter().position(|role| *role == ChannelRole::Lfe);
+    let has_lfe = match upmix {
+        Some(upmix) => upmix.lfe_lowpass.is_some(), //derived from the stereo input
+        None => lfe.enabled && (layout.is_empty() || layout_lfe_channel.is_some()),
+    };
+    let input_subwoofers = if output_subwoofers > 0 && has_lfe {
+        1
     } else {
         0
     };
 
-    let speakers_exclude_sub = speakers.len() - output_subwoofers;
+    //a source with the LFE at a set channel still has it there without subwoofers to play it
+    let lfe_dropped =
+        layout.is_empty() && input_subwoofers == 0 && lfe.enabled && lfe.channel.is_some();
+    let (lfe_channel, speaker_input_channels, input_channels) = if layout.is_empty() {
+        let lfe_channel = lfe
+            .channel
+            .map(|channel| channel as usize)
+            .unwrap_or(speakers_exclude_sub);
+        let skips_lfe = input_subwoofers > 0 || lfe_dropped;
+        //the speakers skip over the LFE channel
+        let speaker_input_channels = (0..speakers_exclude_sub)
+            .map(|index| {
+                if skips_lfe && index >= lfe_channel {
+                    index + 1
+                } else {
+                    index
+                }
+            })
+            .collect();
+        (
+            lfe_channel,
+            speaker_input_channels,
+            speakers_exclude_sub + if skips_lfe { 1 } else { 0 },
+        )
+    } else {
+        let speaker_input_channels = speakers
+            .iter()
+            .filter(|s| !s.is_subwoofer)
+            .enumerate()
+            .map(|(index, s)| {
+                s.role
+                    .and_then(|role| layout.iter().position(|r| *r == role))
+                    .unwrap_or(index) //validation requires a role in the layout
+            })
+            .collect();
+        (
+            layout_lfe_channel.unwrap_or_default(),
+            speaker_input_channels,
+            layout.len(),
+        )
+    };
 
     SpeakerCounts {
         speakers_exclude_sub,
-        input_subwoofers,
         output_subwoofers,
+        output_channels: output_ports
+            .values()
+            .map(|port| port + 1)
+            .max()
+            .unwrap_or(0),
+        port_mapped: !claimed_ports.is_empty(),
+        output_ports,
+        input_channels,
+        speaker_input_channels,
+        role_based: !layout.is_empty(),
+        lfe_dropped,
+        lfe_channel,
+        lfe_gain: lfe.gain,
+        upmix: *upmix,
         input_subwoofer_speakers: (0..input_subwoofers)
             .map(|index| format!("subwoofer_input_{}", index))
             .collect(),
@@ -74,15 +197,47 @@ pub fn get_speaker_counts(speakers: &[Speaker]) -> SpeakerCounts {
 }
 
 pub fn input_speaker_count(speaker_counts: &SpeakerCounts) -> usize {
-    speaker_counts.speakers_exclude_sub + speaker_counts.input_subwoofers
+    speaker_counts.input_channels
+}
+
+/// sources of a track derived from the stereo input, by the role of its speaker
+fn upmix_sources(upmix: &Upmix, role: Option<ChannelRole>, is_sub: bool) -> Vec<Source> {
+    let source = |channel: usize, gain: f32, inverted: bool| Source {
+        channel,
+        gain,
+        inverted,
+    };
+    let Upmix {
+        center_gain,
+        surround_gain,
+        lfe_gain,
+        ..
+    } = *upmix;
+    match role {
+        _ if is_sub => vec![source(0, lfe_gain, false), source(1, lfe_gain, false)],
+        Some(ChannelRole::FrontLeft) => vec![source(0, 0.0, false)],
+        Some(ChannelRole::FrontRight) => vec![source(1, 0.0, false)],
+        Some(ChannelRole::RearLeft | ChannelRole::SideLeft) => vec![
+            source(0, surround_gain, false),
+            source(1, surround_gain, true),
+        ],
+        Some(ChannelRole::RearRight | ChannelRole::SideRight) => vec![
+            source(1, surround_gain, false),
+            source(0, surround_gain, true),
+        ],
+        //the center, and speakers validation rejects, play the sum
+        _ => vec![source(0, center_gain, false), source(1, center_gain, false)],
+    }
 }
 
 pub fn output_speaker_count_no_mixer(speaker_counts: &SpeakerCounts) -> usize {
     speaker_counts.speakers_exclude_sub + speaker_counts.output_subwoofers
 }
 
+#[allow(clippy::type_complexity)]
 pub fn split_inputs<'a>(
     speakers: &'a [Speaker],
+    drivers: &'a [Driver],
     speaker_counts: &'a SpeakerCounts,
 ) -> Option<(
     Mixer,
@@ -90,14 +245,22 @@ pub fn split_inputs<'a>(
     BTreeMap<&'a String, (usize, Vec<usize>)>,
 )> {
     let SpeakerCounts {
-        speakers_exclude_sub,
         output_subwoofers,
+        output_ports,
+        port_mapped,
+        speaker_input_channels,
+        role_based,
+        lfe_dropped,
+        lfe_channel,
+        lfe_gain,
+        upmix,
         input_subwoofer_speakers,
         ..
     } = speaker_counts;
 
     // what if input has a subwoofer?  Do I need to mix sub back to speakers?
-    if *output_subwoofers > 0 {
+    if *output_subwoofers > 0 || !drivers.is_empty() || *role_based || *port_mapped || *lfe_dropped
+    {
         //key is speakername, then tuple of (whether it has a crossover, whether is a sub, input index, output indeces)
         let mut input_channel_mapping: BTreeMap<&String, (bool, bool, usize, Vec<usize>)> =
             BTreeMap::new();
@@ -109,78 +272,99 @@ pub fn split_inputs<'a>(
 
         //channel_mapping.
         for (speaker_index, speaker) in speakers.iter().filter(|v| !v.is_subwoofer).enumerate() {
-            if speaker.crossover.is_some() {
+            //the speaker's track is fanned out to each of its drivers
+            let mut speaker_outputs: Vec<&String> = drivers
+                .iter()
+                .filter(|d| d.speaker == speaker.speaker)
+                .map(|d| &d.driver)
+                .collect();
+            if speaker_outputs.is_empty() {
+                speaker_outputs.push(&speaker.speaker);
+            }
+            for output in speaker_outputs {
+                output_channel_mapping.insert(output, (output_ports[output], vec![track_index]));
+            }
+            //without subwoofers there is nothing to cross over to
+            if speaker.crossover.is_some() && !subs.is_empty() {
                 input_channel_mapping.insert(
                     &speaker.speaker,
                     (
                         true,
                         speaker.is_subwoofer,
-                        speaker_index,
+                        speaker_input_channels[speaker_index],
                         vec![track_index, track_index + 1],
                     ),
                 );
 
-                for (sub_index, sub_name) in subs.iter().enumerate() {
+                for sub_name in subs.iter() {
                     output_channel_mapping
                         .entry(&sub_name.speaker)
                         .and_modify(|(_, v)| v.push(track_index + 1))
-                        .or_insert((sub_index + speakers_exclude_sub, vec![track_index + 1]));
+                        .or_insert((output_ports[&sub_name.speaker], vec![track_index + 1]));
                 }
-                output_channel_mapping.insert(&speaker.speaker, (speaker_index, vec![track_index]));
                 track_index += 2;
-            } else if speaker.crossover.is_none() {
+            } else {
                 input_channel_mapping.insert(
                     &speaker.speaker,
                     (
                         false,
                         speaker.is_subwoofer,
-                        speaker_index,
+                        speaker_input_channels[speaker_index],
                         vec![track_index],
                     ),
                 );
-                output_channel_mapping.insert(&speaker.speaker, (speaker_index, vec![track_index]));
                 track_index += 1;
             }
         }
         for (index, speaker) in input_subwoofer_speakers.iter().enumerate() {
             input_channel_mapping.insert(
-                &speaker,
-                (false, true, speakers_exclude_sub + index, vec![track_index]),
+                speaker,
+                (false, true, lfe_channel + index, vec![track_index]),
             );
-            for (sub_index, sub_name) in subs.iter().enumerate() {
+            for sub_name in subs.iter() {
                 output_channel_mapping
                     .entry(&sub_name.speaker)
                     .and_modify(|(_, v)| v.push(track_index))
-                    .or_insert((sub_index + speakers_exclude_sub, vec![track_index]));
+                    .or_insert((output_ports[&sub_name.speaker], vec![track_index]));
             }
             track_index += 1;
         }
 
         let channels = ChannelCount {
-            num_in_channel: input_speaker_count(&speaker_counts),
+            num_in_channel: input_speaker_count(speaker_counts),
             num_out_channel: track_index,
         };
 
         let mapping: Vec<Mapping> = input_channel_mapping
             .iter()
-            .map(|(_, (_, is_sub, speaker_index, channel_indeces))| {
-                channel_indeces.iter().map(|channel_index| Mapping {
-                    dest: *channel_index,
-                    sources: vec![Source {
+            .flat_map(|(name, (_, is_sub, speaker_index, channel_indeces))| {
+                let sources = match upmix {
+                    Some(upmix) => upmix_sources(
+                        upmix,
+                        speakers
+                            .iter()
+                            .find(|s| &&s.speaker == name)
+                            .and_then(|s| s.role),
+                        *is_sub,
+                    ),
+                    None => vec![Source {
                         channel: *speaker_index,
-                        gain: if *is_sub { 10 } else { 0 }, //bass boost needed for subwoofer channel.  careful, turning up camilladsp to MAX volume will cause issues because of this
+                        gain: if *is_sub { *lfe_gain } else { 0.0 }, //bass boost needed for subwoofer channel.  careful, turning up camilladsp to MAX volume will cause issues because of this
                         inverted: false,
                     }],
+                };
+                channel_indeces.iter().map(move |channel_index| Mapping {
+                    dest: *channel_index,
+                    sources: sources.clone(),
                 })
             })
-            .flatten()
             .collect();
 
-        return Some((
+        Some((
             Mixer { channels, mapping },
             input_channel_mapping,
             output_channel_mapping,
-        ));
+        ))
     } else {
         None
     }
@@ -192,11 +376,7 @@ pub fn combine_inputs(
     split_mixer: &Mixer,
     output_channel_mapping: &BTreeMap<&String, (usize, Vec<usize>)>,
 ) -> Mixer {
-    let SpeakerCounts {
-        speakers_exclude_sub,
-        output_subwoofers,
-        ..
-    } = *speaker_counts;
+    let output_channels = speaker_counts.output_channels;
 
     let mapping = output_channel_mapping
         .iter()
@@ -206,7 +386,7 @@ pub fn combine_inputs(
                 .iter()
                 .map(|channel_index| Source {
                     channel: *channel_index,
-                    gain: 0,
+                    gain: 0.0,
                     inverted: false,
                 })
                 .collect(),
@@ -214,7 +394,7 @@ pub fn combine_inputs(
         .collect();
     let channels = ChannelCount {
         num_in_channel: split_mixer.channels.num_out_channel,
-        num_out_channel: speakers_exclude_sub + output_subwoofers,
+        num_out_channel: output_channels, //ports nothing is routed to stay silent
     };
     Mixer { channels, mapping }
 }
@@ -228,7 +408,10 @@ mod tests {
     use super::split_inputs;
     use super::ChannelCount;
     use super::Mixer;
-    use crate::processor::Speaker;
+    use super::{input_speaker_count, output_speaker_count_no_mixer};
+    use crate::processor::{
+        ChannelRole, CrossoverFamily, Driver, InputLayout, LfeInput, Speaker, Upmix,
+    };
 
     #[test]
     fn test_speaker_counts_no_sub() {
@@ -239,6 +422,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -246,6 +430,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sl".to_string(),
@@ -253,6 +438,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sr".to_string(),
@@ -260,11 +446,12 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
         ];
-        let result = get_speaker_counts(&speakers);
+        let result = get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None);
         assert!(result.speakers_exclude_sub == 4);
-        assert!(result.input_subwoofers == 0);
+        assert!(result.input_subwoofer_speakers.is_empty());
         assert!(result.output_subwoofers == 0);
     }
     #[test]
@@ -276,6 +463,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -283,6 +471,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sl".to_string(),
@@ -290,6 +479,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sr".to_string(),
@@ -297,6 +487,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub1".to_string(),
@@ -304,11 +495,12 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
         ];
-        let result = get_speaker_counts(&speakers);
+        let result = get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None);
         assert!(result.speakers_exclude_sub == 4);
-        assert!(result.input_subwoofers == 1);
+        assert!(result.input_subwoofer_speakers.len() == 1);
         assert!(result.output_subwoofers == 1);
     }
     #[test]
@@ -320,6 +512,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -327,6 +520,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sl".to_string(),
@@ -334,6 +528,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sr".to_string(),
@@ -341,6 +536,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub1".to_string(),
@@ -348,6 +544,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub2".to_string(),
@@ -355,11 +552,12 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
         ];
-        let result = get_speaker_counts(&speakers);
+        let result = get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None);
         assert!(result.speakers_exclude_sub == 4);
-        assert!(result.input_subwoofers == 1);
+        assert!(result.input_subwoofer_speakers.len() == 1);
         assert!(result.output_subwoofers == 2);
     }
 
@@ -372,6 +570,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -379,6 +578,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sl".to_string(),
@@ -386,6 +586,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sr".to_string(),
@@ -393,10 +594,11 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
         ];
-        let speaker_counts = get_speaker_counts(&speakers);
-        let result = split_inputs(&speakers, &speaker_counts);
+        let speaker_counts = get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None);
+        let result = split_inputs(&speakers, &[], &speaker_counts);
         assert!(result.is_none());
     }
     #[test]
@@ -408,6 +610,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -415,6 +618,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sl".to_string(),
@@ -422,6 +626,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sr".to_string(),
@@ -429,6 +634,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub1".to_string(),
@@ -436,10 +642,11 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
         ];
-        let speaker_counts = get_speaker_counts(&speakers);
-        let result = split_inputs(&speakers, &speaker_counts).unwrap();
+        let speaker_counts = get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None);
+        let result = split_inputs(&speakers, &[], &speaker_counts).unwrap();
         assert!(result.0.mapping.len() == 9);
 
         assert!(result.0.mapping[0].dest == 0);
@@ -479,6 +686,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -486,6 +694,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sl".to_string(),
@@ -493,6 +702,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sr".to_string(),
@@ -500,6 +710,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub1".to_string(),
@@ -507,10 +718,11 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
         ];
-        let speaker_counts = get_speaker_counts(&speakers);
-        let result = split_inputs(&speakers, &speaker_counts).unwrap();
+        let speaker_counts = get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None);
+        let result = split_inputs(&speakers, &[], &speaker_counts).unwrap();
         assert!(result.0.mapping.len() == 8);
 
         assert!(result.0.mapping[0].dest == 0); //passthrough
@@ -547,6 +759,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -554,6 +767,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sl".to_string(),
@@ -561,6 +775,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sr".to_string(),
@@ -568,6 +783,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub1".to_string(),
@@ -575,10 +791,11 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
         ];
-        let speaker_counts = get_speaker_counts(&speakers);
-        let result = split_inputs(&speakers, &speaker_counts).unwrap();
+        let speaker_counts = get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None);
+        let result = split_inputs(&speakers, &[], &speaker_counts).unwrap();
         assert!(result.0.mapping.len() == 5);
 
         assert!(result.0.mapping[0].dest == 0); //passthrough
@@ -605,6 +822,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -612,6 +830,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sl".to_string(),
@@ -619,6 +838,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sr".to_string(),
@@ -626,6 +846,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub1".to_string(),
@@ -633,6 +854,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub2".to_string(),
@@ -640,10 +862,11 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
         ];
-        let speaker_counts = get_speaker_counts(&speakers);
-        let result = split_inputs(&speakers, &speaker_counts).unwrap();
+        let speaker_counts = get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None);
+        let result = split_inputs(&speakers, &[], &speaker_counts).unwrap();
         assert!(result.0.mapping.len() == 9);
 
         assert!(result.0.mapping[0].dest == 0);
@@ -683,6 +906,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -690,6 +914,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "c".to_string(),
@@ -697,6 +922,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sl".to_string(),
@@ -704,6 +930,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sr".to_string(),
@@ -711,6 +938,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub1".to_string(),
@@ -718,6 +946,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
         ];
         let mut output_channel_mapping: BTreeMap<&String, (usize, Vec<usize>)> = BTreeMap::new();
@@ -741,7 +970,7 @@ mod tests {
             mapping: vec![],
         };
         let mix = combine_inputs(
-            &get_speaker_counts(&speakers),
+            &get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None),
             &split_mixer,
             &output_channel_mapping,
             //&speakers,
@@ -774,6 +1003,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -781,6 +1011,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "c".to_string(),
@@ -788,6 +1019,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sl".to_string(),
@@ -795,6 +1027,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sr".to_string(),
@@ -802,6 +1035,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub1".to_string(),
@@ -809,6 +1043,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub2".to_string(),
@@ -816,6 +1051,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
         ];
 
@@ -844,7 +1080,7 @@ mod tests {
             mapping: vec![],
         };
         let mix = combine_inputs(
-            &get_speaker_counts(&speakers),
+            &get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None),
             &split_mixer,
             &output_channel_mapping,
             //&speakers,
@@ -874,6 +1110,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -881,6 +1118,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "c".to_string(),
@@ -888,6 +1126,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: false,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub1".to_string(),
@@ -895,6 +1134,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub2".to_string(),
@@ -902,6 +1142,7 @@ mod tests {
                 delay: 10.0,
                 is_subwoofer: true,
                 gain: 2.0,
+                ..Default::default()
             },
         ];
 
@@ -924,7 +1165,7 @@ mod tests {
             mapping: vec![],
         };
         let mix = combine_inputs(
-            &get_speaker_counts(&speakers),
+            &get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None),
             &split_mixer,
             &output_channel_mapping,
             //&speakers,
@@ -943,4 +1184,336 @@ mod tests {
             2                                                            //two subs
         );
     }
+    #[test]
+    fn test_init_mixer_drivers() {
+        let speaker = |name: &str, crossover: Option<i32>, is_subwoofer: bool| Speaker {
+            speaker: name.to_string(),
+            crossover,
+            delay: 0.0,
+            is_subwoofer,
+            gain: 0.0,
+            ..Default::default()
+        };
+        let driver = |name: &str| Driver {
+            speaker: "l".to_string(),
+            driver: name.to_string(),
+            highpass: None,
+            lowpass: None,
+            crossover_type: CrossoverFamily::LinkwitzRiley,
+            crossover_order: 4,
+            delay: 0.0,
+            gain: 0.0,
+            inverted: false,
+            output_port: None,
+        };
+        let speakers = vec![
+            speaker("l", Some(80), false),
+            speaker("r", Some(80), false),
+            speaker("sub1", None, true),
+        ];
+        let drivers = vec![driver("l_woofer"), driver("l_tweeter")];
+        let speaker_counts =
+            get_speaker_counts(&speakers, &drivers, &LfeInput::default(), &[], &None);
+        let (split_mixer, _, output_channel_mapping) =
+            split_inputs(&speakers, &drivers, &speaker_counts).unwrap();
+        assert_eq!(split_mixer.channels.num_in_channel, 3); //2 speakers+1 sub input
+        assert_eq!(split_mixer.channels.num_out_channel, 5); //2*2+1 sub input
+
+        //both drivers play the speaker's highpassed track
+        let woofer = "l_woofer".to_string();
+        let tweeter = "l_tweeter".to_string();
+        let r = "r".to_string();
+        let sub1 = "sub1".to_string();
+        assert_eq!(output_channel_mapping.get(&woofer), Some(&(0, vec![0])));
+        assert_eq!(output_channel_mapping.get(&tweeter), Some(&(1, vec![0])));
+        assert_eq!(output_channel_mapping.get(&r), Some(&(2, vec![2])));
+        assert_eq!(output_channel_mapping.get(&sub1), Some(&(3, vec![1, 3, 4])));
+
+        let mix = combine_inputs(&speaker_counts, &split_mixer, &output_channel_mapping);
+        assert_eq!(mix.channels.num_out_channel, 4); //2 drivers+1 speaker+1 sub
+    }
+    #[test]
+    fn test_init_mixer_drivers_no_sub() {
+        let speakers = vec![Speaker {
+            speaker: "l".to_string(),
+            crossover: Some(80),
+            delay: 0.0,
+            is_subwoofer: false,
+            gain: 0.0,
+            ..Default::default()
+        }];
+        let drivers: Vec<Driver> = ["l_woofer", "l_tweeter"]
+            .iter()
+            .map(|name| Driver {
+                speaker: "l".to_string(),
+                driver: name.to_string(),
+                highpass: None,
+                lowpass: None,
+                crossover_type: CrossoverFamily::LinkwitzRiley,
+                crossover_order: 4,
+                delay: 0.0,
+                gain: 0.0,
+                inverted: false,
+                output_port: None,
+            })
+            .collect();
+        let speaker_counts =
+            get_speaker_counts(&speakers, &drivers, &LfeInput::default(), &[], &None);
+        let (split_mixer, input_channel_mapping, output_channel_mapping) =
+            split_inputs(&speakers, &drivers, &speaker_counts).unwrap();
+        //no subwoofer to cross over to, so the speaker is passed through to both drivers
+        assert_eq!(split_mixer.channels.num_out_channel, 1);
+        assert!(input_channel_mapping
+            .values()
+            .all(|(is_crossover, ..)| !is_crossover));
+        assert_eq!(output_channel_mapping.len(), 2);
+        let mix = combine_inputs(&speaker_counts, &split_mixer, &output_channel_mapping);
+        assert_eq!(mix.channels.num_out_channel, 2);
+    }
+    #[test]
+    fn test_init_mixer_lfe_channel() {
+        let speakers: Vec<Speaker> = ["l", "r", "c", "sl", "sr", "sub1"]
+            .iter()
+            .map(|name| Speaker {
+                speaker: name.to_string(),
+                crossover: None,
+                delay: 0.0,
+                is_subwoofer: *name == "sub1",
+                gain: 0.0,
+                ..Default::default()
+            })
+            .collect();
+        //5.1 order with the LFE after the center, from a source that already boosts it
+        let lfe = LfeInput {
+            enabled: true,
+            channel: Some(3),
+            gain: 0.0,
+        };
+        let speaker_counts = get_speaker_counts(&speakers, &[], &lfe, &[], &None);
+        let (split_mixer, input_channel_mapping, _) =
+            split_inputs(&speakers, &[], &speaker_counts).unwrap();
+        assert_eq!(split_mixer.channels.num_in_channel, 6);
+        let inputs: Vec<(&str, usize)> = input_channel_mapping
+            .iter()
+            .map(|(name, (_, _, input, _))| (name.as_str(), *input))
+            .collect();
+        assert_eq!(
+            inputs,
+            vec![
+                ("c", 2),
+                ("l", 0),
+                ("r", 1),
+                ("sl", 4),
+                ("sr", 5),
+                ("subwoofer_input_0", 3)
+            ]
+        );
+        assert!(split_mixer
+            .mapping
+            .iter()
+            .all(|m| m.sources.iter().all(|source| source.gain == 0.0)));
+    }
+    #[test]
+    fn test_init_mixer_lfe_channel_no_sub() {
+        let speakers: Vec<Speaker> = ["l", "r", "c", "sl", "sr"]
+            .iter()
+            .map(|name| Speaker {
+                speaker: name.to_string(),
+                crossover: None,
+                ..Default::default()
+            })
+            .collect();
+        //5.1 source played on 5 speakers, the LFE after the center is left out
+        let lfe = LfeInput {
+            enabled: true,
+            channel: Some(3),
+            gain: 0.0,
+        };
+        let speaker_counts = get_speaker_counts(&speakers, &[], &lfe, &[], &None);
+        assert_eq!(input_speaker_count(&speaker_counts), 6);
+        assert_eq!(output_speaker_count_no_mixer(&speaker_counts), 5);
+        let (split_mixer, input_channel_mapping, _) =
+            split_inputs(&speakers, &[], &speaker_counts).unwrap();
+        assert_eq!(split_mixer.channels.num_in_channel, 6);
+        assert_eq!(split_mixer.channels.num_out_channel, 5);
+        let inputs: Vec<(&str, usize)> = input_channel_mapping
+            .iter()
+            .map(|(name, (_, _, input, _))| (name.as_str(), *input))
+            .collect();
+        assert_eq!(
+            inputs,
+            vec![("c", 2), ("l", 0), ("r", 1), ("sl", 4), ("sr", 5)]
+        );
+    }
+    #[test]
+    fn test_init_mixer_no_lfe() {
+        let speakers: Vec<Speaker> = ["l", "sub1"]
+            .iter()
+            .map(|name| Speaker {
+                speaker: name.to_string(),
+                crossover: if *name == "l" { Some(80) } else { None },
+                delay: 0.0,
+                is_subwoofer: *name == "sub1",
+                gain: 0.0,
+                ..Default::default()
+            })
+            .collect();
+        let lfe = LfeInput {
+            enabled: false,
+            channel: None,
+            gain: 10.0,
+        };
+        let speaker_counts = get_speaker_counts(&speakers, &[], &lfe, &[], &None);
+        let (split_mixer, _, output_channel_mapping) =
+            split_inputs(&speakers, &[], &speaker_counts).unwrap();
+        //the sub only gets the bass crossed over from the speaker
+        assert_eq!(split_mixer.channels.num_in_channel, 1);
+        assert_eq!(split_mixer.channels.num_out_channel, 2);
+        let sub1 = "sub1".to_string();
+        assert_eq!(output_channel_mapping.get(&sub1), Some(&(1, vec![1])));
+    }
+    #[test]
+    fn test_init_mixer_input_layout() {
+        let speaker = |name: &str, role: ChannelRole| Speaker {
+            speaker: name.to_string(),
+            crossover: None,
+            delay: 0.0,
+            is_subwoofer: false,
+            gain: 0.0,
+            role: Some(role),
+            ..Default::default()
+        };
+        //saved in a different order than the source channels
+        let speakers = vec![
+            speaker("c", ChannelRole::Center),
+            speaker("sr", ChannelRole::RearRight),
+            speaker("l", ChannelRole::FrontLeft),
+            speaker("sl", ChannelRole::RearLeft),
+            speaker("r", ChannelRole::FrontRight),
+        ];
+        let layout = InputLayout::Alsa51.roles(&[], &[]);
+        let speaker_counts =
+            get_speaker_counts(&speakers, &[], &LfeInput::default(), &layout, &None);
+        //no subwoofers, but the inputs still have to be routed
+        let (split_mixer, input_channel_mapping, _) =
+            split_inputs(&speakers, &[], &speaker_counts).unwrap();
+        assert_eq!(split_mixer.channels.num_in_channel, 6);
+        let inputs: Vec<(&str, usize)> = input_channel_mapping
+            .iter()
+            .map(|(name, (_, _, input, _))| (name.as_str(), *input))
+            .collect();
+        assert_eq!(
+            inputs,
+            vec![("c", 4), ("l", 0), ("r", 1), ("sl", 2), ("sr", 3)]
+        );
+    }
+    #[test]
+    fn test_init_mixer_output_ports() {
+        let speaker = |name: &str, is_subwoofer: bool, output_port: Option<i32>| Speaker {
+            speaker: name.to_string(),
+            crossover: if is_subwoofer { None } else { Some(80) },
+            delay: 0.0,
+            is_subwoofer,
+            gain: 0.0,
+            output_port,
+            ..Default::default()
+        };
+        let speakers = vec![
+            speaker("l", false, Some(5)),
+            speaker("r", false, None),
+            speaker("sub1", true, Some(0)),
+        ];
+        let speaker_counts = get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None);
+        let (split_mixer, _, output_channel_mapping) =
+            split_inputs(&speakers, &[], &speaker_counts).unwrap();
+        let ports: Vec<(&str, usize)> = output_channel_mapping
+            .iter()
+            .map(|(name, (port, _))| (name.as_str(), *port))
+            .collect();
+        //r takes the lowest port left free
+        assert_eq!(ports, vec![("l", 5), ("r", 1), ("sub1", 0)]);
+        let mix = combine_inputs(&speaker_counts, &split_mixer, &output_channel_mapping);
+        assert_eq!(mix.channels.num_out_channel, 6); //ports 2 to 4 are silent
+        assert_eq!(mix.mapping.len(), 3);
+    }
+    #[test]
+    fn test_init_mixer_output_ports_no_sub() {
+        let speakers: Vec<Speaker> = [("l", Some(1)), ("r", Some(0))]
+            .iter()
+            .map(|(name, output_port)| Speaker {
+                speaker: name.to_string(),
+                crossover: None,
+                delay: 0.0,
+                is_subwoofer: false,
+                gain: 0.0,
+                output_port: *output_port,
+                ..Default::default()
+            })
+            .collect();
+        let speaker_counts = get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &None);
+        //swapping the outputs needs a mixer even without subwoofers
+        let (split_mixer, _, output_channel_mapping) =
+            split_inputs(&speakers, &[], &speaker_counts).unwrap();
+        let mix = combine_inputs(&speaker_counts, &split_mixer, &output_channel_mapping);
+        let routes: Vec<(usize, usize)> = mix
+            .mapping
+            .iter()
+            .map(|m| (m.sources[0].channel, m.dest))
+            .collect();
+        assert_eq!(routes, vec![(0, 1), (1, 0)]);
+    }
+    #[test]
+    fn test_init_mixer_upmix() {
+        let speaker = |name: &str, role: Option<ChannelRole>| Speaker {
+            speaker: name.to_string(),
+            crossover: if role.is_some() { Some(80) } else { None },
+            delay: 0.0,
+            is_subwoofer: role.is_none(),
+            gain: 0.0,
+            role,
+            ..Default::default()
+        };
+        let speakers = vec![
+            speaker("l", Some(ChannelRole::FrontLeft)),
+            speaker("r", Some(ChannelRole::FrontRight)),
+            speaker("c", Some(ChannelRole::Center)),
+            speaker("sl", Some(ChannelRole::SideLeft)),
+            speaker("sr", Some(ChannelRole::SideRight)),
+            speaker("sub1", None),
+        ];
+        let upmix = Some(Upmix {
+            center_gain: -3.0,
+            surround_gain: -6.0,
+            surround_delay: 12.0,
+            lfe_gain: -3.0,
+            lfe_lowpass: Some(80),
+        });
+        let speaker_counts = get_speaker_counts(&speakers, &[], &LfeInput::default(), &[], &upmix);
+        let (split_mixer, input_channel_mapping, _) =
+            split_inputs(&speakers, &[], &speaker_counts).unwrap();
+        //a stereo source feeds all of the 5.1 speakers
+        assert_eq!(split_mixer.channels.num_in_channel, 2);
+        let sources = |name: &str| -> Vec<(usize, f32, bool)> {
+            let key = name.to_string();
+            let (_, _, _, channel_indeces) = input_channel_mapping.get(&key).unwrap();
+            split_mixer
+                .mapping
+                .iter()
+                .find(|m| m.dest == channel_indeces[0])
+                .unwrap()
+                .sources
+                .iter()
+                .map(|s| (s.channel, s.gain, s.inverted))
+                .collect()
+        };
+        assert_eq!(sources("l"), vec![(0, 0.0, false)]);
+        assert_eq!(sources("r"), vec![(1, 0.0, false)]);
+        assert_eq!(sources("c"), vec![(0, -3.0, false), (1, -3.0, false)]);
+        assert_eq!(sources("sl"), vec![(0, -6.0, false), (1, -6.0, true)]);
+        assert_eq!(sources("sr"), vec![(1, -6.0, false), (0, -6.0, true)]);
+        assert_eq!(
+            sources("subwoofer_input_0"),
+            vec![(0, -3.0, false), (1, -3.0, false)]
+        );
+    }
 }
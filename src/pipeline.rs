@@ -1,14 +1,19 @@
 use crate::filters::{
-    crossover_speaker_name, crossover_subwoofer_name, delay_filter_name, gain_filter_name,
-    peq_filter_name,
+    allpass_filter_name, conv_filter_name, crossover_speaker_name, crossover_subwoofer_name,
+    delay_filter_name, driver_highpass_name, driver_lowpass_name, gain_filter_name,
+    peq_filter_name, upmix_lfe_lowpass_name, upmix_surround_delay_name,
 };
-use crate::processor::{Filter, Speaker};
+use crate::impulses::ImpulseResponse;
+use crate::processor::{ChannelRole, Driver, Filter, PhaseAlignment, Speaker, Upmix};
 use rocket::serde::Serialize;
 use std::collections::BTreeMap;
 
 pub fn create_per_speaker_pipeline_no_mixer(
     speakers: &[Speaker],
     peq_filters: &BTreeMap<&String, Vec<(usize, &Filter)>>,
+    impulse_filters: &BTreeMap<&String, (i32, &ImpulseResponse)>,
+    shared_filters: &[String],
+    final_filters: &[String],
 ) -> Vec<Pipeline> {
     let mut hold_speakers: BTreeMap<&String, (usize, Vec<usize>)> = BTreeMap::new();
     for (index, s) in speakers.iter().enumerate() {
@@ -17,12 +22,63 @@ pub fn create_per_speaker_pipeline_no_mixer(
             .and_modify(|v| v.0 = index)
             .or_insert((index, vec![]));
     }
-    create_per_speaker_pipeline(&hold_speakers, peq_filters)
+    create_per_speaker_pipeline(
+        &hold_speakers,
+        &[],
+        peq_filters,
+        impulse_filters,
+        shared_filters,
+        final_filters,
+    )
+}
+
+/// enabled PEQ followed by the convolution of a speaker or driver
+fn speaker_filter_names<'a>(
+    speaker: &'a str,
+    peq_filters: &'a BTreeMap<&String, Vec<(usize, &Filter)>>,
+    impulse_filters: &'a BTreeMap<&String, (i32, &ImpulseResponse)>,
+) -> impl Iterator<Item = String> + 'a {
+    peq_filters
+        .iter()
+        .filter(move |(name, _)| name.as_str() == speaker)
+        .flat_map(|(_, peq)| peq.iter())
+        .filter(|(_, filter)| filter.enabled)
+        .map(move |(index, _)| peq_filter_name(speaker, *index))
+        .chain(
+            impulse_filters
+                .keys()
+                .filter(move |name| name.as_str() == speaker)
+                .map(|name| conv_filter_name(name)),
+        )
+}
+
+/// band-pass of a driver followed by the filters of the speaker it belongs to,
+/// which run ahead of the driver's own PEQ
+fn driver_filter_names(
+    driver: &Driver,
+    peq_filters: &BTreeMap<&String, Vec<(usize, &Filter)>>,
+    impulse_filters: &BTreeMap<&String, (i32, &ImpulseResponse)>,
+) -> Vec<String> {
+    driver
+        .highpass
+        .map(|_| driver_highpass_name(&driver.driver))
+        .into_iter()
+        .chain(driver.lowpass.map(|_| driver_lowpass_name(&driver.driver)))
+        .chain(speaker_filter_names(
+            &driver.speaker,
+            peq_filters,
+            impulse_filters,
+        ))
+        .collect()
 }
 
 pub fn create_per_speaker_pipeline(
     output_channel_mapping: &BTreeMap<&String, (usize, Vec<usize>)>,
+    drivers: &[Driver], //outputs named after a driver get its band-pass and its speaker's filters first
     peq_filters: &BTreeMap<&String, Vec<(usize, &Filter)>>,
+    impulse_filters: &BTreeMap<&String, (i32, &ImpulseResponse)>,
+    shared_filters: &[String], //filters applied to every output channel, eg loudness
+    final_filters: &[String],  //applied to every output channel after gain, eg dither
 ) -> Vec<Pipeline> {
     output_channel_mapping
         .iter()
@@ -30,21 +86,21 @@ pub fn create_per_speaker_pipeline(
             Pipeline::Filter(PipelineFilter {
                 pipeline_type: PipelineType::Filter,
                 channel: *i,
-                names: peq_filters
-                    .get(speaker)
-                    .unwrap_or(&vec![])
+                names: drivers
                     .iter()
-                    .map(|(index, _)| peq_filter_name(&speaker, *index))
-                    .chain(std::iter::once(delay_filter_name(&speaker)))
-                    .chain(std::iter::once(gain_filter_name(&speaker)))
+                    .filter(|driver| &&driver.driver == speaker)
+                    .flat_map(|driver| driver_filter_names(driver, peq_filters, impulse_filters))
+                    .chain(speaker_filter_names(speaker, peq_filters, impulse_filters))
+                    .chain(shared_filters.iter().cloned())
+                    .chain(std::iter::once(delay_filter_name(speaker)))
+                    .chain(std::iter::once(gain_filter_name(speaker)))
+                    .chain(final_filters.iter().cloned())
                     .collect(),
             })
         })
         .collect()
 }
 
-/** */
-
 pub fn create_crossover_pipeline(
     split_mixer_name: String,
     combine_mixer_name: String,
@@ -58,22 +114,21 @@ pub fn create_crossover_pipeline(
         input_channel_mapping
             .iter()
             .filter(|(_, (is_crossover, _, _, _))| *is_crossover)
-            .map(|(key, (_, _, _, channel_indeces))| {
+            .flat_map(|(key, (_, _, _, channel_indeces))| {
                 vec![
                     Pipeline::Filter(PipelineFilter {
                         pipeline_type: PipelineType::Filter,
                         channel: channel_indeces[0],
-                        names: vec![crossover_speaker_name(&key)],
+                        names: vec![crossover_speaker_name(key)],
                     }),
                     Pipeline::Filter(PipelineFilter {
                         pipeline_type: PipelineType::Filter,
                         channel: channel_indeces[1],
-                        names: vec![crossover_subwoofer_name(&key)],
+                        names: vec![crossover_subwoofer_name(key)],
                     }),
                 ]
                 .into_iter()
-            })
-            .flatten(),
+            }),
     )
     .chain(std::iter::once(Pipeline::Mixer(PipelineMixer {
         pipeline_type: PipelineType::Mixer,
@@ -82,6 +137,76 @@ pub fn create_crossover_pipeline(
     .collect()
 }
 
+/// filter steps right after the split mixer, shifting the phase of each aligned speaker's own track
+/// ahead of its highpass.  The bass track sent to the subwoofers is left alone
+pub fn create_phase_alignment_pipeline(
+    input_channel_mapping: &BTreeMap<&String, (bool, bool, usize, Vec<usize>)>,
+    phase_alignment: &[PhaseAlignment],
+) -> Vec<Pipeline> {
+    input_channel_mapping
+        .iter()
+        .filter(|(_, (_, is_sub, _, _))| !*is_sub)
+        .filter_map(|(key, (_, _, _, channel_indeces))| {
+            let names: Vec<String> = phase_alignment
+                .iter()
+                .enumerate()
+                .filter(|(_, allpass)| &&allpass.speaker == key)
+                .map(|(index, _)| allpass_filter_name(key, index))
+                .collect();
+            (!names.is_empty()).then(|| {
+                Pipeline::Filter(PipelineFilter {
+                    pipeline_type: PipelineType::Filter,
+                    channel: channel_indeces[0],
+                    names,
+                })
+            })
+        })
+        .collect()
+}
+
+/// filter steps right after the split mixer, delaying the upmixed surrounds and lowpassing the derived LFE
+pub fn create_upmix_pipeline(
+    input_channel_mapping: &BTreeMap<&String, (bool, bool, usize, Vec<usize>)>,
+    speakers: &[Speaker],
+    upmix: &Option<Upmix>,
+) -> Vec<Pipeline> {
+    let Some(upmix) = upmix else {
+        return vec![];
+    };
+    input_channel_mapping
+        .iter()
+        .filter_map(|(key, (_, is_sub, _, channel_indeces))| {
+            let is_surround = speakers.iter().any(|s| {
+                &&s.speaker == key
+                    && matches!(
+                        s.role,
+                        Some(
+                            ChannelRole::RearLeft
+                                | ChannelRole::RearRight
+                                | ChannelRole::SideLeft
+                                | ChannelRole::SideRight
+                        )
+                    )
+            });
+            let name = if *is_sub {
+                upmix.lfe_lowpass.map(|_| upmix_lfe_lowpass_name())
+            } else {
+                is_surround.then(upmix_surround_delay_name)
+            };
+            name.map(|name| (name, channel_indeces))
+        })
+        .flat_map(|(name, channel_indeces)| {
+            channel_indeces.iter().map(move |channel_index| {
+                Pipeline::Filter(PipelineFilter {
+                    pipeline_type: PipelineType::Filter,
+                    channel: *channel_index,
+                    names: vec![name.clone()],
+                })
+            })
+        })
+        .collect()
+}
+
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 enum PipelineType {
@@ -94,7 +219,7 @@ enum PipelineType {
 pub struct PipelineFilter {
     #[serde(rename = "type")]
     pipeline_type: PipelineType,
-    channel: usize,
+    pub(crate) channel: usize,
     pub(crate) names: Vec<String>, //these are keys in the Filter hashmap
 }
 
@@ -103,7 +228,7 @@ pub struct PipelineFilter {
 pub struct PipelineMixer {
     #[serde(rename = "type")]
     pipeline_type: PipelineType,
-    name: String,
+    pub(crate) name: String,
 }
 
 #[derive(Serialize)]
@@ -116,10 +241,15 @@ pub enum Pipeline {
 
 #[cfg(test)]
 mod tests {
-    use super::{create_crossover_pipeline, create_per_speaker_pipeline, Pipeline};
+    use super::{
+        create_crossover_pipeline, create_per_speaker_pipeline, create_phase_alignment_pipeline,
+        create_upmix_pipeline, Pipeline,
+    };
     use crate::filters::compute_peq_filter;
     use crate::pipeline::create_per_speaker_pipeline_no_mixer;
-    use crate::processor::{Filter, Speaker};
+    use crate::processor::{
+        AllpassOrder, ChannelRole, CrossoverFamily, Driver, Filter, PhaseAlignment, Speaker, Upmix,
+    };
     use std::collections::BTreeMap;
     #[test]
     fn check_create_pipeline() {
@@ -141,6 +271,94 @@ mod tests {
         assert!(result.len() == 8); //2*(4-1) for cxfilters+2 for mixer
     }
     #[test]
+    fn check_create_pipeline_phase_alignment() {
+        let mut input_channel_mapping: BTreeMap<&String, (bool, bool, usize, Vec<usize>)> =
+            BTreeMap::new();
+        let l = "l".to_string();
+        let r = "r".to_string();
+        let sub = "subwoofer_input_0".to_string();
+        input_channel_mapping.insert(&l, (true, false, 0, vec![0, 1]));
+        //a speaker with its crossover bypassed still has its own track
+        input_channel_mapping.insert(&r, (false, false, 1, vec![2]));
+        input_channel_mapping.insert(&sub, (false, true, 2, vec![3]));
+        let allpass = |speaker: &str| PhaseAlignment {
+            speaker: speaker.to_string(),
+            freq: 80,
+            order: AllpassOrder::Second,
+            q: 0.707,
+        };
+        let result = create_phase_alignment_pipeline(
+            &input_channel_mapping,
+            &[allpass("l"), allpass("r"), allpass("l")],
+        );
+        let steps: Vec<(usize, Vec<String>)> = result
+            .into_iter()
+            .map(|p| match p {
+                Pipeline::Filter(f) => (f.channel, f.names),
+                Pipeline::Mixer(_) => unreachable!(), //should not get here
+            })
+            .collect();
+        //the bass track sent to the subwoofers is not shifted
+        assert_eq!(
+            steps,
+            vec![
+                (
+                    0,
+                    vec!["allpass_l_0".to_string(), "allpass_l_2".to_string()]
+                ),
+                (2, vec!["allpass_r_1".to_string()])
+            ]
+        );
+    }
+    #[test]
+    fn check_create_upmix_pipeline() {
+        let mut input_channel_mapping: BTreeMap<&String, (bool, bool, usize, Vec<usize>)> =
+            BTreeMap::new();
+        let l = "l".to_string();
+        let sl = "sl".to_string();
+        let sub = "subwoofer_input_0".to_string();
+        input_channel_mapping.insert(&l, (true, false, 0, vec![0, 1]));
+        input_channel_mapping.insert(&sl, (true, false, 2, vec![2, 3]));
+        input_channel_mapping.insert(&sub, (false, true, 1, vec![4]));
+        let speaker = |name: &str, role: ChannelRole| Speaker {
+            speaker: name.to_string(),
+            crossover: Some(80),
+            delay: 0.0,
+            is_subwoofer: false,
+            gain: 0.0,
+            role: Some(role),
+            ..Default::default()
+        };
+        let speakers = vec![
+            speaker("l", ChannelRole::FrontLeft),
+            speaker("sl", ChannelRole::SideLeft),
+        ];
+        let upmix = Some(Upmix {
+            center_gain: -3.0,
+            surround_gain: -3.0,
+            surround_delay: 12.0,
+            lfe_gain: -3.0,
+            lfe_lowpass: Some(80),
+        });
+        let steps: Vec<(usize, Vec<String>)> =
+            create_upmix_pipeline(&input_channel_mapping, &speakers, &upmix)
+                .into_iter()
+                .map(|p| match p {
+                    Pipeline::Filter(f) => (f.channel, f.names),
+                    Pipeline::Mixer(_) => unreachable!(), //should not get here
+                })
+                .collect();
+        assert_eq!(
+            steps,
+            vec![
+                (2, vec!["upmix_surround_delay".to_string()]),
+                (3, vec!["upmix_surround_delay".to_string()]),
+                (4, vec!["upmix_lfe_lowpass".to_string()]),
+            ]
+        );
+        assert!(create_upmix_pipeline(&input_channel_mapping, &speakers, &None).is_empty());
+    }
+    #[test]
     fn check_create_per_speaker_pipeline() {
         let mut output_channel_mapping: BTreeMap<&String, (usize, Vec<usize>)> = BTreeMap::new();
         let l = "l".to_string();
@@ -157,22 +375,31 @@ mod tests {
                 gain: 2.0,
                 q: 0.707,
                 speaker: "l".to_string(),
+                ..Default::default()
             },
             Filter {
                 freq: 2000,
                 gain: 2.0,
                 q: 0.707,
                 speaker: "l".to_string(),
+                ..Default::default()
             },
             Filter {
                 freq: 2000,
                 gain: 1.0,
                 q: 0.707,
                 speaker: "r".to_string(),
+                ..Default::default()
             },
         ];
-        let result =
-            create_per_speaker_pipeline(&output_channel_mapping, &compute_peq_filter(&filters));
+        let result = create_per_speaker_pipeline(
+            &output_channel_mapping,
+            &[],
+            &compute_peq_filter(&filters),
+            &BTreeMap::new(),
+            &[],
+            &[],
+        );
         assert!(result.len() == 4);
         match &result[1] {
             //left, keys are alphabetized
@@ -180,7 +407,7 @@ mod tests {
                 assert!(f.names.len() == 4); //2 peq, 1 gain, 1 delay
             }
             Pipeline::Mixer(_) => {
-                assert!(false); //should not get here
+                unreachable!(); //should not get here
             }
         }
         match &result[0] {
@@ -189,7 +416,7 @@ mod tests {
                 assert!(f.names.len() == 2); //1 gain, 1 delay
             }
             Pipeline::Mixer(_) => {
-                assert!(false); //should not get here
+                unreachable!(); //should not get here
             }
         }
         match &result[2] {
@@ -198,7 +425,7 @@ mod tests {
                 assert!(f.names.len() == 3); //1peq, 1 gain, 1 delay
             }
             Pipeline::Mixer(_) => {
-                assert!(false); //should not get here
+                unreachable!(); //should not get here
             }
         }
         match &result[3] {
@@ -207,7 +434,38 @@ mod tests {
                 assert!(f.names.len() == 2); //1 gain, 1 delay
             }
             Pipeline::Mixer(_) => {
-                assert!(false); //should not get here
+                unreachable!(); //should not get here
+            }
+        }
+    }
+    #[test]
+    fn check_create_per_speaker_pipeline_bypassed_filter() {
+        let mut output_channel_mapping: BTreeMap<&String, (usize, Vec<usize>)> = BTreeMap::new();
+        let l = "l".to_string();
+        output_channel_mapping.insert(&l, (0, vec![0, 1]));
+        let filter = |freq: i32, enabled: bool| Filter {
+            freq,
+            gain: 2.0,
+            q: 0.707,
+            speaker: "l".to_string(),
+            enabled,
+            ..Default::default()
+        };
+        let filters = vec![filter(1000, false), filter(2000, true)];
+        let result = create_per_speaker_pipeline(
+            &output_channel_mapping,
+            &[],
+            &compute_peq_filter(&filters),
+            &BTreeMap::new(),
+            &[],
+            &[],
+        );
+        match &result[0] {
+            Pipeline::Filter(f) => {
+                assert_eq!(f.names, vec!["peq_l_1", "delay_l", "gain_l"]);
+            }
+            Pipeline::Mixer(_) => {
+                unreachable!(); //should not get here
             }
         }
     }
@@ -220,6 +478,7 @@ mod tests {
                 delay: 10.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             Speaker {
                 speaker: "c".to_string(),
@@ -227,6 +486,7 @@ mod tests {
                 delay: 10.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             Speaker {
                 speaker: "r".to_string(),
@@ -234,6 +494,7 @@ mod tests {
                 delay: 10.0,
                 gain: 1.0,
                 is_subwoofer: false,
+                ..Default::default()
             },
             Speaker {
                 speaker: "sub1".to_string(),
@@ -241,6 +502,7 @@ mod tests {
                 delay: 10.0,
                 gain: 1.0,
                 is_subwoofer: true,
+                ..Default::default()
             },
         ];
         let filters = vec![
@@ -249,21 +511,30 @@ mod tests {
                 gain: 2.0,
                 q: 0.707,
                 speaker: "l".to_string(),
+                ..Default::default()
             },
             Filter {
                 freq: 2000,
                 gain: 2.0,
                 q: 0.707,
                 speaker: "l".to_string(),
+                ..Default::default()
             },
             Filter {
                 freq: 2000,
                 gain: 1.0,
                 q: 0.707,
                 speaker: "r".to_string(),
+                ..Default::default()
             },
         ];
-        let result = create_per_speaker_pipeline_no_mixer(&speakers, &compute_peq_filter(&filters));
+        let result = create_per_speaker_pipeline_no_mixer(
+            &speakers,
+            &compute_peq_filter(&filters),
+            &BTreeMap::new(),
+            &[],
+            &[],
+        );
         assert!(result.len() == 4);
         match &result[1] {
             //left, keys are alphabetized
@@ -271,7 +542,7 @@ mod tests {
                 assert!(f.names.len() == 4); //2 peq, 1 gain, 1 delay
             }
             Pipeline::Mixer(_) => {
-                assert!(false); //should not get here
+                unreachable!(); //should not get here
             }
         }
         match &result[0] {
@@ -280,7 +551,7 @@ mod tests {
                 assert!(f.names.len() == 2); //1 gain, 1 delay
             }
             Pipeline::Mixer(_) => {
-                assert!(false); //should not get here
+                unreachable!(); //should not get here
             }
         }
         match &result[2] {
@@ -289,7 +560,7 @@ mod tests {
                 assert!(f.names.len() == 3); //1peq, 1 gain, 1 delay
             }
             Pipeline::Mixer(_) => {
-                assert!(false); //should not get here
+                unreachable!(); //should not get here
             }
         }
         match &result[3] {
@@ -298,7 +569,108 @@ mod tests {
                 assert!(f.names.len() == 2); //1 gain, 1 delay
             }
             Pipeline::Mixer(_) => {
-                assert!(false); //should not get here
+                unreachable!(); //should not get here
+            }
+        }
+    }
+    #[test]
+    fn check_create_per_speaker_pipeline_shared_filters() {
+        let mut output_channel_mapping: BTreeMap<&String, (usize, Vec<usize>)> = BTreeMap::new();
+        let l = "l".to_string();
+        output_channel_mapping.insert(&l, (0, vec![0, 1]));
+        let result = create_per_speaker_pipeline(
+            &output_channel_mapping,
+            &[],
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+            &["loudness".to_string()],
+            &["dither".to_string()],
+        );
+        match &result[0] {
+            Pipeline::Filter(f) => {
+                //shared filters go before delay and gain, final filters after
+                assert_eq!(f.names, vec!["loudness", "delay_l", "gain_l", "dither"]);
+            }
+            Pipeline::Mixer(_) => {
+                unreachable!(); //should not get here
+            }
+        }
+    }
+    #[test]
+    fn check_create_per_speaker_pipeline_drivers() {
+        let mut output_channel_mapping: BTreeMap<&String, (usize, Vec<usize>)> = BTreeMap::new();
+        let woofer = "l_woofer".to_string();
+        let tweeter = "l_tweeter".to_string();
+        output_channel_mapping.insert(&woofer, (0, vec![0]));
+        output_channel_mapping.insert(&tweeter, (1, vec![0]));
+        let driver = |name: &str, highpass: Option<i32>, lowpass: Option<i32>| Driver {
+            speaker: "l".to_string(),
+            driver: name.to_string(),
+            highpass,
+            lowpass,
+            crossover_type: CrossoverFamily::LinkwitzRiley,
+            crossover_order: 4,
+            delay: 0.0,
+            gain: 0.0,
+            inverted: false,
+            output_port: None,
+        };
+        let drivers = vec![
+            driver("l_woofer", None, Some(2000)),
+            driver("l_tweeter", Some(2000), None),
+        ];
+        let peq = |speaker: &str| Filter {
+            freq: 1000,
+            gain: 2.0,
+            q: 0.707,
+            speaker: speaker.to_string(),
+            ..Default::default()
+        };
+        let filters = vec![peq("l"), peq("l_tweeter")];
+        let result = create_per_speaker_pipeline(
+            &output_channel_mapping,
+            &drivers,
+            &compute_peq_filter(&filters),
+            &BTreeMap::new(),
+            &[],
+            &[],
+        );
+        match &result[0] {
+            //tweeter, keys are alphabetized
+            Pipeline::Filter(f) => {
+                assert_eq!(f.channel, 1);
+                //band-pass, then the speaker's PEQ ahead of the driver's own
+                assert_eq!(
+                    f.names,
+                    vec![
+                        "driver_highpass_l_tweeter",
+                        "peq_l_0",
+                        "peq_l_tweeter_1",
+                        "delay_l_tweeter",
+                        "gain_l_tweeter"
+                    ]
+                );
+            }
+            Pipeline::Mixer(_) => {
+                unreachable!(); //should not get here
+            }
+        }
+        match &result[1] {
+            //woofer
+            Pipeline::Filter(f) => {
+                assert_eq!(f.channel, 0);
+                assert_eq!(
+                    f.names,
+                    vec![
+                        "driver_lowpass_l_woofer",
+                        "peq_l_0",
+                        "delay_l_woofer",
+                        "gain_l_woofer"
+                    ]
+                );
+            }
+            Pipeline::Mixer(_) => {
+                unreachable!(); //should not get here
             }
         }
     }
@@ -1,39 +1,295 @@
+use crate::impulses::ImpulseResponse;
 use rocket::serde::{Deserialize, Serialize};
 
 pub struct ProcessorSettingsForCamilla {
     pub filters: Vec<Filter>,
+    pub global_filters: Vec<Filter>,
+    pub phase_alignment: Vec<PhaseAlignment>,
     pub speakers: Vec<Speaker>,
+    pub drivers: Vec<Driver>,
+    pub lfe: LfeInput,
+    pub input_layout: InputLayout,
+    pub custom_layout: Vec<ChannelRole>,
     pub device: DeviceType,
+    pub selected_distance: SelectedDistanceType, //unit of the speaker delays
+    pub impulse_responses: Vec<ImpulseResponse>,
+    pub loudness: Option<Loudness>,
+    pub upmix: Option<Upmix>,
+    pub dither: DitherType,
+    pub headroom_compensation: bool,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde", rename_all = "camelCase")]
 pub struct ProcessorSettings {
     pub filters: Vec<Filter>,
+    #[serde(default)]
+    pub global_filters: Vec<Filter>, //applied to every speaker, eg a house curve
+    #[serde(default)]
+    pub phase_alignment: Vec<PhaseAlignment>,
     pub speakers: Vec<SpeakerForUI>,
+    #[serde(default)]
+    pub drivers: Vec<Driver>, //active multi-way speakers, each driver on its own output
+    #[serde(default)]
+    pub lfe: LfeInput,
+    #[serde(default)]
+    pub input_layout: InputLayout,
+    #[serde(default)]
+    pub custom_layout: Vec<ChannelRole>, //roles in input channel order, for the custom layout
+    #[serde(default)]
+    pub groups: Vec<SpeakerGroup>,
     pub selected_distance: SelectedDistanceType,
     pub device: DeviceType,
+    #[serde(default)]
+    pub loudness: Option<Loudness>,
+    #[serde(default)]
+    pub upmix: Option<Upmix>, //the source is stereo, the other speakers are derived from it
+    #[serde(default)]
+    pub dither: DitherType,
+    #[serde(default)]
+    pub headroom_compensation: bool, //lowers every output by the worst case peak gain
 }
 
-#[derive(sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+/// Volume dependent EQ applied to every output channel; see https://github.com/HEnquist/camilladsp#loudness
+pub struct Loudness {
+    pub reference_level: f32, //volume in dB at which no boost is applied
+    pub high_boost: f32, //boost in dB at the treble end once the volume is 20dB below reference
+    pub low_boost: f32,  //boost in dB at the bass end once the volume is 20dB below reference
+}
+
+/// 3dB down, so a center fed the same signal from both sides plays at the level of one side
+pub fn default_upmix_gain() -> f32 {
+    -3.0
+}
+
+/// long enough that the surrounds are heard as ambience rather than as part of the fronts
+pub fn default_upmix_surround_delay() -> f32 {
+    12.0
+}
+
+/// LFE derived below the usual subwoofer crossover
+pub fn default_upmix_lfe_lowpass() -> Option<i32> {
+    Some(80)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, sqlx::FromRow)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+/// Derives the speakers of a multichannel version from a stereo source, by speaker role:
+/// the center plays L+R, the surrounds play the difference L-R (R-L on the right) delayed to
+/// decorrelate them from the fronts, and the subwoofers get a lowpassed L+R as the LFE
+pub struct Upmix {
+    #[serde(default = "default_upmix_gain")]
+    pub center_gain: f32, //dB, applied to each of L and R
+    #[serde(default = "default_upmix_gain")]
+    pub surround_gain: f32, //dB, applied to each of L and R
+    #[serde(default = "default_upmix_surround_delay")]
+    pub surround_delay: f32, //ms
+    #[serde(default = "default_upmix_gain")]
+    pub lfe_gain: f32, //dB, applied to each of L and R
+    #[serde(default = "default_upmix_lfe_lowpass")]
+    pub lfe_lowpass: Option<i32>, //no LFE is derived without one, the subwoofers only get the crossed over bass
+}
+
+/// boost most sources expect on the LFE channel, see https://en.wikipedia.org/wiki/Low-frequency_effects
+pub fn default_lfe_gain() -> f32 {
+    10.0
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+/// The source's LFE channel, which is mixed into every subwoofer
+pub struct LfeInput {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool, //sources without an LFE channel only send the crossed over bass to the subwoofers
+    #[serde(default)]
+    pub channel: Option<i32>, //input channel index; after all non-subwoofer speakers when not set
+    #[serde(default = "default_lfe_gain")]
+    pub gain: f32, //dB; 0 for sources that already include the boost
+}
+
+impl Default for LfeInput {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            channel: None,
+            gain: default_lfe_gain(),
+        }
+    }
+}
+
+#[derive(Clone, sqlx::FromRow)]
 pub struct Speaker {
     pub speaker: String,
     pub crossover: Option<i32>,
     pub delay: f32,
     pub gain: f32,
     pub is_subwoofer: bool,
+    pub crossover_type: CrossoverFamily,
+    pub crossover_speaker_order: i32,
+    pub crossover_subwoofer_order: i32,
+    pub impulse_response: Option<String>,
+    pub impulse_response_channel: i32,
+    pub inverted: bool,
+    pub role: Option<ChannelRole>,
+    pub output_port: Option<i32>,
+}
+
+impl Default for Speaker {
+    fn default() -> Self {
+        Self {
+            speaker: String::new(),
+            crossover: None,
+            delay: 0.0,
+            gain: 0.0,
+            is_subwoofer: false,
+            crossover_type: CrossoverFamily::default(),
+            crossover_speaker_order: default_crossover_order(),
+            crossover_subwoofer_order: default_crossover_order(),
+            impulse_response: None,
+            impulse_response_channel: 0,
+            inverted: false,
+            role: None,
+            output_port: None,
+        }
+    }
+}
+
+/// What a source channel carries; speakers are mapped to input channels by role
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, sqlx::Type)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum ChannelRole {
+    FrontLeft,
+    FrontRight,
+    Center,
+    Lfe,
+    RearLeft,
+    RearRight,
+    SideLeft,
+    SideRight,
+}
+
+/// Order of the channels in the source.  Other than the speaker order,
+/// speakers are mapped to input channels by their role so reordering them changes nothing
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, sqlx::Type)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum InputLayout {
+    #[default]
+    SpeakerOrder, //the non-subwoofer speakers in the order they are saved, then the LFE
+    Alsa51, //HDMI and ALSA 5.1: FL FR RL RR C LFE
+    Alsa71, //HDMI and ALSA 7.1: FL FR RL RR C LFE SL SR
+    Wave,   //WAVE_FORMAT_EXTENSIBLE: the roles in use, ordered FL FR C LFE RL RR SL SR
+    Custom,
+}
+
+const WAVE_ORDER: [ChannelRole; 8] = [
+    ChannelRole::FrontLeft,
+    ChannelRole::FrontRight,
+    ChannelRole::Center,
+    ChannelRole::Lfe,
+    ChannelRole::RearLeft,
+    ChannelRole::RearRight,
+    ChannelRole::SideLeft,
+    ChannelRole::SideRight,
+];
+
+impl InputLayout {
+    /// roles of the input channels in order; empty for the speaker order, which isn't role based.
+    /// `in_use` are the roles the speakers (and LFE) take, which make up the wave layout
+    pub fn roles(&self, custom_layout: &[ChannelRole], in_use: &[ChannelRole]) -> Vec<ChannelRole> {
+        use ChannelRole::*;
+        match self {
+            InputLayout::SpeakerOrder => vec![],
+            InputLayout::Alsa51 => vec![FrontLeft, FrontRight, RearLeft, RearRight, Center, Lfe],
+            InputLayout::Alsa71 => vec![
+                FrontLeft, FrontRight, RearLeft, RearRight, Center, Lfe, SideLeft, SideRight,
+            ],
+            InputLayout::Wave => WAVE_ORDER
+                .iter()
+                .filter(|role| in_use.contains(role))
+                .cloned()
+                .collect(),
+            InputLayout::Custom => custom_layout.to_vec(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, sqlx::Type)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum CrossoverFamily {
+    #[default]
+    Butterworth,
+    LinkwitzRiley,
+}
+
+/// 4 is 24db/oct, matches the crossover used before the order was configurable
+pub fn default_crossover_order() -> i32 {
+    4
+}
+
+/// butterworth q, the flattest second order all-pass group delay
+pub fn default_allpass_q() -> f32 {
+    0.707
+}
+
+/// filters and crossovers saved before they could be bypassed were always applied
+pub fn default_enabled() -> bool {
+    true
+}
+
+/// Dither added when the playback device takes fewer than 32 bits.
+/// The noise shaped types are tuned for the 96kHz that every device runs at.
+/// Off unless chosen, the same as versions saved before dither was added
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, sqlx::Type)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum DitherType {
+    #[default]
+    None,
+    Flat,
+    Highpass,
+    Shibata96,
 }
 
 #[derive(Serialize, Deserialize, Debug, sqlx::Type)]
 #[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[allow(clippy::upper_case_acronyms)]
 pub enum SelectedDistanceType {
     MS,
     FEET,
     METERS,
+    SAMPLES,     //delay at the device sample rate, passed to camilla as is
+    MILLIMETERS, //distance, passed to camilla as is so nothing is lost converting to ms
+}
+
+pub const MILLIMETERS_PER_MS: f32 = 343.0;
+pub const METERS_PER_MS: f32 = 0.3430;
+pub const FEET_PER_MS: f32 = 1.1164;
+
+impl SelectedDistanceType {
+    /// converts a delay saved for camilla to ms; samples and millimetres are saved unconverted
+    pub fn delay_in_ms(&self, delay: f32, samplerate: i32) -> f32 {
+        match self {
+            SelectedDistanceType::SAMPLES => delay * 1000.0 / samplerate as f32,
+            SelectedDistanceType::MILLIMETERS => delay / MILLIMETERS_PER_MS,
+            _ => delay,
+        }
+    }
+
+    /// longest delay in ms a speaker's distance can turn into; distances are delayed relative
+    /// to the farthest speaker, which is at most the distance itself
+    pub fn distance_in_ms(&self, distance: f32, samplerate: i32) -> f32 {
+        match self {
+            SelectedDistanceType::METERS => distance / METERS_PER_MS,
+            SelectedDistanceType::FEET => distance / FEET_PER_MS,
+            _ => self.delay_in_ms(distance, samplerate),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, sqlx::Type)]
 #[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[allow(clippy::upper_case_acronyms)]
 pub enum DeviceType {
     OktoDac8,
     ToppingDm7,
@@ -49,13 +305,166 @@ pub struct SpeakerForUI {
     pub distance: f32, //contains meters, feet, or milliseconds
     pub gain: f32,
     pub is_subwoofer: bool,
+    #[serde(default)]
+    pub crossover_type: CrossoverFamily,
+    #[serde(default = "default_crossover_order")]
+    pub crossover_speaker_order: i32, //high pass applied to the speaker
+    #[serde(default = "default_crossover_order")]
+    pub crossover_subwoofer_order: i32, //low pass applied to the speaker's bass sent to the subwoofers
+    #[serde(default)]
+    pub impulse_response: Option<String>, //filename of an uploaded impulse, convolved after the PEQ
+    #[serde(default)]
+    pub impulse_response_channel: i32, //channel to read from multichannel wav files
+    #[serde(default)]
+    pub inverted: bool, //polarity; applied to subwoofers as well, to line them up with the mains at the crossover
+    #[serde(default = "default_enabled")]
+    pub crossover_enabled: bool, //a bypassed crossover is kept in the version but the speaker plays full range
+    #[serde(default)]
+    pub target: Option<String>, //name of the target curve the speaker is tuned toward
+    #[serde(default)]
+    pub role: Option<ChannelRole>, //source channel the speaker plays, unless the layout is the speaker order
+    #[serde(default)]
+    pub output_port: Option<i32>, //DAC output; without one the speaker takes the next free output
+}
+
+/// the same values the API fills in for fields left out of a request
+impl Default for SpeakerForUI {
+    fn default() -> Self {
+        Self {
+            speaker: String::new(),
+            crossover: None,
+            distance: 0.0,
+            gain: 0.0,
+            is_subwoofer: false,
+            crossover_type: CrossoverFamily::default(),
+            crossover_speaker_order: default_crossover_order(),
+            crossover_subwoofer_order: default_crossover_order(),
+            impulse_response: None,
+            impulse_response_channel: 0,
+            inverted: false,
+            crossover_enabled: default_enabled(),
+            target: None,
+            role: None,
+            output_port: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+/// Speakers, such as a left and right pair, that share their PEQ, crossover and gain.
+/// Filters whose speaker is the group name are applied to every member
+pub struct SpeakerGroup {
+    pub name: String,
+    pub speakers: Vec<String>,
+    #[serde(default)]
+    pub crossover: Option<i32>, //when set, replaces the crossover of every member
+    #[serde(default)]
+    pub gain: Option<f32>, //when set, replaces the gain of every member
+    #[serde(default = "default_enabled")]
+    pub crossover_enabled: bool, //bypasses the group's crossover; members keep their own when it has none
 }
 
-#[derive(Deserialize, Serialize, sqlx::FromRow)]
-#[serde(crate = "rocket::serde")]
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+/// One driver (woofer, mid, tweeter) of an active multi-way speaker, played on its own output.
+/// The speaker's input is fanned out to each of its drivers, which get their own band-pass,
+/// delay, gain and polarity. Filters whose speaker is the driver name are applied after the speaker's PEQ
+pub struct Driver {
+    pub speaker: String, //the multi-way speaker the driver belongs to
+    pub driver: String,
+    #[serde(default)]
+    pub highpass: Option<i32>, //none for the driver playing the lowest band
+    #[serde(default)]
+    pub lowpass: Option<i32>, //none for the driver playing the highest band
+    #[serde(default)]
+    pub crossover_type: CrossoverFamily,
+    #[serde(default = "default_crossover_order")]
+    pub crossover_order: i32,
+    #[serde(default)]
+    pub delay: f32, //added to the speaker's delay, in the unit of the delay filters (ms, samples or mm)
+    #[serde(default)]
+    pub gain: f32, //added to the speaker's gain
+    #[serde(default)]
+    pub inverted: bool, //flips the polarity relative to the speaker
+    #[serde(default)]
+    pub output_port: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, sqlx::Type)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum AllpassOrder {
+    First,
+    #[default]
+    Second,
+}
+
+#[derive(Deserialize, Serialize, Clone, sqlx::FromRow)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+/// All-pass filter on a speaker's own track right after the split mixer, ahead of its highpass
+/// and leaving the bass sent to the subwoofers unshifted.
+/// Shifts the speaker's phase around the crossover to line it up with the subwoofers,
+/// which delay alone can't do
+pub struct PhaseAlignment {
+    pub speaker: String,
+    pub freq: i32,
+    #[serde(default)]
+    pub order: AllpassOrder,
+    #[serde(default = "default_allpass_q")]
+    pub q: f32, //second order only
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, sqlx::Type)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum PeqType {
+    #[default]
+    Peaking,
+    Lowshelf,
+    Highshelf,
+    Highpass,
+    Lowpass,
+    HighpassFO,
+    LowpassFO,
+    Notch,
+    Bandpass,
+    Allpass,
+    AllpassFO,
+    LinkwitzTransform,
+}
+
+#[derive(Deserialize, Serialize, Clone, sqlx::FromRow)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
 pub struct Filter {
     pub freq: i32,
     pub gain: f32,
     pub q: f32,
-    pub speaker: String,
+    #[serde(default)]
+    pub speaker: String, //not used for global filters
+    #[serde(default)]
+    pub filter_type: PeqType,
+    #[serde(default)]
+    pub slope: Option<f32>, //shelves only, dB per octave; used instead of q when present
+    #[serde(default)]
+    pub freq_target: Option<i32>, //linkwitz transform only; freq and q are the actual speaker values
+    #[serde(default)]
+    pub q_target: Option<f32>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool, //a bypassed filter is kept in the version but left out of the pipeline
+}
+
+/// the same values the API fills in for fields left out of a request
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            freq: 0,
+            gain: 0.0,
+            q: 0.0,
+            speaker: String::new(),
+            filter_type: PeqType::default(),
+            slope: None,
+            freq_target: None,
+            q_target: None,
+            enabled: default_enabled(),
+        }
+    }
 }
@@ -0,0 +1,566 @@
+use crate::filters::{delay_filter_name, output_speakers, SpeakerAdjust};
+use crate::mixers::Mixer;
+use crate::pipeline::{Pipeline, PipelineFilter, PipelineMixer};
+use crate::processor::{CrossoverFamily, ProcessorSettingsForCamilla};
+use rocket::serde::Serialize;
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+const MIN_FREQUENCY: f64 = 10.0;
+const MAX_FREQUENCY: f64 = 24000.0;
+const NUM_FREQUENCIES: usize = 256;
+
+/// Normalized biquad (a0 is 1), using the same formulas as camilla; see
+/// https://github.com/HEnquist/camilladsp/blob/master/src/biquad.rs.
+/// First order sections and plain gains are biquads with the unused coefficients set to 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl Biquad {
+    fn new(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    pub fn gain(gain: f32, inverted: bool) -> Self {
+        let linear = 10.0_f64.powf(gain as f64 / 20.0);
+        Self::new(
+            if inverted { -linear } else { linear },
+            0.0,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+    }
+
+    pub fn peaking(freq: f32, gain: f32, q: f32, samplerate: i32) -> Self {
+        let (sn, cs) = omega(freq, samplerate);
+        let ampl = 10.0_f64.powf(gain as f64 / 40.0);
+        let alpha = sn / (2.0 * q as f64);
+        Self::new(
+            1.0 + alpha * ampl,
+            -2.0 * cs,
+            1.0 - alpha * ampl,
+            1.0 + alpha / ampl,
+            -2.0 * cs,
+            1.0 - alpha / ampl,
+        )
+    }
+
+    /// alpha for shelves, from the slope in dB/octave when given and otherwise from q
+    fn shelf_alpha(sn: f64, ampl: f64, q: f32, slope: Option<f32>) -> f64 {
+        match slope {
+            Some(slope) => {
+                sn / 2.0 * ((ampl + 1.0 / ampl) * (1.0 / (slope as f64 / 12.0) - 1.0) + 2.0).sqrt()
+            }
+            None => sn / (2.0 * q as f64),
+        }
+    }
+
+    pub fn lowshelf(freq: f32, gain: f32, q: f32, slope: Option<f32>, samplerate: i32) -> Self {
+        let (sn, cs) = omega(freq, samplerate);
+        let ampl = 10.0_f64.powf(gain as f64 / 40.0);
+        let beta = 2.0 * ampl.sqrt() * Self::shelf_alpha(sn, ampl, q, slope);
+        Self::new(
+            ampl * ((ampl + 1.0) - (ampl - 1.0) * cs + beta),
+            2.0 * ampl * ((ampl - 1.0) - (ampl + 1.0) * cs),
+            ampl * ((ampl + 1.0) - (ampl - 1.0) * cs - beta),
+            (ampl + 1.0) + (ampl - 1.0) * cs + beta,
+            -2.0 * ((ampl - 1.0) + (ampl + 1.0) * cs),
+            (ampl + 1.0) + (ampl - 1.0) * cs - beta,
+        )
+    }
+
+    pub fn highshelf(freq: f32, gain: f32, q: f32, slope: Option<f32>, samplerate: i32) -> Self {
+        let (sn, cs) = omega(freq, samplerate);
+        let ampl = 10.0_f64.powf(gain as f64 / 40.0);
+        let beta = 2.0 * ampl.sqrt() * Self::shelf_alpha(sn, ampl, q, slope);
+        Self::new(
+            ampl * ((ampl + 1.0) + (ampl - 1.0) * cs + beta),
+            -2.0 * ampl * ((ampl - 1.0) + (ampl + 1.0) * cs),
+            ampl * ((ampl + 1.0) + (ampl - 1.0) * cs - beta),
+            (ampl + 1.0) - (ampl - 1.0) * cs + beta,
+            2.0 * ((ampl - 1.0) - (ampl + 1.0) * cs),
+            (ampl + 1.0) - (ampl - 1.0) * cs - beta,
+        )
+    }
+
+    pub fn highpass(freq: f32, q: f32, samplerate: i32) -> Self {
+        let (sn, cs) = omega(freq, samplerate);
+        let alpha = sn / (2.0 * q as f64);
+        Self::new(
+            (1.0 + cs) / 2.0,
+            -(1.0 + cs),
+            (1.0 + cs) / 2.0,
+            1.0 + alpha,
+            -2.0 * cs,
+            1.0 - alpha,
+        )
+    }
+
+    pub fn lowpass(freq: f32, q: f32, samplerate: i32) -> Self {
+        let (sn, cs) = omega(freq, samplerate);
+        let alpha = sn / (2.0 * q as f64);
+        Self::new(
+            (1.0 - cs) / 2.0,
+            1.0 - cs,
+            (1.0 - cs) / 2.0,
+            1.0 + alpha,
+            -2.0 * cs,
+            1.0 - alpha,
+        )
+    }
+
+    pub fn notch(freq: f32, q: f32, samplerate: i32) -> Self {
+        let (sn, cs) = omega(freq, samplerate);
+        let alpha = sn / (2.0 * q as f64);
+        Self::new(1.0, -2.0 * cs, 1.0, 1.0 + alpha, -2.0 * cs, 1.0 - alpha)
+    }
+
+    pub fn bandpass(freq: f32, q: f32, samplerate: i32) -> Self {
+        let (sn, cs) = omega(freq, samplerate);
+        let alpha = sn / (2.0 * q as f64);
+        Self::new(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cs, 1.0 - alpha)
+    }
+
+    pub fn allpass(freq: f32, q: f32, samplerate: i32) -> Self {
+        let (sn, cs) = omega(freq, samplerate);
+        let alpha = sn / (2.0 * q as f64);
+        Self::new(
+            1.0 - alpha,
+            -2.0 * cs,
+            1.0 + alpha,
+            1.0 + alpha,
+            -2.0 * cs,
+            1.0 - alpha,
+        )
+    }
+
+    pub fn highpass_first_order(freq: f32, samplerate: i32) -> Self {
+        let k = (PI * freq as f64 / samplerate as f64).tan();
+        Self::new(1.0, -1.0, 0.0, k + 1.0, k - 1.0, 0.0)
+    }
+
+    pub fn lowpass_first_order(freq: f32, samplerate: i32) -> Self {
+        let k = (PI * freq as f64 / samplerate as f64).tan();
+        Self::new(k, k, 0.0, k + 1.0, k - 1.0, 0.0)
+    }
+
+    pub fn allpass_first_order(freq: f32, samplerate: i32) -> Self {
+        let k = (PI * freq as f64 / samplerate as f64).tan();
+        Self::new(k - 1.0, k + 1.0, 0.0, k + 1.0, k - 1.0, 0.0)
+    }
+
+    pub fn linkwitz_transform(
+        freq_act: f32,
+        q_act: f32,
+        freq_target: f32,
+        q_target: f32,
+        samplerate: i32,
+    ) -> Self {
+        let (freq_act, q_act) = (freq_act as f64, q_act as f64);
+        let (freq_target, q_target) = (freq_target as f64, q_target as f64);
+        let d0i = (2.0 * PI * freq_target).powi(2);
+        let d1i = (2.0 * PI * freq_target) / q_target;
+        let c0i = (2.0 * PI * freq_act).powi(2);
+        let c1i = (2.0 * PI * freq_act) / q_act;
+        let fc = (freq_target + freq_act) / 2.0;
+        let gn = 2.0 * PI * fc / (PI * fc / samplerate as f64).tan();
+        Self::new(
+            d0i + gn * d1i + gn.powi(2),
+            2.0 * (d0i - gn.powi(2)),
+            d0i - gn * d1i + gn.powi(2),
+            c0i + gn * c1i + gn.powi(2),
+            2.0 * (c0i - gn.powi(2)),
+            c0i - gn * c1i + gn.powi(2),
+        )
+    }
+
+    /// complex response (real, imaginary) at the frequency, along with j*dH/dw / H: its real part
+    /// is the group delay in samples and it adds up over a chain of filters
+    pub(crate) fn evaluate(&self, freq: f64, samplerate: i32) -> ((f64, f64), (f64, f64)) {
+        let w = 2.0 * PI * freq / samplerate as f64;
+        //polynomial in z^-1 evaluated at e^{jw}, along with sum of k*p_k*z^-k for the group delay
+        let polynomial = |p: [f64; 3]| {
+            p.iter().enumerate().fold(
+                ((0.0, 0.0), (0.0, 0.0)),
+                |((re, im), (dre, dim)), (k, c)| {
+                    let (s, co) = (w * k as f64).sin_cos();
+                    (
+                        (re + c * co, im - c * s),
+                        (dre + k as f64 * c * co, dim - k as f64 * c * s),
+                    )
+                },
+            )
+        };
+        let (num, num_derivative) = polynomial([self.b0, self.b1, self.b2]);
+        let (den, den_derivative) = polynomial([1.0, self.a1, self.a2]);
+        //j*dP/dw / P of a polynomial is sum k*p_k*z^-k / sum p_k*z^-k
+        let delay = |p: (f64, f64), d: (f64, f64)| divide(d, p);
+        let group_delay = if num.0 == 0.0 && num.1 == 0.0 {
+            (0.0, 0.0)
+        } else {
+            let (num_delay, den_delay) = (delay(num, num_derivative), delay(den, den_derivative));
+            (num_delay.0 - den_delay.0, num_delay.1 - den_delay.1)
+        };
+        (divide(num, den), group_delay)
+    }
+}
+
+/// sine and cosine of the normalized angular frequency
+fn omega(freq: f32, samplerate: i32) -> (f64, f64) {
+    (2.0 * PI * freq as f64 / samplerate as f64).sin_cos()
+}
+
+/// q of each biquad in a butterworth filter of the given order; None is a first order section
+pub fn butterworth_sections(order: i32) -> Vec<Option<f32>> {
+    let n = order as f32;
+    (1..=order / 2)
+        .map(|k| {
+            let angle = (2 * k - 1) as f32 * std::f32::consts::PI / (2.0 * n);
+            Some(1.0 / (2.0 * angle.sin()))
+        })
+        .chain((order % 2 == 1).then_some(None))
+        .collect()
+}
+
+/// sections of a crossover filter; linkwitz riley is two butterworth filters of half the order
+pub fn crossover_sections(family: CrossoverFamily, order: i32) -> Vec<Option<f32>> {
+    match family {
+        CrossoverFamily::Butterworth => butterworth_sections(order),
+        CrossoverFamily::LinkwitzRiley => {
+            let half = butterworth_sections(order / 2);
+            half.iter().chain(half.iter()).cloned().collect()
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub struct ChannelResponse {
+    pub speaker: String,
+    pub delay: f32, //ms, left out of the phase and group delay so they stay readable
+    pub magnitude: Vec<f32>, //dB
+    pub phase: Vec<f32>, //degrees
+    pub group_delay: Vec<f32>, //ms
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<Vec<f32>>, //dB, the speaker's target curve at the same frequencies
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+/// Response of each output channel, evaluated at `frequencies`
+pub struct FrequencyResponse {
+    pub samplerate: i32,
+    pub frequencies: Vec<f32>,
+    pub channels: Vec<ChannelResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub struct Headroom {
+    pub peak_gains: Vec<f32>, //dB per output channel, before any compensation
+    pub applied: f32,         //dB taken off every output channel
+}
+
+/// log spaced frequencies from 10Hz up to 24kHz or nyquist, whichever is lower
+fn log_frequencies(samplerate: i32) -> Vec<f64> {
+    let max_frequency = MAX_FREQUENCY.min(samplerate as f64 / 2.0);
+    let step = (max_frequency / MIN_FREQUENCY).ln() / (NUM_FREQUENCIES - 1) as f64;
+    (0..NUM_FREQUENCIES)
+        .map(|i| MIN_FREQUENCY * (step * i as f64).exp())
+        .collect()
+}
+
+/// Evaluates the pipeline camilla receives at every speaker, or every driver of a multi-way speaker,
+/// with the same signal on every input so mixed in bass, LFE and upmixed channels are included.
+/// Convolution, loudness and dither are not included, and the delay is reported on its own
+pub fn compute_response(
+    pipeline: &[Pipeline],
+    filters: &BTreeMap<String, SpeakerAdjust>,
+    mixers: &BTreeMap<String, Mixer>,
+    input_channels: usize,
+    settings: &ProcessorSettingsForCamilla,
+    samplerate: i32,
+) -> FrequencyResponse {
+    let frequencies = log_frequencies(samplerate);
+    let (values, derivatives) = pipeline_response(
+        pipeline,
+        filters,
+        mixers,
+        input_channels,
+        &frequencies,
+        samplerate,
+    );
+    let channels = output_speakers(&settings.speakers, &settings.drivers)
+        .into_iter()
+        .filter_map(|output| {
+            //the output's channel is the one its delay runs on
+            let delay_name = delay_filter_name(&output.speaker);
+            let channel = pipeline.iter().find_map(|step| match step {
+                Pipeline::Filter(PipelineFilter { channel, names, .. })
+                    if names.contains(&delay_name) =>
+                {
+                    Some(*channel)
+                }
+                _ => None,
+            })?;
+            let (magnitude, (phase, group_delay)) = (0..frequencies.len())
+                .map(|i| {
+                    let sum = |responses: &[Vec<(f64, f64)>]| {
+                        responses.iter().fold((0.0, 0.0), |sum, input| {
+                            (sum.0 + input[i].0, sum.1 + input[i].1)
+                        })
+                    };
+                    let (re, im) = sum(&values[channel]);
+                    let samples = if re == 0.0 && im == 0.0 {
+                        0.0
+                    } else {
+                        divide(sum(&derivatives[channel]), (re, im)).0
+                    };
+                    (
+                        //silent channels are floored rather than reported as -inf
+                        (20.0 * (re * re + im * im).sqrt().max(1e-10).log10()) as f32,
+                        (
+                            im.atan2(re).to_degrees() as f32,
+                            (samples * 1000.0 / samplerate as f64) as f32,
+                        ),
+                    )
+                })
+                .unzip();
+            Some(ChannelResponse {
+                delay: settings
+                    .selected_distance
+                    .delay_in_ms(output.delay, samplerate),
+                speaker: output.speaker,
+                magnitude,
+                phase,
+                group_delay,
+                target: None,
+            })
+        })
+        .collect();
+    FrequencyResponse {
+        samplerate,
+        frequencies: frequencies.iter().map(|f| *f as f32).collect(),
+        channels,
+    }
+}
+
+/// multiplies complex numbers stored as (real, imaginary)
+fn multiply(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+/// response from each input to each channel at each frequency, as complex (real, imaginary)
+type ChannelResponses = Vec<Vec<Vec<(f64, f64)>>>;
+
+/// divides complex numbers stored as (real, imaginary)
+fn divide(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    let norm = b.0 * b.0 + b.1 * b.1;
+    (
+        (a.0 * b.0 + a.1 * b.1) / norm,
+        (a.1 * b.0 - a.0 * b.1) / norm,
+    )
+}
+
+/// routes the response of every input through a mixer
+fn mix(channels: &[Vec<Vec<(f64, f64)>>], mixer: &Mixer) -> ChannelResponses {
+    let silent = channels
+        .first()
+        .map(|inputs| vec![vec![(0.0, 0.0); inputs[0].len()]; inputs.len()])
+        .unwrap_or_default();
+    let mut mixed = vec![silent; mixer.channels.num_out_channel];
+    for mapping in mixer.mapping.iter() {
+        for source in mapping.sources.iter() {
+            let gain = 10.0_f64.powf(source.gain as f64 / 20.0);
+            let gain = if source.inverted { -gain } else { gain };
+            if let (Some(from), Some(to)) =
+                (channels.get(source.channel), mixed.get_mut(mapping.dest))
+            {
+                for (to_input, from_input) in to.iter_mut().zip(from.iter()) {
+                    for (to_value, from_value) in to_input.iter_mut().zip(from_input.iter()) {
+                        to_value.0 += gain * from_value.0;
+                        to_value.1 += gain * from_value.1;
+                    }
+                }
+            }
+        }
+    }
+    mixed
+}
+
+/// Response from each input to each channel of a camilla pipeline, at each frequency.
+/// Along with the response, j*dH/dw is carried through the pipeline so the group delay
+/// of paths summed by a mixer can be found from the ratio of the two
+fn pipeline_response(
+    pipeline: &[Pipeline],
+    filters: &BTreeMap<String, SpeakerAdjust>,
+    mixers: &BTreeMap<String, Mixer>,
+    input_channels: usize,
+    frequencies: &[f64],
+    samplerate: i32,
+) -> (ChannelResponses, ChannelResponses) {
+    let mut values: ChannelResponses = (0..input_channels)
+        .map(|channel| {
+            (0..input_channels)
+                .map(|input| {
+                    let value = if input == channel { 1.0 } else { 0.0 };
+                    vec![(value, 0.0); frequencies.len()]
+                })
+                .collect()
+        })
+        .collect();
+    let mut derivatives: ChannelResponses =
+        vec![vec![vec![(0.0, 0.0); frequencies.len()]; input_channels]; input_channels];
+    for step in pipeline {
+        match step {
+            Pipeline::Filter(PipelineFilter { channel, names, .. }) => {
+                let biquads: Vec<Biquad> = names
+                    .iter()
+                    .filter_map(|name| filters.get(name))
+                    .flat_map(|f| f.biquads(samplerate))
+                    .collect();
+                let step_response: Vec<((f64, f64), (f64, f64))> = frequencies
+                    .iter()
+                    .map(|freq| {
+                        biquads
+                            .iter()
+                            .fold(((1.0, 0.0), (0.0, 0.0)), |(value, delay), biquad| {
+                                let (b_value, b_delay) = biquad.evaluate(*freq, samplerate);
+                                (
+                                    multiply(value, b_value),
+                                    (delay.0 + b_delay.0, delay.1 + b_delay.1),
+                                )
+                            })
+                    })
+                    .collect();
+                if let (Some(inputs), Some(input_derivatives)) =
+                    (values.get_mut(*channel), derivatives.get_mut(*channel))
+                {
+                    for (input, input_derivative) in inputs.iter_mut().zip(input_derivatives) {
+                        for ((value, derivative), (step_value, step_delay)) in input
+                            .iter_mut()
+                            .zip(input_derivative.iter_mut())
+                            .zip(step_response.iter())
+                        {
+                            //product rule: (H*F)' = H'*F + H*F*(F'/F)
+                            let shifted = multiply(*value, *step_value);
+                            let from_step = multiply(shifted, *step_delay);
+                            let from_value = multiply(*derivative, *step_value);
+                            *derivative = (from_value.0 + from_step.0, from_value.1 + from_step.1);
+                            *value = shifted;
+                        }
+                    }
+                }
+            }
+            Pipeline::Mixer(PipelineMixer { name, .. }) => {
+                if let Some(mixer) = mixers.get(name) {
+                    values = mix(&values, mixer);
+                    derivatives = mix(&derivatives, mixer);
+                }
+            }
+        }
+    }
+    (values, derivatives)
+}
+
+/// Worst case gain in dB of every output channel of a camilla configuration:
+/// every input at full scale, in phase, at the frequency where the paths into the channel sum highest.
+/// Delays are applied after mixing so they don't change the result and are left out
+pub fn peak_gains(
+    pipeline: &[Pipeline],
+    filters: &BTreeMap<String, SpeakerAdjust>,
+    mixers: &BTreeMap<String, Mixer>,
+    input_channels: usize,
+    output_channels: usize,
+    samplerate: i32,
+) -> Vec<f32> {
+    let frequencies = log_frequencies(samplerate);
+    let (channels, _) = pipeline_response(
+        pipeline,
+        filters,
+        mixers,
+        input_channels,
+        &frequencies,
+        samplerate,
+    );
+    (0..output_channels)
+        .map(|channel| {
+            let peak = channels
+                .get(channel)
+                .map(|inputs| {
+                    (0..frequencies.len())
+                        .map(|i| {
+                            inputs
+                                .iter()
+                                .map(|input| (input[i].0.powi(2) + input[i].1.powi(2)).sqrt())
+                                .sum::<f64>()
+                        })
+                        .fold(0.0, f64::max)
+                })
+                .unwrap_or(0.0);
+            //silent channels are floored rather than reported as -inf
+            (20.0 * peak.max(1e-10).log10()) as f32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{butterworth_sections, crossover_sections, Biquad};
+    use crate::processor::CrossoverFamily;
+
+    fn gain_at(biquads: &[Biquad], freq: f64) -> f64 {
+        biquads.iter().fold(0.0, |db, b| {
+            let ((re, im), _) = b.evaluate(freq, 96000);
+            db + 20.0 * (re * re + im * im).sqrt().log10()
+        })
+    }
+
+    #[test]
+    fn check_butterworth_sections() {
+        let sections = butterworth_sections(3);
+        assert_eq!(sections.len(), 2);
+        assert!((sections[0].unwrap() - 1.0).abs() < 0.001);
+        assert_eq!(sections[1], None);
+        let sections = crossover_sections(CrossoverFamily::LinkwitzRiley, 4);
+        assert_eq!(sections.len(), 2);
+        assert!((sections[0].unwrap() - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.001);
+    }
+    #[test]
+    fn check_peaking_response() {
+        let peaking = Biquad::peaking(1000.0, 6.0, 1.0, 96000);
+        assert!((gain_at(&[peaking], 1000.0) - 6.0).abs() < 0.01);
+        assert!(gain_at(&[peaking], 20.0).abs() < 0.01);
+    }
+    #[test]
+    fn check_crossover_response() {
+        //butterworth is 3dB down at the crossover, linkwitz riley 6dB
+        let butterworth: Vec<Biquad> = butterworth_sections(4)
+            .iter()
+            .map(|q| Biquad::highpass(80.0, q.unwrap(), 96000))
+            .collect();
+        assert!((gain_at(&butterworth, 80.0) + 3.01).abs() < 0.05);
+        let lr: Vec<Biquad> = crossover_sections(CrossoverFamily::LinkwitzRiley, 4)
+            .iter()
+            .map(|q| Biquad::highpass(80.0, q.unwrap(), 96000))
+            .collect();
+        assert!((gain_at(&lr, 80.0) + 6.02).abs() < 0.05);
+        //first order sections are 3dB down as well
+        let first_order = Biquad::lowpass_first_order(80.0, 96000);
+        assert!((gain_at(&[first_order], 80.0) + 3.01).abs() < 0.05);
+    }
+}
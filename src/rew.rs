@@ -0,0 +1,530 @@
+use crate::filters::output_speakers;
+use crate::processor::{
+    CrossoverFamily, Driver, Filter, PeqType, ProcessorSettingsForCamilla, SelectedDistanceType,
+    Speaker,
+};
+use crate::response::crossover_sections;
+
+/// q used by REW and Equalizer APO when a shelf or pass filter does not specify one
+const DEFAULT_Q: f32 = 0.707;
+
+/// Result of reading REW or Equalizer APO filter text
+pub struct ImportedFilters {
+    pub filters: Vec<Filter>,
+    pub unmapped: Vec<String>, //filter lines that are switched on but could not be converted
+}
+
+/// value following a label, eg `Fc 100 Hz` gives 100 for `Fc`
+fn value_after(tokens: &[&str], label: &str) -> Option<f32> {
+    tokens
+        .iter()
+        .position(|t| t.eq_ignore_ascii_case(label))
+        .and_then(|i| tokens.get(i + 1))
+        .and_then(|v| v.parse::<f32>().ok())
+}
+
+/// slope of shelves written as `LSC 6 dB` or `LS 6dB`
+fn shelf_slope(tokens: &[&str]) -> Option<f32> {
+    tokens
+        .first()
+        .and_then(|t| t.trim_end_matches("dB").parse::<f32>().ok())
+}
+
+/// converts the part of a filter line after `ON`, eg `PK Fc 100 Hz Gain -3.0 dB Q 2.00`.
+/// Returns None when the type is unknown or a required parameter is missing
+fn parse_filter(tokens: &[&str], speaker: &str) -> Option<Filter> {
+    let (code, parameters) = tokens.split_first()?;
+    let freq = value_after(parameters, "Fc")?.round() as i32;
+    let gain = value_after(parameters, "Gain");
+    let q = value_after(parameters, "Q");
+    let (filter_type, gain, q, slope) = match code.to_ascii_uppercase().as_str() {
+        "PK" | "PEQ" => (PeqType::Peaking, gain?, q?, None),
+        "LS" | "LSC" => (
+            PeqType::Lowshelf,
+            gain?,
+            q.unwrap_or(DEFAULT_Q),
+            shelf_slope(parameters),
+        ),
+        "HS" | "HSC" => (
+            PeqType::Highshelf,
+            gain?,
+            q.unwrap_or(DEFAULT_Q),
+            shelf_slope(parameters),
+        ),
+        "LP" | "LPQ" => (PeqType::Lowpass, 0.0, q.unwrap_or(DEFAULT_Q), None),
+        "HP" | "HPQ" => (PeqType::Highpass, 0.0, q.unwrap_or(DEFAULT_Q), None),
+        "LP1" => (PeqType::LowpassFO, 0.0, DEFAULT_Q, None),
+        "HP1" => (PeqType::HighpassFO, 0.0, DEFAULT_Q, None),
+        "NO" => (PeqType::Notch, 0.0, q?, None),
+        "BP" => (PeqType::Bandpass, 0.0, q?, None),
+        "AP" => (PeqType::Allpass, 0.0, q?, None),
+        _ => return None,
+    };
+    Some(Filter {
+        freq,
+        gain,
+        q,
+        speaker: speaker.to_string(),
+        filter_type,
+        slope,
+        freq_target: None,
+        q_target: None,
+        enabled: true,
+    })
+}
+
+/// Reads the "Filter Settings" text exported by Room EQ Wizard, or an Equalizer APO config.
+/// Only `Filter` lines are considered; headers, comments and other APO commands are skipped.
+/// Filters that are switched off, or that REW exports as `None`, are skipped as well
+pub fn parse_filter_text(text: &str, speaker: &str) -> ImportedFilters {
+    let mut filters = vec![];
+    let mut unmapped = vec![];
+    for line in text.lines().map(|line| line.trim()) {
+        let Some((label, rest)) = line.split_once(':') else {
+            continue;
+        };
+        if !label.trim_start().starts_with("Filter") {
+            continue;
+        }
+        let tokens: Vec<&str> = rest.split_whitespace().collect();
+        match tokens.split_first() {
+            Some((&"ON", [])) | Some((&"ON", ["None"])) => {}
+            Some((&"ON", filter)) => match parse_filter(filter, speaker) {
+                Some(f) => filters.push(f),
+                None => unmapped.push(line.to_string()),
+            },
+            Some((&"OFF", _)) => {}
+            _ => unmapped.push(line.to_string()),
+        }
+    }
+    ImportedFilters { filters, unmapped }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, FromFormField)]
+pub enum ExportFormat {
+    Apo, //Equalizer APO config
+    Rew, //Room EQ Wizard filter settings
+}
+
+/// filter line body without the `Filter n: ON` or `Filter n: OFF` prefix.
+/// Returns None for types that neither REW nor Equalizer APO have
+fn filter_line(filter: &Filter) -> Option<String> {
+    let Filter { freq, gain, q, .. } = filter;
+    match filter.filter_type {
+        PeqType::Peaking => Some(format!("PK Fc {} Hz Gain {:.1} dB Q {:.3}", freq, gain, q)),
+        PeqType::Lowshelf | PeqType::Highshelf => {
+            let code = match filter.filter_type {
+                PeqType::Lowshelf => "LSC",
+                _ => "HSC",
+            };
+            Some(match filter.slope {
+                Some(slope) => format!("{} {} dB Fc {} Hz Gain {:.1} dB", code, slope, freq, gain),
+                None => format!("{} Fc {} Hz Gain {:.1} dB Q {:.3}", code, freq, gain, q),
+            })
+        }
+        PeqType::Highpass => Some(format!("HPQ Fc {} Hz Q {:.3}", freq, q)),
+        PeqType::Lowpass => Some(format!("LPQ Fc {} Hz Q {:.3}", freq, q)),
+        PeqType::HighpassFO => Some(format!("HP1 Fc {} Hz", freq)),
+        PeqType::LowpassFO => Some(format!("LP1 Fc {} Hz", freq)),
+        PeqType::Notch => Some(format!("NO Fc {} Hz Q {:.3}", freq, q)),
+        PeqType::Bandpass => Some(format!("BP Fc {} Hz Q {:.3}", freq, q)),
+        PeqType::Allpass => Some(format!("AP Fc {} Hz Q {:.3}", freq, q)),
+        PeqType::AllpassFO | PeqType::LinkwitzTransform => None,
+    }
+}
+
+/// highpass or lowpass of a crossover, as cascaded biquads
+fn pass_lines(freq: i32, family: CrossoverFamily, order: i32, highpass: bool) -> Vec<String> {
+    let code = if highpass { "HP" } else { "LP" };
+    crossover_sections(family, order)
+        .into_iter()
+        .map(|q| match q {
+            Some(q) => format!("{}Q Fc {} Hz Q {:.3}", code, freq, q),
+            None => format!("{}1 Fc {} Hz", code, freq),
+        })
+        .collect()
+}
+
+/// highpass applied to a speaker that crosses over to the subwoofers
+fn crossover_lines(speaker: &Speaker) -> Vec<String> {
+    match speaker.crossover.filter(|_| !speaker.is_subwoofer) {
+        Some(freq) => pass_lines(
+            freq,
+            speaker.crossover_type,
+            speaker.crossover_speaker_order,
+            true,
+        ),
+        None => vec![],
+    }
+}
+
+/// band-pass of a driver of a multi-way speaker
+fn driver_lines(driver: &Driver) -> Vec<String> {
+    let highpass = driver
+        .highpass
+        .map(|freq| pass_lines(freq, driver.crossover_type, driver.crossover_order, true));
+    let lowpass = driver
+        .lowpass
+        .map(|freq| pass_lines(freq, driver.crossover_type, driver.crossover_order, false));
+    highpass.into_iter().chain(lowpass).flatten().collect()
+}
+
+/// Renders the PEQ of every speaker, or only of `speaker`, as Equalizer APO config or Room EQ Wizard filter text.
+/// A multi-way speaker gets a section per driver, with the speaker's PEQ followed by the driver's.
+/// A REW filter settings file holds a single output, so REW text needs `speaker` to pick one
+/// unless the version only has one.
+/// With `include_speaker_settings` the crossover highpass and driver band-pass are added as filters,
+/// and delay and gain are added as APO commands (REW has no equivalent, so they are noted instead).
+/// Filters without an equivalent are noted rather than dropped silently
+pub fn export_filter_text(
+    settings: &ProcessorSettingsForCamilla,
+    version: i32,
+    format: ExportFormat,
+    include_speaker_settings: bool,
+    speaker: Option<&str>, //a speaker, or a driver of a multi-way speaker
+) -> Result<String, String> {
+    let samplerate = settings.device.samplerate();
+    let delay_in_ms = |speaker: &Speaker| {
+        settings
+            .selected_distance
+            .delay_in_ms(speaker.delay, samplerate)
+    };
+    let mut lines = match format {
+        ExportFormat::Apo => vec![format!("# avprocessor version {}", version)],
+        ExportFormat::Rew => vec![],
+    };
+    //drivers come with the delay, gain and polarity of their speaker added in
+    let outputs: Vec<(&Speaker, Speaker)> = settings
+        .speakers
+        .iter()
+        .flat_map(|speaker| {
+            output_speakers(std::slice::from_ref(speaker), &settings.drivers)
+                .into_iter()
+                .map(move |output| (speaker, output))
+        })
+        .filter(|(s, output)| match speaker {
+            Some(name) => s.speaker == name || output.speaker == name,
+            None => true,
+        })
+        .collect();
+    match (speaker, outputs.len()) {
+        (Some(name), 0) => return Err(format!("Speaker or driver {} does not exist", name)),
+        (_, count) if format == ExportFormat::Rew && count > 1 => {
+            return Err(format!(
+                "REW filter settings hold a single output, pick one of {} with speaker",
+                outputs
+                    .iter()
+                    .map(|(_, output)| output.speaker.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        }
+        _ => {}
+    }
+    for (speaker, output) in outputs {
+        let driver = settings.drivers.iter().find(|d| d.driver == output.speaker);
+        let (filters, skipped): (Vec<_>, Vec<_>) = settings
+            .filters
+            .iter()
+            .filter(|f| f.speaker == speaker.speaker)
+            .chain(
+                settings
+                    .filters
+                    .iter()
+                    .filter(|f| driver.is_some() && f.speaker == output.speaker),
+            )
+            .chain(settings.global_filters.iter())
+            .map(|f| (f, filter_line(f)))
+            .partition(|(_, line)| line.is_some());
+        //bypassed filters are kept, switched off
+        let mut filter_lines: Vec<String> = filters
+            .into_iter()
+            .flat_map(|(f, l)| l.map(|l| format!("{} {}", if f.enabled { "ON" } else { "OFF" }, l)))
+            .collect();
+        if include_speaker_settings {
+            filter_lines.extend(
+                crossover_lines(speaker)
+                    .into_iter()
+                    .chain(driver.map(driver_lines).unwrap_or_default())
+                    .map(|l| format!("ON {}", l)),
+            );
+        }
+        let mut notes: Vec<String> = skipped
+            .iter()
+            .map(|(f, _)| format!("{:?} at {} Hz has no equivalent", f.filter_type, f.freq))
+            .collect();
+        match format {
+            ExportFormat::Apo => {
+                lines.push(String::new());
+                lines.push(format!("Channel: {}", output.speaker.to_uppercase()));
+                if include_speaker_settings {
+                    lines.push(format!("Preamp: {:.1} dB", output.gain));
+                    lines.push(match settings.selected_distance {
+                        SelectedDistanceType::SAMPLES => {
+                            format!("Delay: {:.2} samples", output.delay)
+                        }
+                        _ => format!("Delay: {:.3} ms", delay_in_ms(&output)),
+                    });
+                }
+                lines.extend(notes.iter().map(|n| format!("# {}", n)));
+            }
+            ExportFormat::Rew => {
+                if include_speaker_settings {
+                    notes.push(format!("Delay {:.3} ms", delay_in_ms(&output)));
+                    notes.push(format!("Gain {:.1} dB", output.gain));
+                }
+                lines.extend([
+                    "Filter Settings file".to_string(),
+                    String::new(),
+                    format!("avprocessor version {}", version),
+                    String::new(),
+                    format!("Notes: {}", notes.join(", ")),
+                    String::new(),
+                    "Equaliser: Generic".to_string(),
+                    output.speaker.clone(),
+                ]);
+            }
+        }
+        lines.extend(
+            filter_lines
+                .iter()
+                .enumerate()
+                .map(|(i, l)| format!("Filter {}: {}", i + 1, l)),
+        );
+        if format == ExportFormat::Rew {
+            lines.push(String::new());
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export_filter_text, parse_filter_text, ExportFormat};
+    use crate::processor::{
+        CrossoverFamily, DeviceType, DitherType, Driver, Filter, InputLayout, LfeInput, PeqType,
+        ProcessorSettingsForCamilla, SelectedDistanceType, Speaker,
+    };
+
+    #[test]
+    fn check_rew_filter_text() {
+        let text = "Filter Settings file
+
+Room EQ V5.20.13
+Dated: 18-Oct-2026 09:00:00
+
+Notes:
+
+Equaliser: Generic
+Left
+Filter  1: ON  PK       Fc   63.5 Hz  Gain  -5.0 dB  Q  4.00
+Filter  2: ON  LSC 6 dB Fc   100 Hz  Gain   3.0 dB
+Filter  3: OFF PK       Fc   125 Hz  Gain  -3.2 dB  Q  2.51
+Filter  4: ON  HP       Fc   20.0 Hz
+Filter  5: ON  Modal    Fc   40.0 Hz  Gain  -4.0 dB  T60 target  300 ms
+Filter  6: ON  None
+";
+        let result = parse_filter_text(text, "l");
+        assert_eq!(result.filters.len(), 3);
+        assert_eq!(result.filters[0].freq, 64);
+        assert_eq!(result.filters[0].gain, -5.0);
+        assert_eq!(result.filters[0].q, 4.0);
+        assert_eq!(result.filters[0].filter_type, PeqType::Peaking);
+        assert_eq!(result.filters[0].speaker, "l");
+        assert_eq!(result.filters[1].filter_type, PeqType::Lowshelf);
+        assert_eq!(result.filters[1].slope, Some(6.0));
+        assert_eq!(result.filters[2].filter_type, PeqType::Highpass);
+        assert_eq!(result.filters[2].q, 0.707);
+        assert_eq!(
+            result.unmapped,
+            vec!["Filter  5: ON  Modal    Fc   40.0 Hz  Gain  -4.0 dB  T60 target  300 ms"]
+        );
+    }
+    #[test]
+    fn check_apo_filter_text() {
+        let text = "Preamp: -6.0 dB
+Filter: ON PK Fc 100 Hz Gain -3.0 dB Q 2.00
+Filter: ON HS 12dB Fc 8000 Hz Gain 2.0 dB
+Filter: ON NO Fc 50 Hz
+Filter: ON AP Fc 1000 Hz Q 0.5
+";
+        let result = parse_filter_text(text, "c");
+        assert_eq!(result.filters.len(), 3);
+        assert_eq!(result.filters[1].filter_type, PeqType::Highshelf);
+        assert_eq!(result.filters[1].slope, Some(12.0));
+        assert_eq!(result.filters[2].filter_type, PeqType::Allpass);
+        assert_eq!(result.filters[2].q, 0.5);
+        //notch without a q can't be mapped
+        assert_eq!(result.unmapped, vec!["Filter: ON NO Fc 50 Hz"]);
+    }
+    fn create_settings() -> ProcessorSettingsForCamilla {
+        ProcessorSettingsForCamilla {
+            global_filters: vec![],
+            phase_alignment: vec![],
+            drivers: vec![],
+            lfe: LfeInput::default(),
+            input_layout: InputLayout::SpeakerOrder,
+            custom_layout: vec![],
+            upmix: None,
+            filters: vec![
+                Filter {
+                    freq: 100,
+                    gain: -3.0,
+                    q: 2.0,
+                    speaker: "l".to_string(),
+                    ..Default::default()
+                },
+                Filter {
+                    freq: 30,
+                    gain: 0.0,
+                    q: 0.707,
+                    speaker: "sub1".to_string(),
+                    filter_type: PeqType::LinkwitzTransform,
+                    freq_target: Some(20),
+                    q_target: Some(0.5),
+                    ..Default::default()
+                },
+            ],
+            speakers: vec![
+                Speaker {
+                    speaker: "l".to_string(),
+                    crossover: Some(80),
+                    delay: 1.5,
+                    gain: -2.0,
+                    is_subwoofer: false,
+                    crossover_type: CrossoverFamily::LinkwitzRiley,
+                    ..Default::default()
+                },
+                Speaker {
+                    speaker: "sub1".to_string(),
+                    crossover: None,
+                    delay: 0.0,
+                    gain: 0.0,
+                    is_subwoofer: true,
+                    ..Default::default()
+                },
+            ],
+            device: DeviceType::OktoDac8,
+            selected_distance: SelectedDistanceType::MS,
+            impulse_responses: vec![],
+            loudness: None,
+            dither: DitherType::Highpass,
+            headroom_compensation: false,
+        }
+    }
+    #[test]
+    fn check_apo_export() {
+        assert_eq!(
+            export_filter_text(&create_settings(), 3, ExportFormat::Apo, true, None).unwrap(),
+            "# avprocessor version 3
+
+Channel: L
+Preamp: -2.0 dB
+Delay: 1.500 ms
+Filter 1: ON PK Fc 100 Hz Gain -3.0 dB Q 2.000
+Filter 2: ON HPQ Fc 80 Hz Q 0.707
+Filter 3: ON HPQ Fc 80 Hz Q 0.707
+
+Channel: SUB1
+Preamp: 0.0 dB
+Delay: 0.000 ms
+# LinkwitzTransform at 30 Hz has no equivalent"
+        );
+    }
+    #[test]
+    fn check_rew_export_round_trip() {
+        let settings = create_settings();
+        let text = export_filter_text(&settings, 3, ExportFormat::Rew, false, Some("l")).unwrap();
+        assert_eq!(text.matches("Filter Settings file").count(), 1);
+        let result = parse_filter_text(&text, "l");
+        assert_eq!(result.filters.len(), 1);
+        assert_eq!(result.filters[0].freq, 100);
+        assert_eq!(result.filters[0].gain, -3.0);
+        assert_eq!(result.filters[0].q, 2.0);
+        assert!(result.unmapped.is_empty());
+    }
+    #[test]
+    fn check_export_bypassed_filter() {
+        let mut settings = create_settings();
+        settings.filters[0].enabled = false;
+        let text = export_filter_text(&settings, 3, ExportFormat::Apo, false, None).unwrap();
+        assert!(text.contains("Filter 1: OFF PK Fc 100 Hz Gain -3.0 dB Q 2.000"));
+    }
+    #[test]
+    fn check_export_drivers() {
+        let mut settings = create_settings();
+        let driver = |name: &str, highpass: Option<i32>, lowpass: Option<i32>| Driver {
+            speaker: "l".to_string(),
+            driver: name.to_string(),
+            highpass,
+            lowpass,
+            crossover_type: CrossoverFamily::LinkwitzRiley,
+            crossover_order: 4,
+            delay: 0.5,
+            gain: -1.0,
+            inverted: false,
+            output_port: None,
+        };
+        settings.drivers = vec![
+            driver("l_woofer", None, Some(2000)),
+            driver("l_tweeter", Some(2000), None),
+        ];
+        settings.speakers[0].crossover = None;
+        settings.filters.push(Filter {
+            freq: 5000,
+            gain: 2.0,
+            q: 1.0,
+            speaker: "l_tweeter".to_string(),
+            ..Default::default()
+        });
+        let text = export_filter_text(&settings, 3, ExportFormat::Apo, true, None).unwrap();
+        assert_eq!(
+            text,
+            "# avprocessor version 3
+
+Channel: L_WOOFER
+Preamp: -3.0 dB
+Delay: 2.000 ms
+Filter 1: ON PK Fc 100 Hz Gain -3.0 dB Q 2.000
+Filter 2: ON LPQ Fc 2000 Hz Q 0.707
+Filter 3: ON LPQ Fc 2000 Hz Q 0.707
+
+Channel: L_TWEETER
+Preamp: -3.0 dB
+Delay: 2.000 ms
+Filter 1: ON PK Fc 100 Hz Gain -3.0 dB Q 2.000
+Filter 2: ON PK Fc 5000 Hz Gain 2.0 dB Q 1.000
+Filter 3: ON HPQ Fc 2000 Hz Q 0.707
+Filter 4: ON HPQ Fc 2000 Hz Q 0.707
+
+Channel: SUB1
+Preamp: 0.0 dB
+Delay: 0.000 ms
+# LinkwitzTransform at 30 Hz has no equivalent"
+        );
+        //a driver's REW file reads back with the speaker's PEQ and its own
+        let text =
+            export_filter_text(&settings, 3, ExportFormat::Rew, false, Some("l_tweeter")).unwrap();
+        assert!(text.contains("l_tweeter"));
+        let tweeter = parse_filter_text(&text, "l_tweeter");
+        assert_eq!(tweeter.filters.len(), 2);
+        assert_eq!(tweeter.filters[1].freq, 5000);
+    }
+    #[test]
+    fn check_rew_export_single_output() {
+        let settings = create_settings();
+        assert_eq!(
+            export_filter_text(&settings, 3, ExportFormat::Rew, false, None).unwrap_err(),
+            "REW filter settings hold a single output, pick one of l, sub1 with speaker"
+        );
+        assert_eq!(
+            export_filter_text(&settings, 3, ExportFormat::Apo, false, Some("c")).unwrap_err(),
+            "Speaker or driver c does not exist"
+        );
+        //APO config can hold one speaker too
+        let text =
+            export_filter_text(&settings, 3, ExportFormat::Apo, false, Some("sub1")).unwrap();
+        assert!(!text.contains("Channel: L"));
+        assert!(text.contains("Channel: SUB1"));
+    }
+}
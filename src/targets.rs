@@ -0,0 +1,86 @@
+use rocket::serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct TargetPoint {
+    pub freq: f32,
+    pub gain: f32, //dB, relative; only the shape of the curve matters
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+/// Named response a speaker is tuned toward, eg flat or a house curve; maps to the `target_curves` table
+pub struct TargetCurve {
+    pub name: String,
+    pub points: Vec<TargetPoint>,
+}
+
+impl TargetCurve {
+    /// frequency and level pairs, as used by auto EQ
+    pub fn levels(&self) -> Vec<(f64, f64)> {
+        self.points
+            .iter()
+            .map(|p| (p.freq as f64, p.gain as f64))
+            .collect()
+    }
+}
+
+/// groups rows of (name, freq, gain), ordered by name and point, into curves
+pub fn group_target_points(rows: Vec<(String, f32, f32)>) -> Vec<TargetCurve> {
+    let mut curves: Vec<TargetCurve> = vec![];
+    for (name, freq, gain) in rows {
+        let point = TargetPoint { freq, gain };
+        match curves.last_mut() {
+            Some(curve) if curve.name == name => curve.points.push(point),
+            _ => curves.push(TargetCurve {
+                name,
+                points: vec![point],
+            }),
+        }
+    }
+    curves
+}
+
+/// Target curves need at least two points at increasing, positive frequencies
+pub fn validate_target_curve(name: &str, points: &[TargetPoint]) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Target curve name is required".to_string());
+    }
+    if points.len() < 2 {
+        return Err(format!("Target curve {} needs at least two points", name));
+    }
+    if points[0].freq <= 0.0 || points.windows(2).any(|w| w[1].freq <= w[0].freq) {
+        return Err(format!(
+            "Frequencies of target curve {} must be positive and increasing",
+            name
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{group_target_points, validate_target_curve, TargetPoint};
+
+    #[test]
+    fn check_group_target_points() {
+        let curves = group_target_points(vec![
+            ("flat".to_string(), 20.0, 0.0),
+            ("flat".to_string(), 20000.0, 0.0),
+            ("tilt".to_string(), 20.0, 3.0),
+        ]);
+        assert_eq!(curves.len(), 2);
+        assert_eq!(curves[0].points.len(), 2);
+        assert_eq!(curves[1].name, "tilt");
+        assert_eq!(curves[1].levels(), vec![(20.0, 3.0)]);
+    }
+    #[test]
+    fn check_validate_target_curve() {
+        let point = |freq: f32| TargetPoint { freq, gain: 0.0 };
+        assert!(validate_target_curve("flat", &[point(20.0), point(20000.0)]).is_ok());
+        assert!(validate_target_curve("", &[point(20.0), point(20000.0)]).is_err());
+        assert!(validate_target_curve("flat", &[point(20.0)]).is_err());
+        assert!(validate_target_curve("flat", &[point(100.0), point(20.0)]).is_err());
+        assert!(validate_target_curve("flat", &[point(0.0), point(20.0)]).is_err());
+    }
+}
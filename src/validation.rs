@@ -0,0 +1,1211 @@
+use crate::impulses::{validate_impulse_for_speaker, ImpulseResponse};
+use crate::processor::{
+    AllpassOrder, ChannelRole, CrossoverFamily, Driver, Filter, InputLayout, Loudness, PeqType,
+    PhaseAlignment, ProcessorSettings, SelectedDistanceType, SpeakerForUI, SpeakerGroup,
+};
+use crate::targets::TargetCurve;
+use rocket::serde::Serialize;
+use std::collections::BTreeSet;
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+/// A single problem with a configuration, pointing at the speaker or filter it was found on
+pub struct ValidationError {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter_index: Option<usize>,
+    pub field: String,
+    pub reason: String,
+}
+
+impl ValidationError {
+    fn speaker(speaker: &str, field: &str, reason: String) -> Self {
+        Self {
+            speaker: Some(speaker.to_string()),
+            filter_index: None,
+            field: field.to_string(),
+            reason,
+        }
+    }
+    fn filter(index: usize, filter: &Filter, field: &str, reason: String) -> Self {
+        Self {
+            speaker: Some(filter.speaker.clone()),
+            filter_index: Some(index),
+            field: field.to_string(),
+            reason,
+        }
+    }
+}
+
+/// frequencies have to be above 0 and below nyquist for camilla to build the biquad
+fn check_freq(freq: i32, samplerate: i32) -> Option<String> {
+    if freq <= 0 {
+        Some(format!("Frequency {} must be positive", freq))
+    } else if freq >= samplerate / 2 {
+        Some(format!(
+            "Frequency {} must be below {}, half the sample rate",
+            freq,
+            samplerate / 2
+        ))
+    } else {
+        None
+    }
+}
+
+/// camilla allocates the delay buffer up front, a second covers any room
+const MAX_DELAY_MS: f32 = 1000.0;
+
+/// delays in the unit of the distance type, which camilla can't run backwards
+fn check_delay(
+    delay: f32,
+    selected_distance: &SelectedDistanceType,
+    samplerate: i32,
+) -> Option<String> {
+    let delay_in_ms = selected_distance.distance_in_ms(delay, samplerate);
+    if delay < 0.0 {
+        Some(format!("Distance {} must not be negative", delay))
+    } else if delay_in_ms > MAX_DELAY_MS {
+        Some(format!(
+            "Distance {} is a delay of {:.1} ms, above the {} ms limit",
+            delay, delay_in_ms, MAX_DELAY_MS
+        ))
+    } else {
+        None
+    }
+}
+
+fn check_q(q: f32) -> Option<String> {
+    (q <= 0.0).then(|| format!("Q {} must be positive", q))
+}
+
+/// camilla builds crossovers from up to 8 orders of biquads, and Linkwitz-Riley ones from pairs
+fn check_order(order: i32, crossover_type: CrossoverFamily) -> Option<String> {
+    if !(1..=8).contains(&order) {
+        Some(format!("Order {} must be between 1 and 8", order))
+    } else if crossover_type == CrossoverFamily::LinkwitzRiley && order % 2 == 1 {
+        Some(format!("Linkwitz-Riley order {} must be even", order))
+    } else {
+        None
+    }
+}
+
+fn validate_speaker(
+    speaker: &SpeakerForUI,
+    selected_distance: &SelectedDistanceType,
+    samplerate: i32,
+) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    if let Some(reason) = check_delay(speaker.distance, selected_distance, samplerate) {
+        errors.push(ValidationError::speaker(
+            &speaker.speaker,
+            "distance",
+            reason,
+        ));
+    }
+    if let Some(crossover) = speaker.crossover {
+        if speaker.is_subwoofer {
+            errors.push(ValidationError::speaker(
+                &speaker.speaker,
+                "crossover",
+                "Subwoofers can't have a crossover".to_string(),
+            ));
+        } else if let Some(reason) = check_freq(crossover, samplerate) {
+            errors.push(ValidationError::speaker(
+                &speaker.speaker,
+                "crossover",
+                reason,
+            ));
+        }
+    }
+    for (field, order) in [
+        ("crossoverSpeakerOrder", speaker.crossover_speaker_order),
+        ("crossoverSubwooferOrder", speaker.crossover_subwoofer_order),
+    ] {
+        if let Some(reason) = check_order(order, speaker.crossover_type) {
+            errors.push(ValidationError::speaker(&speaker.speaker, field, reason));
+        }
+    }
+    errors
+}
+
+fn validate_group(
+    group: &SpeakerGroup,
+    speakers: &[SpeakerForUI],
+    samplerate: i32,
+) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    let members: Vec<Option<&SpeakerForUI>> = group
+        .speakers
+        .iter()
+        .map(|name| speakers.iter().find(|s| &s.speaker == name))
+        .collect();
+    for (name, member) in group.speakers.iter().zip(members.iter()) {
+        if member.is_none() {
+            errors.push(ValidationError::speaker(
+                &group.name,
+                "speakers",
+                format!("Speaker {} does not exist", name),
+            ));
+        }
+    }
+    if let Some(crossover) = group.crossover {
+        if members.iter().flatten().any(|s| s.is_subwoofer) {
+            errors.push(ValidationError::speaker(
+                &group.name,
+                "crossover",
+                "Subwoofers can't have a crossover".to_string(),
+            ));
+        } else if let Some(reason) = check_freq(crossover, samplerate) {
+            errors.push(ValidationError::speaker(&group.name, "crossover", reason));
+        }
+    }
+    errors
+}
+
+/// all-pass filters line a speaker's phase up with the subwoofers, so there need to be subwoofers
+/// and the speaker can't be one of them
+fn validate_phase_alignment(
+    index: usize,
+    allpass: &PhaseAlignment,
+    speakers: &[SpeakerForUI],
+    samplerate: i32,
+) -> Vec<ValidationError> {
+    let error = |field: &str, reason: String| ValidationError {
+        speaker: Some(allpass.speaker.clone()),
+        filter_index: Some(index),
+        field: format!("phaseAlignment.{}", field),
+        reason,
+    };
+    let mut errors = vec![];
+    match speakers.iter().find(|s| s.speaker == allpass.speaker) {
+        None => errors.push(error(
+            "speaker",
+            format!("Speaker {} does not exist", allpass.speaker),
+        )),
+        Some(speaker) if speaker.is_subwoofer => errors.push(error(
+            "speaker",
+            format!("Speaker {} is a subwoofer", allpass.speaker),
+        )),
+        Some(_) if !speakers.iter().any(|s| s.is_subwoofer) => errors.push(error(
+            "speaker",
+            format!("There are no subwoofers to align {} with", allpass.speaker),
+        )),
+        _ => {}
+    }
+    if let Some(reason) = check_freq(allpass.freq, samplerate) {
+        errors.push(error("freq", reason));
+    }
+    if allpass.order == AllpassOrder::Second {
+        if let Some(reason) = check_q(allpass.q) {
+            errors.push(error("q", reason));
+        }
+    }
+    errors
+}
+
+/// drivers split a speaker's band, so the speaker can't be a subwoofer
+fn validate_driver(
+    index: usize,
+    driver: &Driver,
+    speakers: &[SpeakerForUI],
+    selected_distance: &SelectedDistanceType,
+    samplerate: i32,
+) -> Vec<ValidationError> {
+    let error = |field: &str, reason: String| ValidationError {
+        speaker: Some(driver.speaker.clone()),
+        filter_index: Some(index),
+        field: format!("drivers.{}", field),
+        reason,
+    };
+    let mut errors = vec![];
+    match speakers.iter().find(|s| s.speaker == driver.speaker) {
+        None => errors.push(error(
+            "speaker",
+            format!("Speaker {} does not exist", driver.speaker),
+        )),
+        Some(speaker) if speaker.is_subwoofer => errors.push(error(
+            "speaker",
+            "Subwoofers can't have drivers".to_string(),
+        )),
+        _ => {}
+    }
+    for (field, freq) in [("highpass", driver.highpass), ("lowpass", driver.lowpass)] {
+        if let Some(reason) = freq.and_then(|f| check_freq(f, samplerate)) {
+            errors.push(error(field, reason));
+        }
+    }
+    if let (Some(highpass), Some(lowpass)) = (driver.highpass, driver.lowpass) {
+        if highpass >= lowpass {
+            errors.push(error(
+                "lowpass",
+                format!(
+                    "Lowpass {} must be above the highpass {}",
+                    lowpass, highpass
+                ),
+            ));
+        }
+    }
+    if let Some(reason) = check_order(driver.crossover_order, driver.crossover_type) {
+        errors.push(error("crossoverOrder", reason));
+    }
+    //added to the speaker's delay, in the same unit
+    if let Some(reason) = check_delay(driver.delay, selected_distance, samplerate) {
+        errors.push(error("delay", reason));
+    }
+    errors
+}
+
+/// the ranges camilla accepts for loudness, see https://github.com/HEnquist/camilladsp#loudness
+fn validate_loudness(loudness: &Loudness) -> Vec<ValidationError> {
+    let error = |field: &str, reason: String| ValidationError {
+        speaker: None,
+        filter_index: None,
+        field: format!("loudness.{}", field),
+        reason,
+    };
+    let mut errors = vec![];
+    if !(-100.0..=20.0).contains(&loudness.reference_level) {
+        errors.push(error(
+            "referenceLevel",
+            format!(
+                "Reference level {} must be between -100 and 20 dB",
+                loudness.reference_level
+            ),
+        ));
+    }
+    for (field, boost) in [
+        ("highBoost", loudness.high_boost),
+        ("lowBoost", loudness.low_boost),
+    ] {
+        if !(0.0..=20.0).contains(&boost) {
+            errors.push(error(
+                field,
+                format!("Boost {} must be between 0 and 20 dB", boost),
+            ));
+        }
+    }
+    errors
+}
+
+fn validate_filter(index: usize, filter: &Filter, samplerate: i32) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    let mut check = |field: &str, reason: Option<String>| {
+        if let Some(reason) = reason {
+            errors.push(ValidationError::filter(index, filter, field, reason));
+        }
+    };
+    check("freq", check_freq(filter.freq, samplerate));
+    match (filter.filter_type, filter.slope) {
+        //first order filters have no q, and shelves use the slope when there is one
+        (PeqType::HighpassFO | PeqType::LowpassFO | PeqType::AllpassFO, _) => {}
+        (PeqType::Lowshelf | PeqType::Highshelf, Some(slope)) => check(
+            "slope",
+            (slope <= 0.0 || slope > 12.0)
+                .then(|| format!("Slope {} must be above 0 and at most 12 dB/octave", slope)),
+        ),
+        _ => check("q", check_q(filter.q)),
+    }
+    if filter.filter_type == PeqType::LinkwitzTransform {
+        check(
+            "freqTarget",
+            match filter.freq_target {
+                Some(freq_target) => check_freq(freq_target, samplerate),
+                None => Some("Linkwitz transforms need a target frequency".to_string()),
+            },
+        );
+        check(
+            "qTarget",
+            match filter.q_target {
+                Some(q_target) => check_q(q_target),
+                None => Some("Linkwitz transforms need a target Q".to_string()),
+            },
+        );
+    }
+    errors
+}
+
+/// with a role based layout every speaker (other than the subwoofers, which play the LFE)
+/// needs a role of its own that is one of the layout's channels
+fn validate_input_layout(settings: &ProcessorSettings) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    if settings.input_layout == InputLayout::SpeakerOrder || settings.upmix.is_some() {
+        return errors;
+    }
+    let layout_error = |reason: String| ValidationError {
+        speaker: None,
+        filter_index: None,
+        field: "customLayout".to_string(),
+        reason,
+    };
+    if settings.input_layout == InputLayout::Custom {
+        if settings.custom_layout.is_empty() {
+            errors.push(layout_error(
+                "The custom layout needs at least one channel".to_string(),
+            ));
+        }
+        for (index, role) in settings.custom_layout.iter().enumerate() {
+            if settings.custom_layout[..index].contains(role) {
+                errors.push(layout_error(format!(
+                    "Role {:?} is listed more than once",
+                    role
+                )));
+            }
+        }
+    }
+    let speakers: Vec<&SpeakerForUI> = settings
+        .speakers
+        .iter()
+        .filter(|s| !s.is_subwoofer)
+        .collect();
+    let roles_in_use: Vec<ChannelRole> = speakers
+        .iter()
+        .filter_map(|s| s.role)
+        .chain(settings.lfe.enabled.then_some(ChannelRole::Lfe))
+        .collect();
+    let layout = settings
+        .input_layout
+        .roles(&settings.custom_layout, &roles_in_use);
+    for (index, speaker) in speakers.iter().enumerate() {
+        let reason = match speaker.role {
+            None => Some(format!(
+                "Speaker {} needs a role to find its input channel",
+                speaker.speaker
+            )),
+            Some(role) if !layout.contains(&role) => {
+                Some(format!("Role {:?} is not part of the input layout", role))
+            }
+            Some(role) if speakers[..index].iter().any(|s| s.role == Some(role)) => {
+                Some(format!("Role {:?} is used by more than one speaker", role))
+            }
+            _ => None,
+        };
+        if let Some(reason) = reason {
+            errors.push(ValidationError::speaker(&speaker.speaker, "role", reason));
+        }
+    }
+    errors
+}
+
+/// an upmixed speaker is fed from the stereo input by its role, so every speaker
+/// (other than the subwoofers, which play the derived LFE) needs one
+fn validate_upmix(settings: &ProcessorSettings, samplerate: i32) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    let Some(upmix) = settings.upmix else {
+        return errors;
+    };
+    let upmix_error = |field: &str, reason: String| ValidationError {
+        speaker: None,
+        filter_index: None,
+        field: field.to_string(),
+        reason,
+    };
+    if let Some(reason) = upmix
+        .lfe_lowpass
+        .and_then(|freq| check_freq(freq, samplerate))
+    {
+        errors.push(upmix_error("upmix.lfeLowpass", reason));
+    }
+    if upmix.surround_delay < 0.0 {
+        errors.push(upmix_error(
+            "upmix.surroundDelay",
+            format!(
+                "Surround delay {} must not be negative",
+                upmix.surround_delay
+            ),
+        ));
+    }
+    for speaker in settings.speakers.iter().filter(|s| !s.is_subwoofer) {
+        let reason = match speaker.role {
+            None => Some(format!(
+                "Speaker {} needs a role to be upmixed",
+                speaker.speaker
+            )),
+            Some(ChannelRole::Lfe) => Some(format!(
+                "Speaker {} can't play the LFE, only subwoofers do",
+                speaker.speaker
+            )),
+            _ => None,
+        };
+        if let Some(reason) = reason {
+            errors.push(ValidationError::speaker(&speaker.speaker, "role", reason));
+        }
+    }
+    errors
+}
+
+/// every output, a speaker or a driver of a multi-way speaker, needs a port of its own
+/// that the playback device has
+fn validate_output_ports(settings: &ProcessorSettings) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    let mut ports = BTreeSet::new();
+    let playback_channels = settings.device.playback_channels();
+    let mut check_port = |output: &str, port: i32| {
+        if port < 0 {
+            Some(format!("Output port {} must not be negative", port))
+        } else if port as usize >= playback_channels {
+            Some(format!(
+                "Output port {} of {} must be below {}, the number of device outputs",
+                port, output, playback_channels
+            ))
+        } else if !ports.insert(port) {
+            Some(format!(
+                "Output port {} is used by more than one output",
+                port
+            ))
+        } else {
+            None
+        }
+    };
+    for speaker in settings.speakers.iter() {
+        if let Some(port) = speaker.output_port {
+            if settings
+                .drivers
+                .iter()
+                .any(|d| d.speaker == speaker.speaker)
+            {
+                errors.push(ValidationError::speaker(
+                    &speaker.speaker,
+                    "outputPort",
+                    format!(
+                        "Speaker {} plays through its drivers, which take the output ports",
+                        speaker.speaker
+                    ),
+                ));
+            }
+            if let Some(reason) = check_port(&speaker.speaker, port) {
+                errors.push(ValidationError::speaker(
+                    &speaker.speaker,
+                    "outputPort",
+                    reason,
+                ));
+            }
+        }
+    }
+    for (index, driver) in settings.drivers.iter().enumerate() {
+        if let Some(reason) = driver
+            .output_port
+            .and_then(|port| check_port(&driver.driver, port))
+        {
+            errors.push(ValidationError {
+                speaker: Some(driver.speaker.clone()),
+                filter_index: Some(index),
+                field: "drivers.outputPort".to_string(),
+                reason,
+            });
+        }
+    }
+    errors
+}
+
+/// impulse responses and target curves are stored apart from the versions using them, so the
+/// caller looks them up; `None` is a speaker's impulse response or target that wasn't found
+pub fn validate_speaker_references(
+    speaker: &SpeakerForUI,
+    impulse: Option<&ImpulseResponse>,
+    target: Option<&TargetCurve>,
+    samplerate: i32,
+) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    if let Some(filename) = &speaker.impulse_response {
+        let reason = match impulse {
+            Some(impulse) => {
+                validate_impulse_for_speaker(impulse, speaker.impulse_response_channel, samplerate)
+                    .err()
+            }
+            None => Some(format!("Impulse response {} not found", filename)),
+        };
+        if let Some(reason) = reason {
+            errors.push(ValidationError::speaker(
+                &speaker.speaker,
+                "impulseResponse",
+                reason,
+            ));
+        }
+    }
+    if let (Some(name), None) = (&speaker.target, target) {
+        errors.push(ValidationError::speaker(
+            &speaker.speaker,
+            "target",
+            format!("Target curve {} not found", name),
+        ));
+    }
+    errors
+}
+
+/// Checks a configuration for anything camilla would reject, or that can't be mapped to a speaker.
+/// Every problem is returned rather than stopping at the first
+pub fn validate_settings(settings: &ProcessorSettings, samplerate: i32) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    if settings.speakers.is_empty() {
+        errors.push(ValidationError {
+            speaker: None,
+            filter_index: None,
+            field: "speakers".to_string(),
+            reason: "At least one speaker is required".to_string(),
+        });
+    }
+    let mut names = BTreeSet::new();
+    for speaker in settings.speakers.iter() {
+        if !names.insert(&speaker.speaker) {
+            errors.push(ValidationError::speaker(
+                &speaker.speaker,
+                "speaker",
+                format!("Speaker {} is listed more than once", speaker.speaker),
+            ));
+        }
+        errors.extend(validate_speaker(
+            speaker,
+            &settings.selected_distance,
+            samplerate,
+        ));
+    }
+    let mut grouped = BTreeSet::new();
+    for group in settings.groups.iter() {
+        if !names.insert(&group.name) {
+            errors.push(ValidationError::speaker(
+                &group.name,
+                "name",
+                format!(
+                    "Group {} has the same name as a speaker or group",
+                    group.name
+                ),
+            ));
+        }
+        for speaker in group.speakers.iter() {
+            if !grouped.insert(speaker) {
+                errors.push(ValidationError::speaker(
+                    &group.name,
+                    "speakers",
+                    format!("Speaker {} is in more than one group", speaker),
+                ));
+            }
+        }
+        errors.extend(validate_group(group, &settings.speakers, samplerate));
+    }
+    //drivers get their own output, so their names can't clash with speakers
+    for (index, driver) in settings.drivers.iter().enumerate() {
+        if !names.insert(&driver.driver) {
+            errors.push(ValidationError {
+                speaker: Some(driver.speaker.clone()),
+                filter_index: Some(index),
+                field: "drivers.driver".to_string(),
+                reason: format!(
+                    "Driver {} has the same name as a speaker, group or driver",
+                    driver.driver
+                ),
+            });
+        }
+        errors.extend(validate_driver(
+            index,
+            driver,
+            &settings.speakers,
+            &settings.selected_distance,
+            samplerate,
+        ));
+    }
+    //filters can be on a speaker, a group or a driver
+    for (index, filter) in settings.filters.iter().enumerate() {
+        if !names.contains(&filter.speaker) {
+            errors.push(ValidationError::filter(
+                index,
+                filter,
+                "speaker",
+                format!("Speaker {} does not exist", filter.speaker),
+            ));
+        }
+        errors.extend(validate_filter(index, filter, samplerate));
+    }
+    for (index, allpass) in settings.phase_alignment.iter().enumerate() {
+        errors.extend(validate_phase_alignment(
+            index,
+            allpass,
+            &settings.speakers,
+            samplerate,
+        ));
+    }
+    errors.extend(validate_input_layout(settings));
+    errors.extend(validate_output_ports(settings));
+    errors.extend(validate_upmix(settings, samplerate));
+    if let Some(loudness) = &settings.loudness {
+        errors.extend(validate_loudness(loudness));
+    }
+    //the LFE sits between or after the non-subwoofer inputs
+    let non_subwoofers = settings.speakers.iter().filter(|s| !s.is_subwoofer).count() as i32;
+    match settings.lfe.channel {
+        Some(channel) if settings.lfe.enabled && (channel < 0 || channel > non_subwoofers) => {
+            errors.push(ValidationError {
+                speaker: None,
+                filter_index: None,
+                field: "lfe.channel".to_string(),
+                reason: format!(
+                    "LFE channel {} must be between 0 and {}, the number of non-subwoofer speakers",
+                    channel, non_subwoofers
+                ),
+            })
+        }
+        _ => {}
+    }
+    for (index, filter) in settings.global_filters.iter().enumerate() {
+        errors.extend(
+            validate_filter(index, filter, samplerate)
+                .into_iter()
+                .map(|e| ValidationError {
+                    speaker: None,
+                    field: format!("globalFilters.{}", e.field),
+                    ..e
+                }),
+        );
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_settings, validate_speaker_references, ValidationError};
+    use crate::impulses::{ImpulseFileType, ImpulseResponse};
+    use crate::processor::{
+        AllpassOrder, ChannelRole, CrossoverFamily, DeviceType, DitherType, Driver, Filter,
+        InputLayout, LfeInput, Loudness, PeqType, PhaseAlignment, ProcessorSettings,
+        SelectedDistanceType, SpeakerForUI, SpeakerGroup, Upmix,
+    };
+    use crate::targets::TargetCurve;
+
+    fn speaker(name: &str, crossover: Option<i32>, is_subwoofer: bool) -> SpeakerForUI {
+        SpeakerForUI {
+            speaker: name.to_string(),
+            crossover,
+            distance: 0.0,
+            gain: 0.0,
+            is_subwoofer,
+            ..Default::default()
+        }
+    }
+
+    fn filter(speaker: &str, freq: i32, q: f32) -> Filter {
+        Filter {
+            freq,
+            gain: 2.0,
+            q,
+            speaker: speaker.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn settings(speakers: Vec<SpeakerForUI>, filters: Vec<Filter>) -> ProcessorSettings {
+        ProcessorSettings {
+            filters,
+            global_filters: vec![],
+            phase_alignment: vec![],
+            drivers: vec![],
+            lfe: LfeInput::default(),
+            input_layout: InputLayout::SpeakerOrder,
+            custom_layout: vec![],
+            upmix: None,
+            speakers,
+            groups: vec![],
+            selected_distance: SelectedDistanceType::MS,
+            device: DeviceType::OktoDac8,
+            loudness: None,
+            dither: DitherType::Highpass,
+            headroom_compensation: false,
+        }
+    }
+
+    #[test]
+    fn test_valid_settings() {
+        let settings = settings(
+            vec![speaker("l", Some(80), false), speaker("sub1", None, true)],
+            vec![filter("l", 1000, 0.707)],
+        );
+        assert!(validate_settings(&settings, 96000).is_empty());
+    }
+    #[test]
+    fn test_empty_speakers() {
+        let errors = validate_settings(&settings(vec![], vec![]), 96000);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "speakers");
+    }
+    #[test]
+    fn test_invalid_speakers() {
+        let mut linkwitz = speaker("r", Some(80), false);
+        linkwitz.crossover_type = CrossoverFamily::LinkwitzRiley;
+        linkwitz.crossover_speaker_order = 3;
+        let mut steep = speaker("c", Some(80), false);
+        steep.crossover_subwoofer_order = 40;
+        let settings = settings(
+            vec![
+                speaker("l", Some(80), false),
+                speaker("l", Some(60000), false),
+                linkwitz,
+                steep,
+                speaker("sub1", Some(80), true),
+            ],
+            vec![],
+        );
+        let errors = validate_settings(&settings, 96000);
+        let fields: Vec<(Option<&str>, &str)> = errors
+            .iter()
+            .map(|e| (e.speaker.as_deref(), e.field.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (Some("l"), "speaker"),
+                (Some("l"), "crossover"),
+                (Some("r"), "crossoverSpeakerOrder"),
+                (Some("c"), "crossoverSubwooferOrder"),
+                (Some("sub1"), "crossover"),
+            ]
+        );
+    }
+    #[test]
+    fn test_invalid_filters() {
+        let mut shelf = filter("l", 100, 0.707);
+        shelf.filter_type = PeqType::Lowshelf;
+        shelf.slope = Some(24.0);
+        let mut linkwitz = filter("l", 40, 0.8);
+        linkwitz.filter_type = PeqType::LinkwitzTransform;
+        let settings = settings(
+            vec![speaker("l", None, false)],
+            vec![
+                filter("l", 1000, -1.0),
+                filter("l", 48000, 1.0),
+                filter("c", 1000, 1.0),
+                shelf,
+                linkwitz,
+            ],
+        );
+        let errors = validate_settings(&settings, 96000);
+        assert_eq!(
+            errors[0],
+            ValidationError {
+                speaker: Some("l".to_string()),
+                filter_index: Some(0),
+                field: "q".to_string(),
+                reason: "Q -1 must be positive".to_string(),
+            }
+        );
+        let fields: Vec<(Option<usize>, &str)> = errors
+            .iter()
+            .map(|e| (e.filter_index, e.field.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (Some(0), "q"),
+                (Some(1), "freq"),
+                (Some(2), "speaker"),
+                (Some(3), "slope"),
+                (Some(4), "freqTarget"),
+                (Some(4), "qTarget")
+            ]
+        );
+    }
+    #[test]
+    fn test_invalid_global_filters() {
+        let mut settings = settings(vec![speaker("l", None, false)], vec![]);
+        settings.global_filters = vec![filter("", 1000, 1.0), filter("", 0, 1.0)];
+        assert_eq!(
+            validate_settings(&settings, 96000),
+            vec![ValidationError {
+                speaker: None,
+                filter_index: Some(1),
+                field: "globalFilters.freq".to_string(),
+                reason: "Frequency 0 must be positive".to_string(),
+            }]
+        );
+    }
+    #[test]
+    fn test_groups() {
+        let group = |name: &str, speakers: &[&str], crossover: Option<i32>| SpeakerGroup {
+            name: name.to_string(),
+            speakers: speakers.iter().map(|s| s.to_string()).collect(),
+            crossover,
+            gain: None,
+            crossover_enabled: true,
+        };
+        let mut settings = settings(
+            vec![
+                speaker("l", None, false),
+                speaker("r", None, false),
+                speaker("sub1", None, true),
+            ],
+            vec![filter("fronts", 1000, 1.0)],
+        );
+        settings.groups = vec![group("fronts", &["l", "r"], Some(80))];
+        assert!(validate_settings(&settings, 96000).is_empty());
+
+        settings.groups = vec![
+            group("fronts", &["l", "r", "c"], Some(80)),
+            group("l", &["r", "sub1"], Some(80)),
+        ];
+        let errors = validate_settings(&settings, 96000);
+        let fields: Vec<(Option<&str>, &str)> = errors
+            .iter()
+            .map(|e| (e.speaker.as_deref(), e.field.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (Some("fronts"), "speakers"),
+                (Some("l"), "name"),
+                (Some("l"), "speakers"),
+                (Some("l"), "crossover"),
+            ]
+        );
+    }
+    #[test]
+    fn test_phase_alignment() {
+        let allpass = |speaker: &str, order: AllpassOrder, q: f32| PhaseAlignment {
+            speaker: speaker.to_string(),
+            freq: 80,
+            order,
+            q,
+        };
+        let mut settings = settings(
+            vec![speaker("l", Some(80), false), speaker("sub1", None, true)],
+            vec![],
+        );
+        settings.phase_alignment = vec![
+            allpass("l", AllpassOrder::Second, 0.707),
+            allpass("l", AllpassOrder::First, 0.0),
+        ];
+        assert!(validate_settings(&settings, 96000).is_empty());
+        //the all-pass runs on the speaker's split track, so it doesn't need a crossover
+        settings.speakers[0].crossover = None;
+        assert!(validate_settings(&settings, 96000).is_empty());
+        //but there have to be subwoofers to line up with
+        let subwoofer = settings.speakers.pop().unwrap();
+        assert_eq!(
+            validate_settings(&settings, 96000)[0].field,
+            "phaseAlignment.speaker"
+        );
+        settings.speakers.push(subwoofer);
+        settings.phase_alignment = vec![
+            allpass("sub1", AllpassOrder::First, 0.707),
+            allpass("l", AllpassOrder::Second, 0.0),
+        ];
+        let fields: Vec<(Option<usize>, String)> = validate_settings(&settings, 96000)
+            .into_iter()
+            .map(|e| (e.filter_index, e.field))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (Some(0), "phaseAlignment.speaker".to_string()),
+                (Some(1), "phaseAlignment.q".to_string()),
+            ]
+        );
+    }
+    #[test]
+    fn test_drivers() {
+        let driver = |name: &str, highpass: Option<i32>, lowpass: Option<i32>| Driver {
+            speaker: "l".to_string(),
+            driver: name.to_string(),
+            highpass,
+            lowpass,
+            crossover_type: CrossoverFamily::LinkwitzRiley,
+            crossover_order: 4,
+            delay: 0.0,
+            gain: 0.0,
+            inverted: false,
+            output_port: None,
+        };
+        let mut settings = settings(
+            vec![speaker("l", None, false), speaker("sub1", None, true)],
+            vec![filter("l_tweeter", 3000, 0.707)],
+        );
+        settings.drivers = vec![
+            driver("l_woofer", None, Some(2000)),
+            driver("l_tweeter", Some(2000), None),
+        ];
+        assert!(validate_settings(&settings, 96000).is_empty());
+        let mut sub_driver = driver("sub1_woofer", None, None);
+        sub_driver.speaker = "sub1".to_string();
+        let mut odd_order = driver("l_mid", Some(300), Some(3000));
+        odd_order.crossover_order = 3;
+        let mut steep = driver("l_sub", None, Some(300));
+        steep.crossover_order = 10;
+        steep.delay = -0.5;
+        settings.drivers = vec![
+            driver("l", Some(3000), Some(300)),
+            sub_driver,
+            odd_order,
+            steep,
+        ];
+        let fields: Vec<(Option<usize>, String)> = validate_settings(&settings, 96000)
+            .into_iter()
+            .map(|e| (e.filter_index, e.field))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (Some(0), "drivers.driver".to_string()),
+                (Some(0), "drivers.lowpass".to_string()),
+                (Some(1), "drivers.speaker".to_string()),
+                (Some(2), "drivers.crossoverOrder".to_string()),
+                (Some(3), "drivers.crossoverOrder".to_string()),
+                (Some(3), "drivers.delay".to_string()),
+                (Some(0), "speaker".to_string()),
+            ]
+        );
+    }
+    #[test]
+    fn test_lfe_channel() {
+        let mut settings = settings(
+            vec![
+                speaker("l", Some(80), false),
+                speaker("r", Some(80), false),
+                speaker("sub1", None, true),
+            ],
+            vec![],
+        );
+        settings.lfe.channel = Some(2);
+        assert!(validate_settings(&settings, 96000).is_empty());
+        settings.lfe.channel = Some(3);
+        let errors = validate_settings(&settings, 96000);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "lfe.channel");
+        settings.lfe.enabled = false;
+        assert!(validate_settings(&settings, 96000).is_empty());
+    }
+    #[test]
+    fn test_input_layout() {
+        let with_role = |name: &str, role: Option<ChannelRole>| SpeakerForUI {
+            role,
+            ..speaker(name, None, false)
+        };
+        let mut settings = settings(
+            vec![
+                with_role("l", Some(ChannelRole::FrontLeft)),
+                with_role("r", Some(ChannelRole::FrontRight)),
+                speaker("sub1", None, true),
+            ],
+            vec![],
+        );
+        settings.input_layout = InputLayout::Alsa51;
+        assert!(validate_settings(&settings, 96000).is_empty());
+        settings.speakers[0].role = None;
+        settings.speakers[1].role = Some(ChannelRole::SideRight);
+        let fields: Vec<(Option<String>, String)> = validate_settings(&settings, 96000)
+            .into_iter()
+            .map(|e| (e.speaker, e.field))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (Some("l".to_string()), "role".to_string()),
+                (Some("r".to_string()), "role".to_string()),
+            ]
+        );
+        settings.input_layout = InputLayout::Custom;
+        settings.custom_layout = vec![ChannelRole::SideRight, ChannelRole::SideRight];
+        settings.speakers[0].role = Some(ChannelRole::SideRight);
+        let reasons: Vec<String> = validate_settings(&settings, 96000)
+            .into_iter()
+            .map(|e| e.reason)
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "Role SideRight is listed more than once",
+                "Role SideRight is used by more than one speaker"
+            ]
+        );
+    }
+    #[test]
+    fn test_upmix() {
+        let with_role = |name: &str, role: Option<ChannelRole>| SpeakerForUI {
+            role,
+            ..speaker(name, None, false)
+        };
+        let mut settings = settings(
+            vec![
+                with_role("l", Some(ChannelRole::FrontLeft)),
+                with_role("r", Some(ChannelRole::FrontRight)),
+                with_role("c", Some(ChannelRole::Center)),
+                with_role("sl", Some(ChannelRole::SideLeft)),
+                speaker("sub1", None, true),
+            ],
+            vec![],
+        );
+        let mut upmix = Upmix {
+            center_gain: -3.0,
+            surround_gain: -3.0,
+            surround_delay: 12.0,
+            lfe_gain: -3.0,
+            lfe_lowpass: Some(80),
+        };
+        settings.upmix = Some(upmix);
+        assert!(validate_settings(&settings, 96000).is_empty());
+        settings.speakers[2].role = None;
+        settings.speakers[3].role = Some(ChannelRole::Lfe);
+        upmix.lfe_lowpass = Some(48000);
+        upmix.surround_delay = -1.0;
+        settings.upmix = Some(upmix);
+        let fields: Vec<(Option<String>, String)> = validate_settings(&settings, 96000)
+            .into_iter()
+            .map(|e| (e.speaker, e.field))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (None, "upmix.lfeLowpass".to_string()),
+                (None, "upmix.surroundDelay".to_string()),
+                (Some("c".to_string()), "role".to_string()),
+                (Some("sl".to_string()), "role".to_string()),
+            ]
+        );
+    }
+    #[test]
+    fn test_speaker_references() {
+        let impulse = ImpulseResponse {
+            filename: "left.wav".to_string(),
+            path: "/tmp/left.wav".to_string(),
+            file_type: ImpulseFileType::Wav,
+            sample_format: None,
+            sample_rate: 48000,
+            channels: 2,
+            length: 4096,
+            upload_date: "".to_string(),
+        };
+        let target = TargetCurve {
+            name: "harman".to_string(),
+            points: vec![],
+        };
+        let mut l = speaker("l", None, false);
+        l.impulse_response = Some("left.wav".to_string());
+        l.impulse_response_channel = 1;
+        l.target = Some("harman".to_string());
+        assert!(validate_speaker_references(&l, Some(&impulse), Some(&target), 48000).is_empty());
+        let reasons: Vec<String> = validate_speaker_references(&l, Some(&impulse), None, 96000)
+            .into_iter()
+            .map(|e| e.reason)
+            .collect();
+        //every problem is listed, not only the first
+        assert_eq!(
+            reasons,
+            vec![
+                "Impulse response left.wav has sample rate 48000 but the device runs at 96000",
+                "Target curve harman not found"
+            ]
+        );
+        let fields: Vec<String> = validate_speaker_references(&l, None, Some(&target), 48000)
+            .into_iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(fields, vec!["impulseResponse"]);
+    }
+    #[test]
+    fn test_output_ports() {
+        let with_port = |name: &str, is_subwoofer: bool, output_port: Option<i32>| SpeakerForUI {
+            output_port,
+            ..speaker(name, None, is_subwoofer)
+        };
+        let mut settings = settings(
+            vec![
+                with_port("l", false, Some(3)),
+                with_port("r", false, None),
+                with_port("sub1", true, Some(0)),
+            ],
+            vec![],
+        );
+        assert!(validate_settings(&settings, 96000).is_empty());
+        settings.speakers[1].output_port = Some(3);
+        settings.speakers[2].output_port = Some(-1);
+        let reasons: Vec<String> = validate_settings(&settings, 96000)
+            .into_iter()
+            .map(|e| e.reason)
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "Output port 3 is used by more than one output",
+                "Output port -1 must not be negative"
+            ]
+        );
+        //the okto has 8 outputs
+        settings.speakers[1].output_port = Some(8);
+        settings.speakers[2].output_port = Some(7);
+        let errors = validate_settings(&settings, 96000);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].speaker, Some("r".to_string()));
+        assert_eq!(
+            errors[0].reason,
+            "Output port 8 of r must be below 8, the number of device outputs"
+        );
+    }
+    #[test]
+    fn test_distances() {
+        let with_distance = |name: &str, distance: f32| SpeakerForUI {
+            distance,
+            ..speaker(name, None, false)
+        };
+        let mut settings = settings(
+            vec![with_distance("l", 3.0), with_distance("r", 300.0)],
+            vec![],
+        );
+        settings.selected_distance = SelectedDistanceType::METERS;
+        let errors = validate_settings(&settings, 96000);
+        assert!(errors.is_empty());
+        //400m is more than a second away
+        settings.speakers[0].distance = -1.0;
+        settings.speakers[1].distance = 400.0;
+        let errors: Vec<(Option<String>, String, String)> = validate_settings(&settings, 96000)
+            .into_iter()
+            .map(|e| (e.speaker, e.field, e.reason))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    Some("l".to_string()),
+                    "distance".to_string(),
+                    "Distance -1 must not be negative".to_string()
+                ),
+                (
+                    Some("r".to_string()),
+                    "distance".to_string(),
+                    "Distance 400 is a delay of 1166.2 ms, above the 1000 ms limit".to_string()
+                ),
+            ]
+        );
+        //samples are at the device sample rate
+        settings.selected_distance = SelectedDistanceType::SAMPLES;
+        settings.speakers[0].distance = 96000.0;
+        settings.speakers[1].distance = 96001.0;
+        let fields: Vec<String> = validate_settings(&settings, 96000)
+            .into_iter()
+            .map(|e| e.speaker.unwrap())
+            .collect();
+        assert_eq!(fields, vec!["r"]);
+    }
+    #[test]
+    fn test_loudness() {
+        let mut settings = settings(vec![speaker("l", None, false)], vec![]);
+        settings.loudness = Some(Loudness {
+            reference_level: -25.0,
+            high_boost: 7.0,
+            low_boost: 7.0,
+        });
+        assert!(validate_settings(&settings, 96000).is_empty());
+        settings.loudness = Some(Loudness {
+            reference_level: 25.0,
+            high_boost: -1.0,
+            low_boost: 21.0,
+        });
+        let fields: Vec<String> = validate_settings(&settings, 96000)
+            .into_iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "loudness.referenceLevel",
+                "loudness.highBoost",
+                "loudness.lowBoost"
+            ]
+        );
+    }
+}